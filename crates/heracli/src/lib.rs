@@ -0,0 +1,198 @@
+//! Command line interface for heraclitus repositories.
+//!
+//! The `hera` binary uses heraclitus' default datatypes. Crates with their
+//! own datatypes can build a CLI for their repositories from a `main` calling
+//! `heracli::run::<MyDatatypes>()`, where `MyDatatypes` is defined with
+//! `datatype_enum!` and an interface controller providing at least the
+//! `Partitioning` and `ContentDiff` interfaces, such as
+//! `DefaultInterfaceController`.
+
+use prettytable::{Table, cell, row,};
+use structopt::StructOpt;
+use url::Url;
+use uuid::Uuid;
+
+use heraclitus::{
+    url,
+    uuid,
+    Identifiable,
+    IdentifiableGraph,
+    ModelError,
+    datatype::{
+        artifact_graph::{
+            ArtifactGraphDtype,
+            Storage,
+        },
+        diff::{
+            ContentDiffController,
+            diff,
+            diff_contents,
+        },
+        partitioning::PartitioningState,
+        DatatypeEnum,
+        DatatypeMarker,
+        DatatypesRegistry,
+        InterfaceController,
+    },
+    repo::{
+        Repository,
+        RepoController,
+    },
+};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "heracli")]
+struct Options {
+    #[structopt(short = "r", long = "repo")]
+    repo: String,
+    /// Datatypes to register, by name. All compiled-in datatypes are
+    /// registered if none are given. The core datatypes for artifact graphs,
+    /// refs and unary partitioning are always registered.
+    #[structopt(long = "datatypes", env = "HERA_DATATYPES", raw(use_delimiter = "true"))]
+    datatypes: Vec<String>,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    #[structopt(name = "init")]
+    Init,
+    #[structopt(name = "ls")]
+    List {
+        #[structopt(long = "origin")]
+        resolve_origin: bool,
+    },
+    /// List registered datatypes.
+    #[structopt(name = "datatypes")]
+    Datatypes,
+    /// Summarize partitions changed between two versions of an artifact.
+    #[structopt(name = "diff")]
+    Diff {
+        /// Version of the artifact graph containing the artifact.
+        #[structopt(name = "GRAPH")]
+        graph: Uuid,
+        #[structopt(name = "FROM")]
+        from: Uuid,
+        #[structopt(name = "TO")]
+        to: Uuid,
+        /// Also show the datatype's diff of each changed partition's content.
+        #[structopt(long = "content")]
+        content: bool,
+    },
+}
+
+/// Build a registry of the datatypes selected in `names`, or all datatypes
+/// of `T` if `names` is empty.
+fn dtypes_registry<T: DatatypeEnum>(names: &[String]) -> Result<DatatypesRegistry<T>, heraclitus::Error> {
+    if names.is_empty() {
+        Ok(DatatypesRegistry::with_all_datatypes())
+    } else {
+        DatatypesRegistry::with_datatypes(names)
+    }
+}
+
+/// Run the CLI with arguments from the environment, using datatypes from `T`.
+pub fn run<T>() -> Result<(), heraclitus::Error>
+        where
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ContentDiffController>
+{
+    let opt = Options::from_args();
+
+    let repo_location = heraclitus::RepositoryLocation {
+        url: Url::parse(&opt.repo).expect("TODO"),
+    };
+    let mut repo = Repository::new(&repo_location);
+    let dtype_registry = dtypes_registry::<T>(&opt.datatypes)?;
+
+    match opt.command {
+        Command::Init => {
+            repo.init(&dtype_registry)?;
+            let mut ag_store = ArtifactGraphDtype::store(&repo);
+            ag_store.get_or_create_origin_root(&dtype_registry, &repo)?;
+        },
+        Command::List {resolve_origin} => {
+            let mut ag_store = ArtifactGraphDtype::store(&repo);
+            let (origin_ag, root_ag) = ag_store.get_or_create_origin_root(&dtype_registry, &repo)?;
+
+            let resolve_root = if resolve_origin {
+                origin_ag
+            } else {
+                root_ag
+            };
+
+            let mut table = Table::new();
+
+            for art_idx in resolve_root.artifacts.graph().node_indices() {
+                let art = &resolve_root.artifacts[art_idx];
+                table.add_row(row![
+                    art.id.uuid,
+                    art.id.hash,
+                    art.name().as_ref().map_or("", String::as_ref),
+                    dtype_registry.get_datatype_by_uuid(&art.dtype_uuid).map_or("", |dtype| dtype.name),
+                ]);
+            }
+
+            table.printstd();
+        },
+        Command::Datatypes => {
+            let mut dtypes: Vec<_> = dtype_registry.iter_dtypes().collect();
+            dtypes.sort_by_key(|dtype| &dtype.name);
+
+            let mut table = Table::new();
+
+            for dtype in dtypes {
+                table.add_row(row![
+                    dtype.id().uuid,
+                    dtype.name,
+                    dtype.version(),
+                ]);
+            }
+
+            table.printstd();
+        },
+        Command::Diff {graph, from, to, content} => {
+            let mut ag_store = ArtifactGraphDtype::store(&repo);
+            let (_, root_ag) = ag_store.get_or_create_origin_root(&dtype_registry, &repo)?;
+
+            let root_ver_graph = ag_store.get_version_graph(&repo, &root_ag)?;
+            let (ag_ver_idx, _) = root_ver_graph.get_by_uuid(&graph)
+                .ok_or_else(|| ModelError::NotFound(graph))?;
+            let art_graph = ag_store.get_artifact_graph(&dtype_registry, &repo, &root_ver_graph, ag_ver_idx)?;
+
+            let ver_graph = ag_store.get_version_graph(&repo, &art_graph)?;
+            let (from_idx, _) = ver_graph.get_by_uuid(&from)
+                .ok_or_else(|| ModelError::NotFound(from))?;
+            let (to_idx, _) = ver_graph.get_by_uuid(&to)
+                .ok_or_else(|| ModelError::NotFound(to))?;
+
+            let mut table = Table::new();
+            table.add_row(row!["Partition", "Change", "From hunks", "To hunks"]);
+
+            for partition_diff in diff(&dtype_registry, &repo, &ver_graph, from_idx, to_idx)? {
+                table.add_row(row![
+                    partition_diff.index,
+                    format!("{:?}", partition_diff.change),
+                    partition_diff.from.len(),
+                    partition_diff.to.len(),
+                ]);
+            }
+
+            table.printstd();
+
+            if content {
+                match diff_contents(&dtype_registry, &repo, &ver_graph, from_idx, to_idx)? {
+                    Some(content_diffs) => for (p_id, content_diff) in content_diffs {
+                        print!("Partition {}:\n{}", p_id, content_diff);
+                    },
+                    None => println!("Datatype has no content diff"),
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
@@ -7,7 +7,7 @@ use enumset::{
 use lazy_static::lazy_static;
 use uuid::Uuid;
 
-use crate::Datatype;
+use crate::{Datatype, Error, ModelError};
 use crate::repo::{Repository, RepoController};
 use crate::store::Backend;
 
@@ -191,6 +191,18 @@ impl DependencyDescription {
             store_restriction,
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn datatype_restriction(&self) -> &DependencyTypeRestriction {
+        &self.datatype_restriction
+    }
+
+    pub fn cardinality_restriction(&self) -> &DependencyCardinalityRestriction {
+        &self.cardinality_restriction
+    }
 }
 
 pub struct InterfaceDescription {
@@ -289,7 +301,27 @@ impl InterfaceRegistry {
     }
 
     pub fn get_index(&self, name: &str) -> crate::InterfaceIndex {
-        *self.ifaces_idx.get(name).expect("Unknown interface")
+        *self.find_index(name).expect("Unknown interface")
+    }
+
+    pub fn find_index(&self, name: &str) -> Option<&crate::InterfaceIndex> {
+        self.ifaces_idx.get(name)
+    }
+
+    /// Whether interface `sub` is or transitively extends interface `sup`.
+    pub fn extends(&self, sub: crate::InterfaceIndex, sup: crate::InterfaceIndex) -> bool {
+        // Extension edges are directed from super to sub interfaces.
+        petgraph::algo::has_path_connecting(self.extension.graph(), sup, sub, None)
+    }
+
+    /// All interfaces that are or transitively extend interface `sup`.
+    pub fn sub_interfaces(&self, sup: crate::InterfaceIndex) -> HashSet<crate::InterfaceIndex> {
+        let mut dfs = petgraph::visit::Dfs::new(self.extension.graph(), sup);
+        let mut subs = HashSet::new();
+        while let Some(idx) = dfs.next(self.extension.graph()) {
+            subs.insert(idx);
+        }
+        subs
     }
 
     pub fn register_interfaces(&mut self, interfaces: &[&InterfaceDescription]) {
@@ -308,6 +340,10 @@ impl InterfaceRegistry {
     }
 }
 
+/// Variant names of datatypes every repository uses: for its artifact
+/// graphs, their refs, and the partitioning of unpartitioned artifacts.
+pub const CORE_DATATYPES: &[&str] = &["ArtifactGraph", "Ref", "UnaryPartitioning"];
+
 pub struct DatatypesRegistry<T: DatatypeEnum> {
     interfaces: InterfaceRegistry,
     dtypes: HashMap<String, Datatype>,
@@ -323,10 +359,82 @@ impl<T: DatatypeEnum> DatatypesRegistry<T> {
         }
     }
 
+    /// Create a registry of all datatypes in `T` and all interfaces of its
+    /// interface controller.
+    pub fn with_all_datatypes() -> DatatypesRegistry<T> {
+        let mut dtypes_registry = DatatypesRegistry::new();
+        dtypes_registry.register_interfaces(&<T as DatatypeEnum>::InterfaceControllerType::all_descriptions());
+        dtypes_registry.register_datatype_models(T::all_variants());
+        dtypes_registry
+    }
+
+    /// Create a registry of datatypes in `T` selected by their variant names,
+    /// and all interfaces of its interface controller.
+    ///
+    /// Those of `CORE_DATATYPES` in `T` are always registered, since
+    /// repositories can not be created or read without them.
+    pub fn with_datatypes<S: AsRef<str>>(names: &[S]) -> Result<DatatypesRegistry<T>, Error> {
+        let mut selected = CORE_DATATYPES.iter()
+            .filter(|&&name| T::variant_names().contains(&name))
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        for name in names {
+            if !selected.iter().any(|s| s == name.as_ref()) {
+                selected.push(name.as_ref().into());
+            }
+        }
+
+        let models = selected.iter()
+            .map(|name| T::from_name(name)
+                .ok_or_else(|| Error::Model(ModelError::UnknownDatatype(name.clone()))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut dtypes_registry = DatatypesRegistry::new();
+        dtypes_registry.register_interfaces(&<T as DatatypeEnum>::InterfaceControllerType::all_descriptions());
+        dtypes_registry.register_datatype_models(models);
+        Ok(dtypes_registry)
+    }
+
     pub fn get_datatype(&self, name: &str) -> Option<&Datatype> {
         self.dtypes.get(name)
     }
 
+    pub fn get_datatype_by_uuid(&self, uuid: &Uuid) -> Option<&Datatype> {
+        self.models.get(uuid).and_then(|model| self.dtypes.get(model.as_model().name()))
+    }
+
+    /// Whether a datatype implements an interface, either directly or by
+    /// implementing an interface that transitively extends it.
+    ///
+    /// Unknown interfaces are implemented by no datatype.
+    pub fn implements_interface(&self, dtype: &Datatype, iface_name: &str) -> bool {
+        match self.interfaces.find_index(iface_name) {
+            Some(&sup) => dtype.implements.iter().any(|&iface| self.interfaces.extends(iface, sup)),
+            None => false,
+        }
+    }
+
+    /// Iterate over datatypes implementing an interface, either directly or
+    /// through a sub-interface.
+    pub fn iter_implementors<'a>(&'a self, iface_name: &'a str) -> impl Iterator<Item = &'a Datatype> {
+        self.dtypes.values().filter(move |dtype| self.implements_interface(dtype, iface_name))
+    }
+
+    /// Whether a datatype may be the source of a dependency with this type
+    /// restriction.
+    pub fn satisfies_type_restriction(
+        &self,
+        dtype: &Datatype,
+        restriction: &DependencyTypeRestriction,
+    ) -> bool {
+        match restriction {
+            DependencyTypeRestriction::Datatype(names) => names.contains(dtype.name),
+            DependencyTypeRestriction::ImplementsInterface(iface_names) =>
+                iface_names.iter().any(|iface_name| self.implements_interface(dtype, iface_name)),
+            DependencyTypeRestriction::Any => true,
+        }
+    }
+
     // TODO: Kludge around Model/Interface controller mess
     // TODO: Unable to implement as Index trait because of trait obj lifetime?
     pub fn get_model<'a>(&self, uuid: &Uuid) -> &(dyn Model<T::InterfaceControllerType> + 'a) {
@@ -388,10 +496,6 @@ pub mod testing {
     }
 
     pub fn init_dtypes_registry<T: DatatypeEnum>() -> DatatypesRegistry<T> {
-        let mut dtypes_registry = DatatypesRegistry::new();
-        dtypes_registry.register_interfaces(&<T as DatatypeEnum>::InterfaceControllerType::all_descriptions());
-        let models = T::all_variants();
-        dtypes_registry.register_datatype_models(models);
-        dtypes_registry
+        DatatypesRegistry::with_all_datatypes()
     }
 }
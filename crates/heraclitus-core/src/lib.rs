@@ -29,7 +29,7 @@ extern crate self as heraclitus;
 
 use std::collections::HashSet;
 use std::collections::hash_map::DefaultHasher;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::hash::{Hash, Hasher};
 use std::io;
 
@@ -58,6 +58,27 @@ pub enum Error {
     TODO(&'static str),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Store(message) => write!(f, "Store error: {}", message),
+            Error::Model(e) => write!(f, "Model error: {}", e),
+            Error::TODO(message) => write!(f, "Unimplemented: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Model(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(io_error: io::Error) -> Self {
         Error::Io(io_error)
@@ -70,20 +91,101 @@ impl From<ModelError> for Error {
     }
 }
 
+/// Errors from violating the invariants of the heraclitus data model, as
+/// opposed to failures of a store.
 #[derive(Debug)]
 pub enum ModelError {
+    /// A relation would introduce a cycle into a graph required to be acyclic.
+    Cycle,
+    /// The identified entity does not exist.
+    NotFound(Uuid),
+    /// No artifact with this UUID exists in the artifact graph.
+    UnknownArtifact(Uuid),
+    /// No datatype with this name is registered.
+    UnknownDatatype(String),
     HashMismatch {
         uuid: Uuid,
         expected: HashType,
         found: HashType,
     },
-    NotFound(Uuid),
+    /// A graph or graph description is internally inconsistent.
+    MalformedGraph(String),
+    InvalidDependencies(Vec<DependencyViolation>),
+    /// A producer cannot produce versions for its inputs or outputs.
+    IncompatibleProducer {
+        producer: Uuid,
+        reason: String,
+    },
     Other(String),
 }
 
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::Cycle => write!(f, "Relation would create a cycle"),
+            ModelError::NotFound(uuid) => write!(f, "Not found: {}", uuid),
+            ModelError::UnknownArtifact(uuid) => write!(f, "Unknown artifact: {}", uuid),
+            ModelError::UnknownDatatype(name) => write!(f, "Unknown datatype: {}", name),
+            ModelError::HashMismatch {uuid, expected, found} =>
+                write!(f, "Hash mismatch for {}: expected {}, found {}", uuid, expected, found),
+            ModelError::MalformedGraph(message) => write!(f, "Malformed graph: {}", message),
+            ModelError::InvalidDependencies(violations) => {
+                write!(f, "Invalid dependencies:")?;
+                for violation in violations {
+                    write!(f, " {};", violation)?;
+                }
+                Ok(())
+            },
+            ModelError::IncompatibleProducer {producer, reason} =>
+                write!(f, "Incompatible producer {}: {}", producer, reason),
+            ModelError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// A dependency relation in an artifact graph that does not match those
+/// declared by the datatypes it relates.
+#[derive(Debug, PartialEq)]
+pub enum DependencyViolation {
+    /// The dependent artifact's datatype does not declare the relation.
+    Undeclared {
+        artifact: Uuid,
+        relation: String,
+    },
+    /// The dependency's datatype is not allowed for the relation.
+    Datatype {
+        artifact: Uuid,
+        relation: String,
+        dependency: Uuid,
+    },
+    /// The number of dependencies for the relation is not allowed.
+    Cardinality {
+        artifact: Uuid,
+        relation: String,
+        count: u64,
+    },
+}
+
+impl fmt::Display for DependencyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DependencyViolation::Undeclared {artifact, relation} =>
+                write!(f, "artifact {} has undeclared relation \"{}\"", artifact, relation),
+            DependencyViolation::Datatype {artifact, relation, dependency} =>
+                write!(f, "artifact {} has relation \"{}\" to {} of a disallowed datatype",
+                    artifact, relation, dependency),
+            DependencyViolation::Cardinality {artifact, relation, count} =>
+                write!(f, "artifact {} has a disallowed number ({}) of relation \"{}\"",
+                    artifact, count, relation),
+        }
+    }
+}
+
 impl<T: Debug> From<daggy::WouldCycle<T>> for Error {
     fn from(_e: daggy::WouldCycle<T>) -> Self {
-        Error::Store("TODO: Daggy cycle".into())
+        Error::Model(ModelError::Cycle)
     }
 }
 
@@ -214,6 +316,10 @@ impl Datatype {
         dtype.id.hash = s.finish();
         dtype
     }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
 }
 
 impl Identifiable for Datatype {
@@ -435,3 +435,303 @@ fn impl_datatype_store(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
         }
     }
 }
+
+
+/// Derive a complete datatype whose state and delta types are stored through
+/// serde, with storage for every enabled backend.
+///
+/// This derives `DatatypeMarker`, `DatatypeMeta`, `Model`, `ComposableState`
+/// (or `StateOnly` if there is no delta type) and `Storage` for each backend.
+/// Debug filesystem hunks are stored as JSON files and Postgres hunks in the
+/// shared `hunk_payload` table through `default_postgres_store_backend!`, so
+/// no migrations are needed. Derived datatypes implement no interfaces.
+///
+/// ```ignore
+/// #[derive(Default, Datatype)]
+/// #[datatype(
+///     name = "Counter",
+///     version = 1,
+///     representations(State, Delta),
+///     dependencies(source(datatype = "Blob", cardinality = 1)),
+///     state = "u64",
+///     delta = "i64",
+///     compose = "add_signed",
+/// )]
+/// pub struct Counter;
+/// ```
+///
+/// `name` defaults to the name of the type, `version` to 1 and
+/// `representations` to `State`. `compose` is a path to a function
+/// `fn(&mut State, &Delta)` and is required if `delta` is given. `encoding`
+/// is the Postgres payload encoding, either `"jsonb"` (the default) or
+/// `"bytea"`.
+///
+/// Each dependency is a name, optionally followed by restrictions: any number
+/// of `datatype = "..."` or `interface = "..."` (but not both), and a
+/// `cardinality = n` for an exact number of dependencies. Dependencies are
+/// unbounded and of any datatype otherwise.
+#[proc_macro_derive(Datatype, attributes(datatype))]
+pub fn datatype_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+
+    let gen = match DatatypeAttributes::from_ast(&ast) {
+        Ok(attrs) => impl_datatype(&ast, &attrs),
+        Err(e) => e.to_compile_error(),
+    };
+
+    gen.into()
+}
+
+struct DependencyAttributes {
+    name: String,
+    datatypes: Vec<String>,
+    interfaces: Vec<String>,
+    cardinality: Option<u64>,
+}
+
+impl DependencyAttributes {
+    fn from_meta(meta: &syn::Meta) -> Result<Self> {
+        let mut dependency = DependencyAttributes {
+            name: meta.name().to_string(),
+            datatypes: vec![],
+            interfaces: vec![],
+            cardinality: None,
+        };
+
+        let nested = match meta {
+            syn::Meta::Word(_) => return Ok(dependency),
+            syn::Meta::List(list) => &list.nested,
+            syn::Meta::NameValue(_) =>
+                return Err(syn::Error::new_spanned(meta, "Expected a dependency name with optional restrictions")),
+        };
+
+        for item in nested {
+            match item {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) => match (nv.ident.to_string().as_str(), &nv.lit) {
+                    ("datatype", syn::Lit::Str(s)) => dependency.datatypes.push(s.value()),
+                    ("interface", syn::Lit::Str(s)) => dependency.interfaces.push(s.value()),
+                    ("cardinality", syn::Lit::Int(i)) => dependency.cardinality = Some(i.value()),
+                    _ => return Err(syn::Error::new_spanned(nv, "Unknown dependency restriction")),
+                },
+                _ => return Err(syn::Error::new_spanned(item, "Unknown dependency restriction")),
+            }
+        }
+
+        if !dependency.datatypes.is_empty() && !dependency.interfaces.is_empty() {
+            return Err(syn::Error::new_spanned(
+                meta,
+                "Dependency may be restricted by datatypes or interfaces, not both"));
+        }
+
+        Ok(dependency)
+    }
+}
+
+struct DatatypeAttributes {
+    name: String,
+    version: u64,
+    representations: Vec<syn::Ident>,
+    dependencies: Vec<DependencyAttributes>,
+    state: syn::Type,
+    delta: Option<(syn::Type, syn::Path)>,
+    encoding: syn::Ident,
+}
+
+impl DatatypeAttributes {
+    fn from_ast(ast: &syn::DeriveInput) -> Result<Self> {
+        let mut name = None;
+        let mut version = None;
+        let mut representations = None;
+        let mut dependencies = vec![];
+        let mut state = None;
+        let mut delta = None;
+        let mut compose = None;
+        let mut encoding = None;
+
+        let metas = ast.attrs.iter()
+            .filter(|a| a.path.segments.len() == 1 && a.path.segments[0].ident == "datatype")
+            .map(|a| a.parse_meta())
+            .collect::<Result<Vec<_>>>()?;
+
+        for meta in &metas {
+            let nested = match meta {
+                syn::Meta::List(list) => &list.nested,
+                _ => return Err(syn::Error::new_spanned(meta, "Expected #[datatype(...)]")),
+            };
+
+            for item in nested {
+                let item_meta = match item {
+                    syn::NestedMeta::Meta(m) => m,
+                    _ => return Err(syn::Error::new_spanned(item, "Unknown datatype attribute")),
+                };
+
+                match item_meta {
+                    syn::Meta::NameValue(nv) => match (nv.ident.to_string().as_str(), &nv.lit) {
+                        ("name", syn::Lit::Str(s)) => name = Some(s.value()),
+                        ("version", syn::Lit::Int(i)) => version = Some(i.value()),
+                        ("state", syn::Lit::Str(s)) => state = Some(s.parse::<syn::Type>()?),
+                        ("delta", syn::Lit::Str(s)) => delta = Some(s.parse::<syn::Type>()?),
+                        ("compose", syn::Lit::Str(s)) => compose = Some(s.parse::<syn::Path>()?),
+                        ("encoding", syn::Lit::Str(s)) if s.value() == "jsonb" || s.value() == "bytea" =>
+                            encoding = Some(proc_macro2::Ident::new(&s.value(), s.span())),
+                        _ => return Err(syn::Error::new_spanned(nv, "Unknown datatype attribute")),
+                    },
+                    syn::Meta::List(list) if list.ident == "representations" => {
+                        representations = Some(list.nested.iter()
+                            .map(|r| match r {
+                                syn::NestedMeta::Meta(syn::Meta::Word(w)) => Ok(w.clone()),
+                                _ => Err(syn::Error::new_spanned(r, "Expected a representation kind")),
+                            })
+                            .collect::<Result<Vec<_>>>()?);
+                    },
+                    syn::Meta::List(list) if list.ident == "dependencies" => {
+                        for dependency in &list.nested {
+                            match dependency {
+                                syn::NestedMeta::Meta(m) => dependencies.push(DependencyAttributes::from_meta(m)?),
+                                _ => return Err(syn::Error::new_spanned(dependency, "Expected a dependency")),
+                            }
+                        }
+                    },
+                    _ => return Err(syn::Error::new_spanned(item_meta, "Unknown datatype attribute")),
+                }
+            }
+        }
+
+        let state = state.ok_or_else(|| syn::Error::new_spanned(
+            &ast.ident,
+            "Datatype must specify a state type with #[datatype(state = \"...\")]"))?;
+        let delta = match (delta, compose) {
+            (Some(delta), Some(compose)) => Some((delta, compose)),
+            (None, None) => None,
+            (Some(_), None) => return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "Datatype with a delta type must specify a compose function")),
+            (None, Some(_)) => return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "Datatype without a delta type cannot specify a compose function")),
+        };
+
+        Ok(DatatypeAttributes {
+            name: name.unwrap_or_else(|| ast.ident.to_string()),
+            version: version.unwrap_or(1),
+            representations: representations
+                .unwrap_or_else(|| vec![proc_macro2::Ident::new("State", ast.ident.span())]),
+            dependencies,
+            state,
+            delta,
+            encoding: encoding.unwrap_or_else(|| proc_macro2::Ident::new("jsonb", ast.ident.span())),
+        })
+    }
+}
+
+fn impl_datatype(ast: &syn::DeriveInput, attrs: &DatatypeAttributes) -> proc_macro2::TokenStream {
+    let name = &ast.ident;
+    let store_backend_name = proc_macro2::Ident::new(&format!("{}Backend", name), name.span());
+    let marker = impl_datatype_store(ast);
+
+    let dtype_name = &attrs.name;
+    let version = attrs.version;
+    let representations = &attrs.representations;
+    let dependencies = attrs.dependencies.iter().map(|dependency| {
+        let dep_name = &dependency.name;
+        let datatype_restriction = if !dependency.datatypes.is_empty() {
+            let datatypes = &dependency.datatypes;
+            quote! {
+                heraclitus::datatype::DependencyTypeRestriction::Datatype(
+                    vec![#(#datatypes),*].into_iter().collect())
+            }
+        } else if !dependency.interfaces.is_empty() {
+            let interfaces = &dependency.interfaces;
+            quote! {
+                heraclitus::datatype::DependencyTypeRestriction::ImplementsInterface(
+                    vec![#(#interfaces),*].into_iter().collect())
+            }
+        } else {
+            quote! { heraclitus::datatype::DependencyTypeRestriction::Any }
+        };
+        let cardinality_restriction = match dependency.cardinality {
+            Some(n) => quote! { heraclitus::datatype::DependencyCardinalityRestriction::Exact(#n) },
+            None => quote! { heraclitus::datatype::DependencyCardinalityRestriction::Unbounded },
+        };
+
+        quote! {
+            heraclitus::datatype::DependencyDescription::new(
+                #dep_name,
+                #datatype_restriction,
+                #cardinality_restriction,
+                heraclitus::datatype::DependencyStoreRestriction::Same,
+            )
+        }
+    });
+
+    let state = &attrs.state;
+    let composable = match attrs.delta {
+        Some((ref delta, ref compose)) => quote! {
+            impl heraclitus::datatype::ComposableState for #name {
+                type StateType = #state;
+                type DeltaType = #delta;
+
+                fn compose_state(
+                    state: &mut Self::StateType,
+                    delta: &Self::DeltaType,
+                ) -> Result<(), heraclitus::Error> {
+                    #compose(state, delta);
+                    Ok(())
+                }
+            }
+        },
+        None => quote! {
+            impl heraclitus::datatype::StateOnly for #name {
+                type StateOnlyType = #state;
+            }
+        },
+    };
+
+    let debug_filesystem = if cfg!(feature="backend-debug-filesystem") {
+        quote! {
+            heraclitus::default_debug_filesystem_store_backend!(#store_backend_name);
+        }
+    } else {
+        quote! {}
+    };
+
+    let postgres = if cfg!(feature="backend-postgres") {
+        let encoding = &attrs.encoding;
+        quote! {
+            heraclitus::default_postgres_store_backend!(#store_backend_name, #encoding);
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        #marker
+
+        impl heraclitus::datatype::DatatypeMeta for #name {
+            const NAME: &'static str = #dtype_name;
+            const VERSION: u64 = #version;
+        }
+
+        impl<T: heraclitus::datatype::InterfaceControllerEnum> heraclitus::datatype::Model<T> for #name {
+            fn reflection(&self) -> heraclitus::datatype::Reflection<T> {
+                heraclitus::datatype::Reflection {
+                    representations: heraclitus::enumset::enum_set!(
+                                #(heraclitus::RepresentationKind::#representations |)*
+                            ),
+                    implements: vec![],
+                    dependencies: vec![
+                        #(#dependencies,)*
+                    ],
+                }
+            }
+
+            heraclitus::datatype_controllers!(#name, ());
+        }
+
+        #composable
+
+        #debug_filesystem
+
+        #postgres
+    }
+}
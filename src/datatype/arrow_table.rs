@@ -0,0 +1,313 @@
+//! Columnar tables of Apache Arrow record batches.
+//!
+//! This datatype is only available with the `datatype-arrow` feature, so is
+//! not part of `DefaultDatatypes`. Include it in a custom `datatype_enum!` to
+//! use it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+
+use arrow::array::BooleanArray;
+use arrow::compute::filter_record_batch;
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use serde::{
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+
+use heraclitus_macros::{
+    DatatypeMarker,
+    stored_datatype_controller,
+};
+
+use crate::{
+    Error,
+    ModelError,
+    RepresentationKind,
+};
+use super::{
+    DatatypeMeta,
+    InterfaceControllerEnum,
+    Reflection,
+};
+
+
+#[derive(Default, DatatypeMarker)]
+pub struct ArrowDatatype;
+
+impl DatatypeMeta for ArrowDatatype {
+    const NAME: &'static str = "Arrow";
+    const VERSION: u64 = 1;
+}
+
+impl<T: InterfaceControllerEnum> super::Model<T> for ArrowDatatype {
+    fn reflection(&self) -> Reflection<T> {
+        Reflection {
+            representations: enumset::enum_set!(
+                        RepresentationKind::State |
+                        RepresentationKind::Delta |
+                    ),
+            implements: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    datatype_controllers!(ArrowDatatype, ());
+}
+
+pub(crate) fn arrow_error(e: ArrowError) -> Error {
+    Error::Store(e.to_string())
+}
+
+/// A sequence of record batches sharing a schema.
+///
+/// Batches are serialized, compared and hashed through the Arrow IPC stream
+/// format. Batches that can not be encoded, for example because they do not
+/// match the schema, are not equal to any batches.
+#[derive(Clone)]
+pub struct RecordBatches {
+    pub schema: SchemaRef,
+    pub batches: Vec<RecordBatch>,
+}
+
+impl RecordBatches {
+    pub fn new(schema: SchemaRef, batches: Vec<RecordBatch>) -> Self {
+        RecordBatches {schema, batches}
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.batches.iter().map(RecordBatch::num_rows).sum()
+    }
+
+    /// Encode these batches as an Arrow IPC stream.
+    pub fn to_ipc(&self) -> Result<Vec<u8>, Error> {
+        let mut buffer = vec![];
+        {
+            let mut writer = StreamWriter::try_new(&mut buffer, &self.schema).map_err(arrow_error)?;
+            for batch in &self.batches {
+                writer.write(batch).map_err(arrow_error)?;
+            }
+            writer.finish().map_err(arrow_error)?;
+        }
+        Ok(buffer)
+    }
+
+    /// Decode batches from an Arrow IPC stream.
+    pub fn from_ipc<R: Read>(reader: R) -> Result<Self, Error> {
+        let reader = StreamReader::try_new(reader).map_err(arrow_error)?;
+        let schema = reader.schema();
+        let batches = reader.collect::<Result<Vec<_>, _>>().map_err(arrow_error)?;
+        Ok(RecordBatches {schema, batches})
+    }
+}
+
+impl fmt::Debug for RecordBatches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RecordBatches")
+            .field("schema", &self.schema)
+            .field("batch_rows", &self.batches.iter().map(RecordBatch::num_rows).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Hash for RecordBatches {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.to_ipc() {
+            Ok(ipc) => ipc.hash(state),
+            // Unencodable batches are unequal to all batches, so need only
+            // be hashed consistently.
+            Err(_) => {
+                self.schema.to_json().to_string().hash(state);
+                for batch in &self.batches {
+                    batch.num_rows().hash(state);
+                }
+            },
+        }
+    }
+}
+
+impl PartialEq for RecordBatches {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.to_ipc(), other.to_ipc()) {
+            (Ok(ipc), Ok(other_ipc)) => ipc == other_ipc,
+            _ => false,
+        }
+    }
+}
+
+impl Serialize for RecordBatches {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let ipc = self.to_ipc().map_err(|e| serde::ser::Error::custom(format!("{:?}", e)))?;
+        serializer.serialize_bytes(&ipc)
+    }
+}
+
+impl<'de> Deserialize<'de> for RecordBatches {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ipc = Vec::<u8>::deserialize(deserializer)?;
+        RecordBatches::from_ipc(&ipc[..]).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))
+    }
+}
+
+/// Rows to delete, as row indices within each batch of the prior state.
+pub type DeletionVectors = BTreeMap<usize, BTreeSet<usize>>;
+
+/// Deletions from the prior state's batches followed by appended batches.
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct ArrowDelta {
+    pub deletions: DeletionVectors,
+    pub appended: RecordBatches,
+}
+
+impl crate::datatype::ComposableState for ArrowDatatype {
+    type StateType = RecordBatches;
+    type DeltaType = ArrowDelta;
+
+    fn compose_state(
+        state: &mut Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        Self::validate_delta(state, delta)?;
+
+        for (&b_idx, rows) in &delta.deletions {
+            if let Some(batch) = state.batches.get_mut(b_idx) {
+                *batch = delete_rows(batch, rows)?;
+            }
+        }
+        state.batches.retain(|batch| batch.num_rows() > 0);
+        state.batches.extend(delta.appended.batches.iter().cloned());
+
+        Ok(())
+    }
+
+    fn validate_delta(
+        state: &Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        if delta.appended.schema != state.schema {
+            return Err(ModelError::Other("Appended batches do not match the state schema".into()).into());
+        }
+        for (&b_idx, rows) in &delta.deletions {
+            let batch = state.batches.get(b_idx)
+                .ok_or_else(|| ModelError::Other(format!("Deletion vector for missing batch {}", b_idx)))?;
+            // Deletion vectors are ordered, so only the last row may be missing.
+            if let Some(&r_idx) = rows.iter().next_back() {
+                if r_idx >= batch.num_rows() {
+                    return Err(ModelError::Other(
+                        format!("Deletion vector for missing row {} of batch {}", r_idx, b_idx)).into());
+                }
+            }
+        }
+        if delta.appended.batches.iter().any(|batch| batch.schema() != delta.appended.schema) {
+            return Err(ModelError::Other("Appended batch does not match its schema".into()).into());
+        }
+
+        Ok(())
+    }
+}
+
+fn delete_rows(batch: &RecordBatch, rows: &BTreeSet<usize>) -> Result<RecordBatch, Error> {
+    let keep = BooleanArray::from((0..batch.num_rows())
+        .map(|r_idx| !rows.contains(&r_idx))
+        .collect::<Vec<_>>());
+    filter_record_batch(batch, &keep).map_err(arrow_error)
+}
+
+#[stored_datatype_controller(ArrowDatatype)]
+pub trait Storage: super::Storage {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use maplit::{btreemap, btreeset};
+
+    use crate::datatype::ComposableState;
+
+    fn batch(schema: &SchemaRef, ids: Vec<i64>, names: Vec<&str>) -> RecordBatch {
+        RecordBatch::try_new(schema.clone(), vec![
+            Arc::new(Int64Array::from(ids)),
+            Arc::new(StringArray::from(names)),
+        ]).unwrap()
+    }
+
+    #[test]
+    fn test_compose_deletions_and_appends() {
+        let schema: SchemaRef = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let mut state = RecordBatches::new(schema.clone(), vec![
+            batch(&schema, vec![1, 2, 3], vec!["a", "b", "c"]),
+            batch(&schema, vec![4], vec!["d"]),
+        ]);
+        let delta = ArrowDelta {
+            deletions: btreemap!{0 => btreeset![1], 1 => btreeset![0]},
+            appended: RecordBatches::new(schema.clone(), vec![batch(&schema, vec![5], vec!["e"])]),
+        };
+
+        ArrowDatatype::compose_state(&mut state, &delta).unwrap();
+
+        let expected = RecordBatches::new(schema.clone(), vec![
+            batch(&schema, vec![1, 3], vec!["a", "c"]),
+            batch(&schema, vec![5], vec!["e"]),
+        ]);
+        assert_eq!(state, expected);
+
+        let round_trip = RecordBatches::from_ipc(&state.to_ipc().unwrap()[..]).unwrap();
+        assert_eq!(round_trip, state);
+    }
+
+    #[test]
+    fn test_validate_delta() {
+        let schema: SchemaRef = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let state = RecordBatches::new(schema.clone(), vec![
+            batch(&schema, vec![1, 2], vec!["a", "b"]),
+        ]);
+        let delta = |deletions| ArrowDelta {
+            deletions,
+            appended: RecordBatches::new(schema.clone(), vec![]),
+        };
+
+        assert!(ArrowDatatype::validate_delta(&state, &delta(btreemap!{0 => btreeset![1]})).is_ok());
+        assert!(ArrowDatatype::validate_delta(&state, &delta(btreemap!{1 => btreeset![0]})).is_err(),
+            "Deletion from missing batch was accepted");
+        assert!(ArrowDatatype::validate_delta(&state, &delta(btreemap!{0 => btreeset![2]})).is_err(),
+            "Deletion of missing row was accepted");
+        assert!(ArrowDatatype::compose_state(&mut state.clone(), &delta(btreemap!{1 => btreeset![0]})).is_err(),
+            "Deletion from missing batch was composed");
+
+        let other_schema: SchemaRef = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+        ]));
+        let other_delta = ArrowDelta {
+            deletions: DeletionVectors::new(),
+            appended: RecordBatches::new(other_schema, vec![]),
+        };
+        assert!(ArrowDatatype::validate_delta(&state, &other_delta).is_err(),
+            "Append with a different schema was accepted");
+    }
+}
@@ -28,6 +28,7 @@ use enumset::{
 };
 use lazy_static::lazy_static;
 use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::{
     Uuid,
 };
@@ -52,6 +53,7 @@ use crate::{
     PartCompletion,
     Partition,
     PartitionIndex,
+    PartitionKind,
     Version,
     VersionGraph,
     VersionGraphIndex,
@@ -76,6 +78,13 @@ use crate::datatype::interface::{
     ProducerController,
     ProductionOutput,
 };
+use crate::datatype::diff::{
+    ContentChange,
+    ContentDiff,
+    ContentDiffController,
+    DiffableState,
+};
+use crate::datatype::snapshot::SnapshotPolicy;
 use crate::repo::Repository;
 
 
@@ -119,19 +128,23 @@ impl DatatypeMeta for ArtifactGraphDtype {
     const VERSION: u64 = 1;
 }
 
-impl<T: InterfaceController<ArtifactMeta>> super::Model<T> for ArtifactGraphDtype {
+impl<T> super::Model<T> for ArtifactGraphDtype
+        where T: InterfaceController<ArtifactMeta> + InterfaceController<ContentDiffController> {
     fn reflection(&self) -> Reflection<T> {
         Reflection {
             representations: enum_set!(
                         RepresentationKind::State |
                         RepresentationKind::Delta |
                     ),
-            implements: vec![], // TODO: should artifact graph be an interface?
+            // TODO: should artifact graph be an interface?
+            implements: vec![
+                <T as InterfaceController<ContentDiffController>>::VARIANT,
+            ],
             dependencies: vec![],
         }
     }
 
-    datatype_controllers!(ArtifactGraphDtype, ());
+    datatype_controllers!(ArtifactGraphDtype, (ContentDiffController));
 }
 
 
@@ -142,8 +155,8 @@ impl crate::datatype::ComposableState for ArtifactGraphDtype {
     fn compose_state(
         state: &mut Self::StateType,
         delta: &Self::DeltaType,
-    ) {
-        state.compose(delta).expect("TODO");
+    ) -> Result<(), Error> {
+        state.compose(delta)
     }
 }
 
@@ -167,14 +180,16 @@ struct OriginGraphTemplate {
 }
 
 impl OriginGraphTemplate {
-    fn new<T: DatatypeEnum>(dtypes_registry: &DatatypesRegistry<T>) -> OriginGraphTemplate {
+    fn new<T: DatatypeEnum>(dtypes_registry: &DatatypesRegistry<T>) -> Result<OriginGraphTemplate, Error> {
         Self::for_uuids(dtypes_registry, None)
     }
 
+    /// Fails if the registry lacks the artifact graph or unary partitioning
+    /// datatypes.
     fn for_uuids<T: DatatypeEnum>(
         dtypes_registry: &DatatypesRegistry<T>,
         hunk_uuids: Option<&HunkUuidSpec>,
-    ) -> OriginGraphTemplate {
+    ) -> Result<OriginGraphTemplate, Error> {
         let mut origin_ag = ArtifactGraphDescriptionType::new();
 
         let origin_art = ArtifactDescription::New {
@@ -201,14 +216,14 @@ impl OriginGraphTemplate {
         let (origin_ag, idx_map) = ArtifactGraph::from_description(
             &origin_ag_desc,
             dtypes_registry,
-            hunk_uuids.map(|spec| spec.hunk_uuid));
+            hunk_uuids.map(|spec| spec.hunk_uuid))?;
 
-        OriginGraphTemplate {
+        Ok(OriginGraphTemplate {
             artifact_graph: origin_ag,
             origin_idx: idx_map[&origin_desc_idx],
             root_idx: idx_map[&root_desc_idx],
             up_idx: idx_map[&up_desc_idx],
-        }
+        })
     }
 
     fn origin(&self) -> &Artifact {
@@ -260,7 +275,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         origin_spec: HunkUuidSpec,
     ) -> Result<ArtifactGraph, Error> {
 
-        let fake_origin = OriginGraphTemplate::for_uuids(dtypes_registry, Some(&origin_spec));
+        let fake_origin = OriginGraphTemplate::for_uuids(dtypes_registry, Some(&origin_spec))?;
         let fake_vg = VersionGraph::new_from_source_artifacts(&fake_origin.artifact_graph);
         let mut fake_origin_version = Version::new(fake_origin.origin(), RepresentationKind::State);
         fake_origin_version.id.uuid = origin_spec.version_uuid;
@@ -270,6 +285,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
             partition: Partition {
                 partitioning: &fake_vg[fake_vg.artifact_versions(fake_origin.up())[0]],
                 index: crate::datatype::partitioning::UNARY_PARTITION_INDEX,
+                kind: PartitionKind::Leaf,
             },
             representation: RepresentationKind::State,
             completion: PartCompletion::Complete,
@@ -279,7 +295,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         let ag_desc = self.get_composite_state(repo, &comp)?;
 
         let (origin_ag, _) = ArtifactGraph::from_description(&ag_desc, dtypes_registry,
-            Some(origin_spec.hunk_uuid));
+            Some(origin_spec.hunk_uuid))?;
 
         Ok(origin_ag)
     }
@@ -295,7 +311,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         repo: &Repository,
     ) -> Result<HunkUuidSpec, Error> {
 
-        let origin = OriginGraphTemplate::new(dtypes_registry);
+        let origin = OriginGraphTemplate::new(dtypes_registry)?;
         let origin_art_id = origin.origin().id;
 
         // Create version graph for the origin AG.
@@ -327,6 +343,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
                 partition: Partition {
                     partitioning: &ver_graph[up_ver_idx],
                     index: part_id,
+                    kind: PartitionKind::Leaf,
                 },
                 representation: RepresentationKind::State,
                 completion: PartCompletion::Complete,
@@ -351,6 +368,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
                 partition: Partition {
                     partitioning: &ver_graph[up_ver_idx],
                     index: part_id,
+                    kind: PartitionKind::Leaf,
                 },
                 representation: RepresentationKind::State,
                 completion: PartCompletion::Complete,
@@ -377,7 +395,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         let (root_ag, _) = ArtifactGraph::from_description(
             &ArtifactGraphDescription::new(),
             dtypes_registry,
-            None);
+            None)?;
         let root_ag_payload = Payload::State(root_ag.as_description(dtypes_registry));
         let root_ag_hunk = Hunk {
             id: ArtifactGraphDtype::hash_payload(&root_ag_payload).into(),
@@ -385,6 +403,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
             partition: Partition {
                 partitioning: &ver_graph[up_ver_idx],
                 index: part_id,
+                kind: PartitionKind::Leaf,
             },
             representation: RepresentationKind::State,
             completion: PartCompletion::Complete,
@@ -454,6 +473,10 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
                     InterfaceController<CustomProductionPolicyController>
     {
 
+        // Create and validate the new AG before changing the parent graph.
+        let (mut art_graph, new_idx_map) = ArtifactGraph::from_description(&art_graph_desc, dtypes_registry, None)?;
+        art_graph.validate(dtypes_registry, repo)?;
+
         // Create delta for parent graph, with new AG artifact related to UP.
         let mut parent_ag_delta_desc = ArtifactGraphDescription::new();
         let new_ag_art = ArtifactDescription::New {
@@ -474,7 +497,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
             additions: parent_ag_delta_desc,
             removals: vec![]
         };
-        let parent_ag_idx_map = parent.apply_delta(&parent_ag_delta, dtypes_registry);
+        let parent_ag_idx_map = parent.apply_delta(&parent_ag_delta, dtypes_registry)?;
 
         // Set the correct ID and hash for the new AG artifacts.
         for node_idx in parent_ag_delta.additions.artifacts.graph().node_indices() {
@@ -484,10 +507,12 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         }
 
         // Create new version for parent graph delta.
-        let new_parent_v_idx = grandp_vg.new_child(parent_v_idx, RepresentationKind::Delta);
+        let new_parent_v_idx = grandp_vg.new_child(parent_v_idx, RepresentationKind::Delta)?;
         // TODO: have to do this because do not have access to grandparent AG.
         grandp_vg[new_parent_v_idx].status = VersionStatus::Committed;
-        let parent_v_part_idx = grandp_vg.get_partitioning(parent_v_idx).expect("TODO").0;
+        let parent_v_part_idx = grandp_vg.get_partitioning(parent_v_idx)
+            .ok_or_else(|| ModelError::MalformedGraph("Artifact graph version has no partitioning".into()))?
+            .0;
         let parent_v_part_edge = grandp_vg.versions.find_edge(parent_v_part_idx, parent_v_idx).unwrap();
         grandp_vg.versions.add_edge(parent_v_part_idx, new_parent_v_idx, grandp_vg[parent_v_part_edge].clone()).unwrap();
         self.create_staging_version(repo, grandp_vg, new_parent_v_idx)?;
@@ -502,6 +527,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
             partition: Partition {
                 partitioning: &grandp_vg[parent_v_part_idx],
                 index: part_id,
+                kind: PartitionKind::Leaf,
             },
             representation: RepresentationKind::Delta,
             completion: PartCompletion::Complete,
@@ -510,10 +536,6 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         self.create_hunk(repo, &parent_ag_hunk)?;
         self.write_hunk(repo, &parent_ag_hunk, &parent_ag_payload)?;
 
-        // Create new AG.
-        // Done earlier here so ownership of the description can be transferred.
-        let (mut art_graph, new_idx_map) = ArtifactGraph::from_description(&art_graph_desc, dtypes_registry, None);
-
         // Create version for new artifact graph's artifact.
         let new_ag_art = &parent[parent_ag_idx_map[&new_ag_art_idx]];
         let parent_ag_up_art = &parent[parent_ag_idx_map[&parent_ag_up_idx]];
@@ -545,6 +567,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
             partition: Partition {
                 partitioning: &grandp_vg[parent_v_part_idx], // TODO: this is wrong
                 index: part_id,
+                kind: PartitionKind::Leaf,
             },
             representation: RepresentationKind::State,
             completion: PartCompletion::Complete,
@@ -589,7 +612,7 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
 
         let ag_desc = self.get_composite_state(repo, composition)?;
 
-        let ag = ArtifactGraph::from_description(&ag_desc, dtypes_registry, None).0;
+        let ag = ArtifactGraph::from_description(&ag_desc, dtypes_registry, None)?.0;
 
         Ok(ag)
     }
@@ -683,9 +706,10 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
             Box::new(ParsimoniousRepresentationProductionStrategyPolicy);
 
         let (ver_art_idx, _) = {
-            let new_ver = ver_graph.versions.node_weight(v_idx).expect("TODO");
+            let new_ver = ver_graph.versions.node_weight(v_idx)
+                .ok_or_else(|| ModelError::MalformedGraph("Version is not in the version graph".into()))?;
             art_graph.get_by_id(&new_ver.artifact.id)
-                .expect("TODO: Unknown artifact")
+                .ok_or_else(|| ModelError::UnknownArtifact(new_ver.artifact.id.uuid))?
         };
 
         let dependent_arts = art_graph.artifacts.children(ver_art_idx).iter(&art_graph.artifacts);
@@ -701,29 +725,31 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
             if let Some(producer_controller) = producer_interface {
 
                 let production_policies: Option<Vec<Box<dyn ProductionPolicy>>> =
-                    self.get_production_policies(&repo, dependent)?
-                    .map(|policies| policies.iter().filter_map(|p| match p {
-                        ProductionPolicies::Extant =>
-                            Some(Box::new(ExtantProductionPolicy) as Box<dyn ProductionPolicy>),
-                        ProductionPolicies::LeafBootstrap =>
-                            Some(Box::new(LeafBootstrapProductionPolicy) as Box<dyn ProductionPolicy>),
-                        ProductionPolicies::Custom => {
-                            let custom_policy_interface = dtypes_registry
-                                .get_model_interface::<CustomProductionPolicyController>(&dtype_uuid)
-                                // .get_model(&dtype.name)
-                                // .get_controller()
-                                .map(|gen| gen(&repo));
-                            if let Some(custom_policy_controller) = custom_policy_interface {
-
-                                Some(custom_policy_controller.get_custom_production_policy(
-                                    &repo,
-                                    art_graph,
-                                    dep_art_idx).expect("TODO"))
-                            } else {
-                                None // TODO: custom policy for non-interface producer.
+                    match self.get_production_policies(&repo, dependent)? {
+                        None => None,
+                        Some(policies) => Some(policies.iter().filter_map(|p| match p {
+                            ProductionPolicies::Extant =>
+                                Some(Ok(Box::new(ExtantProductionPolicy) as Box<dyn ProductionPolicy>)),
+                            ProductionPolicies::LeafBootstrap =>
+                                Some(Ok(Box::new(LeafBootstrapProductionPolicy) as Box<dyn ProductionPolicy>)),
+                            ProductionPolicies::Custom => {
+                                let custom_policy_interface = dtypes_registry
+                                    .get_model_interface::<CustomProductionPolicyController>(&dtype_uuid)
+                                    // .get_model(&dtype.name)
+                                    // .get_controller()
+                                    .map(|gen| gen(&repo));
+                                if let Some(custom_policy_controller) = custom_policy_interface {
+
+                                    Some(custom_policy_controller.get_custom_production_policy(
+                                        &repo,
+                                        art_graph,
+                                        dep_art_idx))
+                                } else {
+                                    None // TODO: custom policy for non-interface producer.
+                                }
                             }
-                        }
-                    }).collect());
+                        }).collect::<Result<_, Error>>()?),
+                    };
 
                 let production_policy_reqs = match production_policies {
                         None => default_production_policies.iter(),
@@ -789,7 +815,10 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
                             ver_graph,
                             new_prod_ver_idx,
                             &production_strategies)
-                                .expect("TODO: producer is incompatible with input versions"),
+                                .ok_or_else(|| ModelError::IncompatibleProducer {
+                                    producer: dependent.id.uuid,
+                                    reason: "No production strategy is compatible with input versions".into(),
+                                })?,
                     };
 
                     self.create_staging_version(
@@ -845,6 +874,77 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         art_graph: &'ag ArtifactGraph,
     ) -> Result<VersionGraph<'ag>, Error>;
 
+    /// Get UUIDs of versions from which a version descends through `Parent`
+    /// relations, excluding the version itself.
+    ///
+    /// By default this walks `ver_graph`, so assumes the version's ancestry
+    /// is loaded. Backends may instead query their stores.
+    fn get_ancestor_uuids(
+        &self,
+        _repo: &Repository,
+        ver_graph: &VersionGraph,
+        v_idx: VersionGraphIndex,
+    ) -> Result<BTreeSet<Uuid>, Error> {
+        Ok(ver_graph.ancestors(v_idx, &VersionRelation::Parent)
+            .map(|a_idx| ver_graph[a_idx].id.uuid)
+            .collect())
+    }
+
+    /// Get UUIDs of versions descending from a version through `Parent`
+    /// relations, excluding the version itself.
+    ///
+    /// By default this walks `ver_graph`, so assumes the version's
+    /// descendants are loaded. Backends may instead query their stores.
+    fn get_descendant_uuids(
+        &self,
+        _repo: &Repository,
+        ver_graph: &VersionGraph,
+        v_idx: VersionGraphIndex,
+    ) -> Result<BTreeSet<Uuid>, Error> {
+        Ok(ver_graph.descendants(v_idx, &VersionRelation::Parent)
+            .map(|d_idx| ver_graph[d_idx].id.uuid)
+            .collect())
+    }
+
+    /// Whether `ancestor` is an ancestor of `v_idx` through `Parent`
+    /// relations. Versions are their own ancestors.
+    fn is_ancestor(
+        &self,
+        repo: &Repository,
+        ver_graph: &VersionGraph,
+        ancestor: VersionGraphIndex,
+        v_idx: VersionGraphIndex,
+    ) -> Result<bool, Error> {
+        Ok(ancestor == v_idx ||
+            self.get_ancestor_uuids(repo, ver_graph, v_idx)?.contains(&ver_graph[ancestor].id.uuid))
+    }
+
+    /// Get UUIDs of the lowest common ancestors of versions. See
+    /// `VersionGraph::lowest_common_ancestors`.
+    fn get_merge_base_uuids(
+        &self,
+        _repo: &Repository,
+        ver_graph: &VersionGraph,
+        v_idxs: &[VersionGraphIndex],
+    ) -> Result<BTreeSet<Uuid>, Error> {
+        Ok(ver_graph.lowest_common_ancestors(v_idxs).into_iter()
+            .map(|a_idx| ver_graph[a_idx].id.uuid)
+            .collect())
+    }
+
+    /// Get UUIDs of a version and its first parents back to a root version,
+    /// in that order.
+    fn get_first_parent_history_uuids(
+        &self,
+        _repo: &Repository,
+        ver_graph: &VersionGraph,
+        v_idx: VersionGraphIndex,
+    ) -> Result<Vec<Uuid>, Error> {
+        Ok(ver_graph.first_parent_history(v_idx)
+            .map(|h_idx| ver_graph[h_idx].id.uuid)
+            .collect())
+    }
+
     fn create_hunk(
         &mut self,
         repo: &Repository,
@@ -886,6 +986,10 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
     ///
     /// Note that partition indices in `partitions` may be abset from the
     /// returned `CompositionMap` if they have never been populated.
+    ///
+    /// This assumes the requested partitions exist in the partitionings of
+    /// all ancestral versions. See `get_partitioned_composition_map` for
+    /// partitionings whose partition sets change.
     fn get_composition_map<'ag: 'r, 'vg1, 'vg2, 'r: 'vg1 + 'vg2>(
         &self,
         repo: &Repository,
@@ -893,6 +997,55 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         v_idx: VersionGraphIndex,
         partitions: BTreeSet<PartitionIndex>,
     ) -> Result<CompositionMap<'ag, 'vg1, 'vg2>, Error>  {
+        self.compose_partitions(repo, ver_graph, v_idx, partitions, |_| Ok(None))
+    }
+
+    /// Get hunk sets sufficient to reconstruct composite states for a set of
+    /// partitions, where the partition set of the partitioning may change
+    /// between versions.
+    ///
+    /// If an ancestral version is partitioned by a partitioning version
+    /// lacking a requested partition, that partition did not exist for that
+    /// version, so its composition does not extend to that or earlier
+    /// versions.
+    fn get_partitioned_composition_map<'ag: 'r, 'vg1, 'vg2, 'r: 'vg1 + 'vg2, T: DatatypeEnum>(
+        &self,
+        dtypes_registry: &DatatypesRegistry<T>,
+        repo: &Repository,
+        ver_graph: &'r VersionGraph<'ag>,
+        v_idx: VersionGraphIndex,
+        partitions: BTreeSet<PartitionIndex>,
+    ) -> Result<CompositionMap<'ag, 'vg1, 'vg2>, Error>
+            where
+                <T as DatatypeEnum>::InterfaceControllerType :
+                    InterfaceController<crate::datatype::partitioning::PartitioningState>
+    {
+        let mut partition_sets = BTreeMap::new();
+
+        self.compose_partitions(repo, ver_graph, v_idx, partitions, |part_idx| {
+            if !partition_sets.contains_key(&part_idx) {
+                let part_ids = self.get_partitioning_partitions(
+                    dtypes_registry, repo, ver_graph, part_idx)?
+                    .keys().cloned().collect();
+                partition_sets.insert(part_idx, part_ids);
+            }
+
+            Ok(partition_sets.get(&part_idx).cloned())
+        })
+    }
+
+    /// Walk the ancestry of a version to collect hunks for a set of
+    /// partitions. `partition_ids` may give the partition set of each
+    /// partitioning version encountered.
+    fn compose_partitions<'ag: 'r, 'vg1, 'vg2, 'r: 'vg1 + 'vg2, F>(
+        &self,
+        repo: &Repository,
+        ver_graph: &'r VersionGraph<'ag>,
+        v_idx: VersionGraphIndex,
+        partitions: BTreeSet<PartitionIndex>,
+        mut partition_ids: F,
+    ) -> Result<CompositionMap<'ag, 'vg1, 'vg2>, Error>
+            where F: FnMut(VersionGraphIndex) -> Result<Option<BTreeSet<PartitionIndex>>, Error> {
         // TODO: assumes whole version graph is loaded.
         // TODO: not backend-specific, but could be optimized to be so.
         let ancestors = crate::util::petgraph::induced_stream_toposort(
@@ -911,6 +1064,8 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         // Partition indices that are locked from composition changes because
         // they have received a hunk from an unreached version.
         let mut locked: BTreeMap<Uuid, BTreeSet<PartitionIndex>> = BTreeMap::new();
+        // Kinds of partitions as of their most recent hunks.
+        let mut kinds: BTreeMap<PartitionIndex, PartitionKind> = BTreeMap::new();
 
         for n_idx in ancestors {
             let version = &ver_graph[n_idx];
@@ -919,25 +1074,65 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
                 unresolved.append(&mut part_idxs);
             }
 
+            let (part_idx, partitioning) = ver_graph.get_partitioning(n_idx)
+                .ok_or_else(|| ModelError::MalformedGraph("Version has no partitioning".into()))?;
+
+            // Partitions absent from this version's partitioning did not yet
+            // exist, so are resolved.
+            if let Some(part_ids) = partition_ids(part_idx)? {
+                let absent = unresolved.difference(&part_ids).cloned().collect::<Vec<_>>();
+                for part_idx in absent {
+                    unresolved.remove(&part_idx);
+                    unseen.remove(&part_idx);
+                }
+            }
+
             let hunks = self.get_hunks(
                 &repo,
                 version,
-                &ver_graph[ver_graph.get_partitioning(n_idx).expect("TODO: comp map part").0],
+                partitioning,
                 Some(&unresolved))?;
 
+            // A version may have both a `State` hunk and other hunks for a
+            // partition if the partition was snapshotted, in which case the
+            // `State` hunk supersedes the others.
+            let snapshotted: BTreeSet<PartitionIndex> = hunks.iter()
+                .filter(|hunk| hunk.representation == RepresentationKind::State && hunk.precedence.is_none())
+                .map(|hunk| hunk.partition.index)
+                .collect();
+
             for hunk in hunks {
                 let part_idx = hunk.partition.index;
-                unseen.remove(&part_idx);
 
-                if hunk.representation == RepresentationKind::State {
-                    unresolved.remove(&part_idx);
+                if snapshotted.contains(&part_idx) && hunk.representation != RepresentationKind::State {
+                    continue;
+                }
+
+                // A partition whose kind differs from that of its more recent
+                // hunks was redefined by the partitioning, so its earlier
+                // hunks do not contribute to the composition. The redefined
+                // partition must then start with a state, which would have
+                // resolved it before reaching this hunk.
+                let kind = *kinds.entry(part_idx).or_insert(hunk.partition.kind);
+                if kind != hunk.partition.kind {
+                    return Err(ModelError::MalformedGraph(format!(
+                        "Partition {} changed kind without a state hunk", part_idx)).into());
                 }
 
+                unseen.remove(&part_idx);
+
+                // Precedence hunks carry no data, but defer the partition's
+                // composition to the precedent version's ancestry.
                 if let Some(ver_uuid) = hunk.precedence {
                     locked.entry(ver_uuid)
                         .or_insert_with(BTreeSet::new)
-                        .insert(hunk.partition.index);
-                    unresolved.remove(&hunk.partition.index);
+                        .insert(part_idx);
+                    unresolved.remove(&part_idx);
+                    continue;
+                }
+
+                if hunk.representation == RepresentationKind::State {
+                    unresolved.remove(&part_idx);
                 }
 
                 map.entry(part_idx)
@@ -951,10 +1146,68 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
             }
         }
 
-        assert!(unresolved == unseen && locked.is_empty(), "Composition map was unfulfilled!");
+        if unresolved != unseen || !locked.is_empty() {
+            return Err(ModelError::MalformedGraph("Composition map was unfulfilled".into()).into());
+        }
         Ok(map)
     }
 
+    /// Get the partitions of a version of a partitioning artifact, with their
+    /// kinds.
+    ///
+    /// The partitioning may itself be partitioned, in which case its own
+    /// partitioning is resolved recursively and the partitions are the
+    /// union of those of each of its partitions' states. Partitionings that
+    /// are unpartitioned or self-partitioning are composed from their unary
+    /// partition.
+    fn get_partitioning_partitions<T: DatatypeEnum>(
+        &self,
+        dtypes_registry: &DatatypesRegistry<T>,
+        repo: &Repository,
+        ver_graph: &VersionGraph,
+        part_idx: VersionGraphIndex,
+    ) -> Result<BTreeMap<PartitionIndex, PartitionKind>, Error>
+            where
+                <T as DatatypeEnum>::InterfaceControllerType :
+                    InterfaceController<crate::datatype::partitioning::PartitioningState>
+    {
+        use crate::datatype::partitioning::PartitioningState;
+
+        let part_part_ids = match ver_graph.get_partitioning(part_idx) {
+            Some((part_part_idx, _)) if part_part_idx != part_idx =>
+                self.get_partitioning_partitions(dtypes_registry, repo, ver_graph, part_part_idx)?
+                    .keys().cloned().collect(),
+            _ => crate::datatype::partitioning::UnaryPartitioningState.get_partition_ids(),
+        };
+
+        let part_comp_map = self.get_composition_map(
+                &repo,
+                &ver_graph,
+                part_idx,
+                part_part_ids,
+            )?;
+        if part_comp_map.is_empty() {
+            return Err(ModelError::MalformedGraph(format!(
+                "Partitioning version {} has no state", ver_graph[part_idx].id.uuid)).into());
+        }
+        let part_control: Box<dyn PartitioningState> =
+                dtypes_registry
+                    .get_model_interface::<dyn PartitioningState>(&ver_graph[part_idx].artifact.dtype_uuid)
+                    .map(|gen| gen(&repo))
+                    .ok_or_else(|| ModelError::MalformedGraph(format!(
+                        "Partitioning artifact {} has no partitioning controller",
+                        ver_graph[part_idx].artifact.id.uuid)))?;
+
+        let mut partitions = BTreeMap::new();
+        for part_comp in part_comp_map.values() {
+            partitions.append(&mut part_control
+                .get_composite_interface(&repo, part_comp)?
+                .get_partition_kinds());
+        }
+
+        Ok(partitions)
+    }
+
     fn iter_version_partitions<
             'ag: 'vg_par, 'vg_par, // Normal Partition lifetimes.
             'vg: 'vg_par + 'q, // A lifetime for the version graph reference which must outlive
@@ -972,29 +1225,15 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
                 <T as DatatypeEnum>::InterfaceControllerType :
                     InterfaceController<crate::datatype::partitioning::PartitioningState>
     {
-        use crate::datatype::partitioning::PartitioningState;
+        let (ver_part_idx, ver_partitioning) = ver_graph.get_partitioning(v_idx)
+            .ok_or_else(|| ModelError::MalformedGraph("Version has no partitioning".into()))?;
 
-        let (ver_part_idx, ver_partitioning) = ver_graph.get_partitioning(v_idx).expect("TODO");
-        let ver_part_comp = self.get_composition_map(
-                &repo,
-                &ver_graph,
-                ver_part_idx,
-                // TODO: need to recursively get partitioning's partitioning.
-                crate::datatype::partitioning::UnaryPartitioningState.get_partition_ids(),
-            ).unwrap().into_iter().last().unwrap().1;
-        let ver_part_control: Box<dyn PartitioningState> =
-                dtypes_registry
-                    .get_model_interface::<dyn PartitioningState>(&ver_partitioning.artifact.dtype_uuid)
-                    .map(|gen| gen(&repo))
-                    .expect("Partitioning must have controller for backend");
-
-        let iter = ver_part_control
-                .get_composite_interface(&repo, &ver_part_comp).unwrap()
-                .get_partition_ids()
+        let iter = self.get_partitioning_partitions(dtypes_registry, repo, ver_graph, ver_part_idx)?
                 .into_iter()
-                .map(move |index| Partition {
+                .map(move |(index, kind)| Partition {
                     partitioning: ver_partitioning,
-                    index
+                    index,
+                    kind,
                 });
 
         Ok(Box::new(iter))
@@ -1020,6 +1259,19 @@ pub trait Storage: crate::datatype::Storage<StateType = ArtifactGraphDescription
         specs: ProductionStrategySpecs,
     ) -> Result<(), Error>;
 
+    fn write_snapshot_policy(
+        &mut self,
+        repo: &Repository,
+        artifact: &Artifact,
+        policy: &SnapshotPolicy,
+    ) -> Result<(), Error>;
+
+    fn get_snapshot_policy(
+        &self,
+        repo: &Repository,
+        artifact: &Artifact,
+    ) -> Result<Option<SnapshotPolicy>, Error>;
+
     fn get_production_specs<'ag>(
         &self,
         repo: &Repository,
@@ -1082,6 +1334,10 @@ impl ArtifactGraphDescription {
             if node_idx == part_idx {
                 continue;
             }
+            // Self-partitioning artifacts have no partitioning dependency.
+            if let ArtifactDescription::New { self_partitioning: true, .. } = self.artifacts[node_idx] {
+                continue;
+            }
             let has_partitioning = self.artifacts.parents(node_idx).iter(&self.artifacts)
                 .fold(false, |hp, (e_idx, _p_idx)| {
                     hp || match self.artifacts[e_idx] {
@@ -1178,8 +1434,7 @@ impl ArtifactGraphDescription {
         }
 
         for edge in delta.additions.artifacts.graph().raw_edges() {
-            self.artifacts.add_edge(idx_map[&edge.source()], idx_map[&edge.target()], edge.weight.clone())
-                .expect("TODO");
+            self.artifacts.add_edge(idx_map[&edge.source()], idx_map[&edge.target()], edge.weight.clone())?;
         }
 
         Ok(())
@@ -1229,6 +1484,56 @@ impl Hash for ArtifactGraphDescription {
 }
 
 
+impl DiffableState for ArtifactGraphDescription {
+    /// Diff artifacts added, removed, and, for artifacts identified by UUID,
+    /// modified.
+    fn diff_states(from: Option<&Self>, to: Option<&Self>) -> ContentDiff {
+        fn split(
+            desc: Option<&ArtifactGraphDescription>,
+        ) -> (BTreeMap<Uuid, &ArtifactDescription>, Vec<&ArtifactDescription>) {
+            let mut keyed = BTreeMap::new();
+            let mut unkeyed = vec![];
+            for art in desc.into_iter().flat_map(|desc| desc.artifacts.raw_nodes()) {
+                match art.weight {
+                    ArtifactDescription::New {id: Some(ref id), ..} => {
+                        keyed.insert(id.uuid, &art.weight);
+                    },
+                    ArtifactDescription::Existing(uuid) => {
+                        keyed.insert(uuid, &art.weight);
+                    },
+                    ArtifactDescription::New {id: None, ..} => unkeyed.push(&art.weight),
+                }
+            }
+            (keyed, unkeyed)
+        }
+
+        let (from_keyed, from_unkeyed) = split(from);
+        let (to_keyed, to_unkeyed) = split(to);
+        let mut changes = vec![];
+
+        for (uuid, to_art) in &to_keyed {
+            match from_keyed.get(uuid) {
+                None => changes.push(ContentChange::Added(json!(to_art))),
+                Some(from_art) if from_art != to_art =>
+                    changes.push(ContentChange::Modified(json!(from_art), json!(to_art))),
+                Some(_) => {},
+            }
+        }
+        changes.extend(to_unkeyed.iter()
+            .filter(|art| !from_unkeyed.contains(*art))
+            .map(|art| ContentChange::Added(json!(art))));
+        changes.extend(from_keyed.iter()
+            .filter(|(uuid, _)| !to_keyed.contains_key(*uuid))
+            .map(|(_, art)| ContentChange::Removed(json!(art))));
+        changes.extend(from_unkeyed.iter()
+            .filter(|art| !to_unkeyed.contains(*art))
+            .map(|art| ContentChange::Removed(json!(art))));
+
+        ContentDiff {changes}
+    }
+}
+
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum ArtifactDescription {
     New {
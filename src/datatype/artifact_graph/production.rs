@@ -176,7 +176,8 @@ impl ProductionPolicy for ExtantProductionPolicy {
         let ver_rels = [
             VersionRelation::Dependence(
                 &art_graph[art_graph.artifacts.graph()
-                    .find_edge(dep_art_idx, p_art_idx).expect("TODO")]),
+                    .find_edge(dep_art_idx, p_art_idx)
+                    .expect("Impossible: producer artifact is a dependent of the version's artifact")]),
         ];
 
         // TODO: a mess that could be written much more concisely.
@@ -205,7 +206,8 @@ impl ProductionPolicy for ExtantProductionPolicy {
                                     petgraph::Direction::Incoming)
                                 .filter(|e| e.weight() == art_rel)
                                 .map(|e| e.id())
-                                .nth(0).expect("TODO");
+                                .nth(0)
+                                .expect("Impossible: version relations are derived from artifact relations");
 
                             dependencies.insert(ProductionDependencySpec {
                                 version: new_dep_vers,
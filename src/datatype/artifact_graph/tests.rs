@@ -4,13 +4,16 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
 use maplit::{
+    btreemap,
     btreeset,
     hashmap,
 };
 use uuid::Uuid;
 
 use crate::{
+    DependencyViolation,
     Partition,
+    PartitionKind,
     PartCompletion,
 };
 use crate::datatype::{
@@ -171,19 +174,207 @@ fn simple_blob_prod_ag_fixture(
     (ag_desc, idxs)
 }
 
+/// Create a single blob partitioned by an arbitrary partitioning.
+fn single_blob_ag_fixture(
+) -> (ArtifactGraphDescription, HashMap<&'static str, ArtifactGraphIndex>) {
+
+    let mut ag_desc = ArtifactGraphDescription::new();
+
+    let blob_node = ArtifactDescription::New {
+        id: None,
+        name: Some("Test Blob".into()),
+        dtype: "Blob".into(),
+        self_partitioning: false,
+    };
+    let blob_node_idx = ag_desc.artifacts.add_node(blob_node);
+
+    let part_idx = ag_desc.add_uniform_partitioning(ArtifactDescription::New {
+        id: None,
+        name: Some("Arbitrary Partitioning".into()),
+        dtype: "ArbitraryPartitioning".into(),
+        self_partitioning: false,
+    });
+    let up_idx = ag_desc.add_unary_partitioning();
+
+    let idxs = hashmap![
+        "UP"            => up_idx,
+        "Partitioning"  => part_idx,
+        "Test Blob"     => blob_node_idx,
+    ];
+
+    (ag_desc, idxs)
+}
+
+/// Create and commit a version of the arbitrary partitioning in `idxs` with
+/// the given partitions.
+fn commit_arbitrary_partitioning<'ag>(
+    dtypes_registry: &DatatypesRegistry<TestDatatypes>,
+    repo: &Repository,
+    ag: &'ag ArtifactGraph,
+    idxs: &HashMap<&str, ArtifactGraphIndex>,
+    ver_graph: &mut VersionGraph<'ag>,
+    partition_ids: BTreeSet<PartitionIndex>,
+) -> VersionGraphIndex {
+    let mut model_ctrl = ArtifactGraphDtype::store(repo);
+
+    let up_idx = ver_graph.artifact_versions(&ag[idxs["UP"]])[0];
+    model_ctrl.create_staging_version(
+        repo,
+        ver_graph,
+        up_idx.clone()).unwrap();
+    let part_art_idx = idxs["Partitioning"];
+    let part_idx = ver_graph.versions.add_node(
+        Version::new(&ag[part_art_idx], RepresentationKind::State));
+    let up_part_rel = ag.artifacts.find_edge(idxs["UP"], part_art_idx).unwrap();
+    ver_graph.versions.add_edge(
+        up_idx,
+        part_idx,
+        VersionRelation::Dependence(&ag[up_part_rel])).unwrap();
+
+    {
+        let mut part_control = ArbitraryPartitioning::store(repo);
+
+        model_ctrl.create_staging_version(
+            repo,
+            ver_graph,
+            part_idx).unwrap();
+        let part_state = crate::datatype::Payload::State(
+            ArbitraryPartitioningState { partition_ids });
+        let hunk = Hunk {
+            id: ArbitraryPartitioning::hash_payload(&part_state).into(),
+            version: &ver_graph[part_idx],
+            partition: Partition {
+                partitioning: &ver_graph[up_idx],
+                index: UNARY_PARTITION_INDEX,
+                kind: PartitionKind::Leaf,
+            },
+            representation: RepresentationKind::State,
+            completion: PartCompletion::Complete,
+            precedence: None,
+        };
+        model_ctrl.create_hunk(repo, &hunk).unwrap();
+        part_control.write_hunk(repo, &hunk, &part_state).unwrap();
+    }
+    model_ctrl.commit_version(
+        dtypes_registry,
+        repo,
+        ag,
+        ver_graph,
+        part_idx).unwrap();
+
+    part_idx
+}
+
+/// Write and commit a staged blob version with a payload for each partition
+/// in `payloads`.
+fn commit_blob_version<'ag>(
+    dtypes_registry: &DatatypesRegistry<TestDatatypes>,
+    repo: &Repository,
+    ag: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    v_idx: VersionGraphIndex,
+    payloads: BTreeMap<PartitionIndex, Payload<Vec<u8>, (Vec<usize>, Vec<u8>)>>,
+) {
+    commit_blob_version_kinds(dtypes_registry, repo, ag, ver_graph, v_idx, payloads, BTreeMap::new());
+}
+
+/// As `commit_blob_version`, but with hunks for partitions in `kinds` given
+/// that kind instead of the partitioning's.
+fn commit_blob_version_kinds<'ag>(
+    dtypes_registry: &DatatypesRegistry<TestDatatypes>,
+    repo: &Repository,
+    ag: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    v_idx: VersionGraphIndex,
+    payloads: BTreeMap<PartitionIndex, Payload<Vec<u8>, (Vec<usize>, Vec<u8>)>>,
+    kinds: BTreeMap<PartitionIndex, PartitionKind>,
+) {
+    let mut model_ctrl = ArtifactGraphDtype::store(repo);
+    let mut blob_control = BlobDatatype::store(repo);
+
+    model_ctrl.create_staging_version(
+        repo,
+        ver_graph,
+        v_idx).unwrap();
+
+    let ver_hash = {
+        let partitions = model_ctrl
+            .iter_version_partitions(
+                dtypes_registry,
+                repo,
+                ver_graph,
+                v_idx,
+            ).unwrap()
+            .filter(|partition| payloads.contains_key(&partition.index))
+            .map(|partition| Partition {
+                kind: kinds.get(&partition.index).cloned().unwrap_or(partition.kind),
+                ..partition
+            })
+            .collect::<Vec<_>>();
+
+        let mut ver_hash = DefaultHasher::new();
+        // Partitions are in ascending order, so version hash is correct.
+        for partition in partitions {
+            let payload = &payloads[&partition.index];
+            let hunk = Hunk {
+                id: BlobDatatype::hash_payload(payload).into(),
+                version: &ver_graph[v_idx],
+                partition,
+                representation: match payload {
+                    Payload::State(_) => RepresentationKind::State,
+                    Payload::Delta(_) => RepresentationKind::Delta,
+                },
+                completion: PartCompletion::Complete,
+                precedence: None,
+            };
+            hunk.id.hash.hash(&mut ver_hash);
+
+            model_ctrl.create_hunk(repo, &hunk).unwrap();
+            blob_control.write_hunk(repo, &hunk, payload).unwrap();
+        }
+
+        ver_hash.finish()
+    };
+
+    ver_graph[v_idx].id.hash = ver_hash;
+
+    model_ctrl.commit_version(
+        dtypes_registry,
+        repo,
+        ag,
+        ver_graph,
+        v_idx).unwrap();
+}
+
+/// Compose the states of all partitions of a blob version.
+fn get_blob_states(
+    repo: &Repository,
+    ver_graph: &VersionGraph,
+    v_idx: VersionGraphIndex,
+    partition_ids: BTreeSet<PartitionIndex>,
+) -> BTreeMap<PartitionIndex, Vec<u8>> {
+    let model_ctrl = ArtifactGraphDtype::store(repo);
+    let blob_control = BlobDatatype::store(repo);
+
+    model_ctrl.get_composition_map(repo, ver_graph, v_idx, partition_ids).unwrap()
+        .iter()
+        .map(|(p_id, composition)| (*p_id, blob_control.get_composite_state(repo, composition).unwrap()))
+        .collect()
+}
+
 #[test]
 fn test_artifact_graph_description_reflection() {
     let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
 
     let (ag_0_desc, ag_0_idxs) = simple_blob_prod_ag_fixture(None);
 
-    let (mut ag_1, ag_1_idxs) = ArtifactGraph::from_description(&ag_0_desc, &dtypes_registry, None);
+    let (mut ag_1, ag_1_idxs) = ArtifactGraph::from_description(&ag_0_desc, &dtypes_registry, None).unwrap();
 
     let ag_desc_1 = ag_1.as_description(&dtypes_registry);
     assert!(ag_desc_1.is_valid_state());
     assert_eq!(ag_desc_1, ag_desc_1);
 
-    let (ag_2, _) = ArtifactGraph::from_description(&ag_desc_1, &dtypes_registry, None);
+    let (ag_2, _) = ArtifactGraph::from_description(&ag_desc_1, &dtypes_registry, None).unwrap();
 
     let ag_desc_2 = ag_2.as_description(&dtypes_registry);
     assert_eq!(ag_desc_1, ag_desc_2);
@@ -194,6 +385,49 @@ fn test_artifact_graph_description_reflection() {
     assert_ne!(ag_desc_1, ag_desc_1_changed);
 }
 
+#[test]
+fn test_artifact_graph_description_errors() {
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+
+    let mut unknown_dtype_desc = ArtifactGraphDescription::new();
+    unknown_dtype_desc.artifacts.add_node(ArtifactDescription::New {
+        id: None,
+        name: None,
+        dtype: "Bogus".into(),
+        self_partitioning: false,
+    });
+    match ArtifactGraph::from_description(&unknown_dtype_desc, &dtypes_registry, None) {
+        Err(Error::Model(ModelError::UnknownDatatype(ref name))) => assert_eq!(name, "Bogus"),
+        _ => panic!("Artifact with unknown datatype was created"),
+    }
+
+    let uuid = Uuid::new_v4();
+    let mut mismatch_desc = ArtifactGraphDescription::new();
+    mismatch_desc.artifacts.add_node(ArtifactDescription::New {
+        id: Some(PartialIdentity {uuid, hash: Some(0)}),
+        name: None,
+        dtype: "Blob".into(),
+        self_partitioning: true,
+    });
+    match ArtifactGraph::from_description(&mismatch_desc, &dtypes_registry, None) {
+        Err(Error::Model(ModelError::HashMismatch {uuid: found_uuid, expected: 0, ..})) =>
+            assert_eq!(found_uuid, uuid),
+        _ => panic!("Artifact with mismatched hash was created"),
+    }
+
+    let (mut ag, _) = ArtifactGraph::from_description(
+        &simple_blob_prod_ag_fixture(None).0, &dtypes_registry, None).unwrap();
+    let delta = ArtifactGraphDelta {
+        additions: ArtifactGraphDescription::new(),
+        removals: vec![uuid],
+    };
+    match ag.apply_delta(&delta, &dtypes_registry) {
+        Err(err @ Error::Model(ModelError::UnknownArtifact(_))) =>
+            assert_eq!(err.to_string(), format!("Model error: Unknown artifact: {}", uuid)),
+        _ => panic!("Delta removing an unknown artifact was applied"),
+    }
+}
+
 fn test_create_origin(backend: Backend) {
 
     let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
@@ -244,6 +478,60 @@ fn test_create_get_artifact_graph(backend: Backend) {
     assert_eq!(root_ag.id.hash, root_ag2.id.hash);
 }
 
+fn test_validate_artifact_graph(backend: Backend) {
+
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(backend, &dtypes_registry);
+
+    let (ag_desc, idxs) = simple_blob_prod_ag_fixture(None);
+    let (ag, _) = ArtifactGraph::from_description(&ag_desc, &dtypes_registry, None).unwrap();
+    ag.validate(&dtypes_registry, &repo).unwrap();
+
+    // Producer with two inputs and a reference as output, and a blob with an
+    // undeclared dependency.
+    let mut invalid_desc = ag_desc.clone();
+    invalid_desc.artifacts.add_edge(
+        idxs["Test Blob 1"],
+        idxs["Test Producer 2"],
+        ArtifactRelation::ProducedFrom("input".into())).unwrap();
+    let output_edge = invalid_desc.artifacts.find_edge(
+        idxs["Test Producer 2"], idxs["Test Blob 3"]).unwrap();
+    invalid_desc.artifacts.remove_edge(output_edge);
+    invalid_desc.artifacts.add_edge(
+        idxs["Test Producer 2"],
+        idxs["blobs"],
+        ArtifactRelation::ProducedFrom("output".into())).unwrap();
+    invalid_desc.artifacts.add_edge(
+        idxs["Test Blob 1"],
+        idxs["Test Blob 3"],
+        ArtifactRelation::DtypeDepends(DatatypeRelation {
+            name: "bogus".into()
+        })).unwrap();
+    let (invalid_ag, invalid_idxs) = ArtifactGraph::from_description(&invalid_desc, &dtypes_registry, None).unwrap();
+    let uuid = |name: &str| invalid_ag[invalid_idxs[&idxs[name]]].id.uuid;
+
+    match invalid_ag.validate(&dtypes_registry, &repo) {
+        Err(Error::Model(ModelError::InvalidDependencies(violations))) => {
+            assert_eq!(violations.len(), 3);
+            assert!(violations.contains(&DependencyViolation::Cardinality {
+                artifact: uuid("Test Producer 2"),
+                relation: "input".into(),
+                count: 2,
+            }));
+            assert!(violations.contains(&DependencyViolation::Undeclared {
+                artifact: uuid("Test Blob 3"),
+                relation: "bogus".into(),
+            }));
+            assert!(violations.contains(&DependencyViolation::Datatype {
+                artifact: uuid("Test Producer 2"),
+                relation: "output".into(),
+                dependency: uuid("blobs"),
+            }));
+        },
+        _ => panic!("Invalid artifact graph was validated"),
+    }
+}
+
 fn test_create_get_version_graph(backend: Backend) {
 
     let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
@@ -276,6 +564,7 @@ fn test_create_get_version_graph(backend: Backend) {
             partition: Partition {
                 partitioning: &ver_graph[up_idx],
                 index: part_id,
+                kind: PartitionKind::Leaf,
             },
             representation: RepresentationKind::State,
             completion: PartCompletion::Complete,
@@ -393,6 +682,7 @@ fn test_production(backend: Backend) {
             partition: Partition {
                 partitioning: &ver_graph[up_idx],
                 index: UNARY_PARTITION_INDEX,
+                kind: PartitionKind::Leaf,
             },
             representation: RepresentationKind::State,
             completion: PartCompletion::Complete,
@@ -484,7 +774,7 @@ fn test_production(backend: Backend) {
         );
 
     // Test delta state updates.
-    let blob1_ver2_idx = ver_graph.new_child(blob1_ver_idx, RepresentationKind::Delta);
+    let blob1_ver2_idx = ver_graph.new_child(blob1_ver_idx, RepresentationKind::Delta).unwrap();
     ver_graph.versions.add_edge(part_idx, blob1_ver2_idx,
         VersionRelation::Dependence(
             &ag[ag.artifacts.find_edge(part_art_idx, blob1_art_idx).unwrap()])).unwrap();
@@ -606,6 +896,584 @@ fn test_production(backend: Backend) {
     }
 }
 
+fn test_merge(backend: Backend) {
+    use crate::datatype::merge::{
+        merge,
+        merge_with,
+        MergeOutcome,
+    };
+
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(backend, &dtypes_registry);
+
+    let (ag, idxs) = install_fixture(&dtypes_registry, &repo, &single_blob_ag_fixture).unwrap();
+
+    let mut ver_graph = VersionGraph::new_from_source_artifacts(&ag);
+    let part_idx = commit_arbitrary_partitioning(
+        &dtypes_registry, &repo, &ag, &idxs, &mut ver_graph, btreeset![0, 1]);
+
+    let blob_art_idx = idxs["Test Blob"];
+    let blob_ver_idx = ver_graph.versions.add_node(
+        Version::new(&ag[blob_art_idx], RepresentationKind::State));
+    ver_graph.versions.add_edge(part_idx, blob_ver_idx,
+        VersionRelation::Dependence(
+            &ag[ag.artifacts.find_edge(idxs["Partitioning"], blob_art_idx).unwrap()])).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, blob_ver_idx, btreemap!{
+        0 => Payload::State(vec![0, 1, 2, 3]),
+        1 => Payload::State(vec![0, 1, 2, 3]),
+    });
+
+    // Branches changing disjoint partitions.
+    let a_idx = ver_graph.new_child_same_dependencies(blob_ver_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, a_idx, btreemap!{
+        0 => Payload::Delta((vec![0], vec![10])),
+    });
+    let b_idx = ver_graph.new_child_same_dependencies(blob_ver_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, b_idx, btreemap!{
+        1 => Payload::Delta((vec![1], vec![11])),
+    });
+
+    let merge_idx = match merge(&dtypes_registry, &repo, &ag, &mut ver_graph, &[a_idx, b_idx]).unwrap() {
+        MergeOutcome::Merged(idx) => idx,
+        outcome => panic!("Disjoint changes did not merge: {:?}", outcome),
+    };
+    assert_eq!(ver_graph.get_parents(merge_idx).len(), 2);
+
+    let model_ctrl = ArtifactGraphDtype::store(&repo);
+    let merge_hunks = model_ctrl.get_hunks(&repo, &ver_graph[merge_idx], &ver_graph[part_idx], None).unwrap();
+    assert_eq!(merge_hunks.len(), 2);
+    for hunk in &merge_hunks {
+        let precedent_idx = if hunk.partition.index == 0 {a_idx} else {b_idx};
+        assert_eq!(hunk.precedence, Some(ver_graph[precedent_idx].id.uuid));
+    }
+    assert_eq!(
+        get_blob_states(&repo, &ver_graph, merge_idx, btreeset![0, 1]),
+        btreemap!{
+            0 => vec![10, 1, 2, 3],
+            1 => vec![0, 11, 2, 3],
+        });
+
+    // A branch also changing partition 1 conflicts with `b`.
+    let c_idx = ver_graph.new_child_same_dependencies(a_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, c_idx, btreemap!{
+        1 => Payload::Delta((vec![2], vec![12])),
+    });
+    assert_eq!(ver_graph.merge_base(&[c_idx, b_idx]), Some(blob_ver_idx));
+
+    assert_eq!(
+        merge(&dtypes_registry, &repo, &ag, &mut ver_graph, &[c_idx, b_idx]).unwrap(),
+        MergeOutcome::Conflicted(btreeset![1]));
+
+    let merge_idx = match merge_with::<BlobDatatype, _>(
+            &dtypes_registry, &repo, &ag, &mut ver_graph, &[c_idx, b_idx]).unwrap() {
+        MergeOutcome::Merged(idx) => idx,
+        outcome => panic!("Blob merge did not resolve conflict: {:?}", outcome),
+    };
+    assert_eq!(
+        get_blob_states(&repo, &ver_graph, merge_idx, btreeset![0, 1]),
+        btreemap!{
+            0 => vec![10, 1, 2, 3],
+            1 => vec![0, 11, 12, 3],
+        });
+}
+
+fn test_ancestry(backend: Backend) {
+    use crate::datatype::merge::{
+        merge,
+        MergeOutcome,
+    };
+
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(backend, &dtypes_registry);
+
+    let (ag, idxs) = install_fixture(&dtypes_registry, &repo, &single_blob_ag_fixture).unwrap();
+
+    let mut ver_graph = VersionGraph::new_from_source_artifacts(&ag);
+    let part_idx = commit_arbitrary_partitioning(
+        &dtypes_registry, &repo, &ag, &idxs, &mut ver_graph, btreeset![0, 1]);
+
+    let blob_art_idx = idxs["Test Blob"];
+    let root_idx = ver_graph.versions.add_node(
+        Version::new(&ag[blob_art_idx], RepresentationKind::State));
+    ver_graph.versions.add_edge(part_idx, root_idx,
+        VersionRelation::Dependence(
+            &ag[ag.artifacts.find_edge(idxs["Partitioning"], blob_art_idx).unwrap()])).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, root_idx, btreemap!{
+        0 => Payload::State(vec![0, 1]),
+        1 => Payload::State(vec![0, 1]),
+    });
+
+    let a_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, a_idx, btreemap!{
+        0 => Payload::Delta((vec![0], vec![10])),
+    });
+    let b_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, b_idx, btreemap!{
+        1 => Payload::Delta((vec![1], vec![11])),
+    });
+    // Merge with `b` as the first parent.
+    let merge_idx = match merge(&dtypes_registry, &repo, &ag, &mut ver_graph, &[b_idx, a_idx]).unwrap() {
+        MergeOutcome::Merged(idx) => idx,
+        outcome => panic!("Disjoint changes did not merge: {:?}", outcome),
+    };
+
+    assert_eq!(ver_graph.get_parents(merge_idx), vec![b_idx, a_idx]);
+    assert_eq!(
+        ver_graph.first_parent_history(merge_idx).collect::<Vec<_>>(),
+        vec![merge_idx, b_idx, root_idx]);
+    assert_eq!(
+        ver_graph.ancestors(merge_idx, &VersionRelation::Parent).collect::<BTreeSet<_>>(),
+        btreeset![a_idx, b_idx, root_idx]);
+    assert_eq!(
+        ver_graph.descendants(root_idx, &VersionRelation::Parent).collect::<BTreeSet<_>>(),
+        btreeset![a_idx, b_idx, merge_idx]);
+    assert!(ver_graph.is_ancestor(a_idx, merge_idx));
+    assert!(ver_graph.is_ancestor(merge_idx, merge_idx));
+    assert!(!ver_graph.is_ancestor(a_idx, b_idx));
+    assert_eq!(ver_graph.lowest_common_ancestors(&[a_idx, b_idx]), vec![root_idx]);
+    assert_eq!(ver_graph.lowest_common_ancestors(&[a_idx, merge_idx]), vec![a_idx]);
+
+    let uuids = |v_idxs: &[VersionGraphIndex]| v_idxs.iter()
+        .map(|&v_idx| ver_graph[v_idx].id.uuid)
+        .collect::<Vec<_>>();
+
+    let model_ctrl = ArtifactGraphDtype::store(&repo);
+    assert_eq!(
+        model_ctrl.get_ancestor_uuids(&repo, &ver_graph, merge_idx).unwrap(),
+        uuids(&[a_idx, b_idx, root_idx]).into_iter().collect());
+    assert_eq!(
+        model_ctrl.get_descendant_uuids(&repo, &ver_graph, a_idx).unwrap(),
+        uuids(&[merge_idx]).into_iter().collect());
+    assert!(model_ctrl.is_ancestor(&repo, &ver_graph, root_idx, merge_idx).unwrap());
+    assert!(!model_ctrl.is_ancestor(&repo, &ver_graph, b_idx, a_idx).unwrap());
+    assert_eq!(
+        model_ctrl.get_merge_base_uuids(&repo, &ver_graph, &[a_idx, b_idx]).unwrap(),
+        uuids(&[root_idx]).into_iter().collect());
+    assert_eq!(
+        model_ctrl.get_first_parent_history_uuids(&repo, &ver_graph, merge_idx).unwrap(),
+        uuids(&[merge_idx, b_idx, root_idx]));
+
+    // First parents survive loading the version graph.
+    let loaded_ver_graph = model_ctrl.get_version_graph(&repo, &ag).unwrap();
+    let (loaded_merge_idx, _) = loaded_ver_graph.get_by_id(&ver_graph[merge_idx].id).unwrap();
+    assert_eq!(
+        loaded_ver_graph.first_parent_history(loaded_merge_idx)
+            .map(|v_idx| loaded_ver_graph[v_idx].id.uuid)
+            .collect::<Vec<_>>(),
+        uuids(&[merge_idx, b_idx, root_idx]));
+}
+
+fn test_diff(backend: Backend) {
+    use crate::datatype::diff::{
+        ContentChange,
+        diff,
+        diff_contents,
+        PartitionChange,
+    };
+
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(backend, &dtypes_registry);
+
+    let (ag, idxs) = install_fixture(&dtypes_registry, &repo, &single_blob_ag_fixture).unwrap();
+
+    let mut ver_graph = VersionGraph::new_from_source_artifacts(&ag);
+    let part_idx = commit_arbitrary_partitioning(
+        &dtypes_registry, &repo, &ag, &idxs, &mut ver_graph, btreeset![0, 1, 2]);
+
+    let blob_art_idx = idxs["Test Blob"];
+    let root_idx = ver_graph.versions.add_node(
+        Version::new(&ag[blob_art_idx], RepresentationKind::State));
+    ver_graph.versions.add_edge(part_idx, root_idx,
+        VersionRelation::Dependence(
+            &ag[ag.artifacts.find_edge(idxs["Partitioning"], blob_art_idx).unwrap()])).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, root_idx, btreemap!{
+        0 => Payload::State(vec![0, 1]),
+        1 => Payload::State(vec![0, 1]),
+    });
+
+    let child_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, child_idx, btreemap!{
+        1 => Payload::Delta((vec![0], vec![10])),
+        2 => Payload::State(vec![2]),
+    });
+
+    let diffs = diff(&dtypes_registry, &repo, &ver_graph, root_idx, child_idx).unwrap();
+    assert_eq!(
+        diffs.iter().map(|d| (d.index, d.change)).collect::<Vec<_>>(),
+        vec![(1, PartitionChange::Modified), (2, PartitionChange::Added)]);
+    assert_eq!(diffs[0].from.len(), 1);
+    assert_eq!(diffs[0].to.len(), 2);
+    assert!(diffs[1].from.is_empty());
+    assert_eq!(diffs[1].to[0].version.id, ver_graph[child_idx].id);
+
+    let diffs = diff(&dtypes_registry, &repo, &ver_graph, child_idx, root_idx).unwrap();
+    assert_eq!(
+        diffs.iter().map(|d| (d.index, d.change)).collect::<Vec<_>>(),
+        vec![(1, PartitionChange::Modified), (2, PartitionChange::Removed)]);
+
+    assert!(diff(&dtypes_registry, &repo, &ver_graph, child_idx, child_idx).unwrap().is_empty());
+
+    let content_diffs = diff_contents(&dtypes_registry, &repo, &ver_graph, root_idx, child_idx)
+        .unwrap()
+        .expect("Blobs have content diffs");
+    assert_eq!(content_diffs[&1].changes, vec![
+        ContentChange::Modified(
+            serde_json::json!({"range": [0, 1], "bytes": [0]}),
+            serde_json::json!({"range": [0, 1], "bytes": [10]})),
+    ]);
+    assert_eq!(content_diffs[&2].changes, vec![
+        ContentChange::Added(serde_json::json!({"range": [0, 1], "bytes": [2]})),
+    ]);
+}
+
+fn test_rebase(backend: Backend) {
+    use crate::datatype::rebase::{
+        cherry_pick,
+        rebase,
+        RebaseOutcome,
+    };
+
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(backend, &dtypes_registry);
+
+    let (ag, idxs) = install_fixture(&dtypes_registry, &repo, &single_blob_ag_fixture).unwrap();
+
+    let mut ver_graph = VersionGraph::new_from_source_artifacts(&ag);
+    let part_idx = commit_arbitrary_partitioning(
+        &dtypes_registry, &repo, &ag, &idxs, &mut ver_graph, btreeset![0, 1]);
+
+    let blob_art_idx = idxs["Test Blob"];
+    let root_idx = ver_graph.versions.add_node(
+        Version::new(&ag[blob_art_idx], RepresentationKind::State));
+    ver_graph.versions.add_edge(part_idx, root_idx,
+        VersionRelation::Dependence(
+            &ag[ag.artifacts.find_edge(idxs["Partitioning"], blob_art_idx).unwrap()])).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, root_idx, btreemap!{
+        0 => Payload::State(vec![0, 1, 2, 3]),
+        1 => Payload::State(vec![0, 1, 2, 3]),
+    });
+
+    // Upstream correction to partition 0.
+    let upstream_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, upstream_idx, btreemap!{
+        0 => Payload::Delta((vec![0], vec![10])),
+    });
+
+    // Branch changing partition 1.
+    let a_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, a_idx, btreemap!{
+        1 => Payload::Delta((vec![1], vec![11])),
+    });
+    let b_idx = ver_graph.new_child_same_dependencies(a_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, b_idx, btreemap!{
+        1 => Payload::Delta((vec![2], vec![12])),
+    });
+
+    let rebased = match rebase::<BlobDatatype, _>(
+            &dtypes_registry, &repo, &ag, &mut ver_graph, &[a_idx, b_idx], upstream_idx).unwrap() {
+        RebaseOutcome::Rebased(rebased) => rebased,
+        outcome => panic!("Rebase of disjoint changes failed: {:?}", outcome),
+    };
+    assert_eq!(rebased.len(), 2);
+    assert_eq!(ver_graph.get_parents(rebased[0]), vec![upstream_idx]);
+    assert_eq!(ver_graph.get_parents(rebased[1]), vec![rebased[0]]);
+    assert_eq!(
+        get_blob_states(&repo, &ver_graph, rebased[1], btreeset![0, 1]),
+        btreemap!{
+            0 => vec![10, 1, 2, 3],
+            1 => vec![0, 11, 12, 3],
+        });
+
+    let picked_idx = match cherry_pick::<BlobDatatype, _>(
+            &dtypes_registry, &repo, &ag, &mut ver_graph, upstream_idx, a_idx).unwrap() {
+        RebaseOutcome::Rebased(picked) => picked[0],
+        outcome => panic!("Cherry-pick of disjoint changes failed: {:?}", outcome),
+    };
+    assert_eq!(
+        get_blob_states(&repo, &ver_graph, picked_idx, btreeset![0, 1]),
+        btreemap!{
+            0 => vec![10, 1, 2, 3],
+            1 => vec![0, 11, 2, 3],
+        });
+
+    // A branch also changing partition 0 conflicts with upstream.
+    let c_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, c_idx, btreemap!{
+        0 => Payload::Delta((vec![1], vec![21])),
+    });
+    let version_count = ver_graph.versions.node_count();
+    assert_eq!(
+        rebase::<BlobDatatype, _>(&dtypes_registry, &repo, &ag, &mut ver_graph, &[c_idx], upstream_idx).unwrap(),
+        RebaseOutcome::Conflicted(btreeset![0]));
+    assert_eq!(ver_graph.versions.node_count(), version_count);
+
+    // Picking `b` alone conflicts, since upstream lacks `a`'s change to
+    // partition 1.
+    assert_eq!(
+        cherry_pick::<BlobDatatype, _>(&dtypes_registry, &repo, &ag, &mut ver_graph, b_idx, upstream_idx).unwrap(),
+        RebaseOutcome::Conflicted(btreeset![1]));
+
+    // Deltas are not checked when written, so one that does not apply to
+    // upstream's state conflicts even though upstream did not change it.
+    let d_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, d_idx, btreemap!{
+        1 => Payload::Delta((vec![4], vec![31])),
+    });
+    let version_count = ver_graph.versions.node_count();
+    assert_eq!(
+        rebase::<BlobDatatype, _>(&dtypes_registry, &repo, &ag, &mut ver_graph, &[d_idx], upstream_idx).unwrap(),
+        RebaseOutcome::Conflicted(btreeset![1]));
+    assert_eq!(ver_graph.versions.node_count(), version_count);
+}
+
+fn test_partition_kinds(backend: Backend) {
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(backend, &dtypes_registry);
+
+    let (ag, idxs) = install_fixture(&dtypes_registry, &repo, &single_blob_ag_fixture).unwrap();
+
+    let mut ver_graph = VersionGraph::new_from_source_artifacts(&ag);
+    let part_idx = commit_arbitrary_partitioning(
+        &dtypes_registry, &repo, &ag, &idxs, &mut ver_graph, btreeset![0, 1]);
+
+    let blob_art_idx = idxs["Test Blob"];
+    let root_idx = ver_graph.versions.add_node(
+        Version::new(&ag[blob_art_idx], RepresentationKind::State));
+    ver_graph.versions.add_edge(part_idx, root_idx,
+        VersionRelation::Dependence(
+            &ag[ag.artifacts.find_edge(idxs["Partitioning"], blob_art_idx).unwrap()])).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, root_idx, btreemap!{
+        0 => Payload::State(vec![0, 1, 2, 3]),
+        1 => Payload::State(vec![0, 1, 2, 3]),
+    });
+
+    let ag_control = ArtifactGraphDtype::store(&repo);
+
+    // Partition 0 is redefined as a level with a state, so its composition
+    // starts there.
+    let level_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version_kinds(&dtypes_registry, &repo, &ag, &mut ver_graph, level_idx, btreemap!{
+        0 => Payload::State(vec![4, 5]),
+    }, btreemap!{0 => PartitionKind::Level});
+    let level_delta_idx = ver_graph.new_child_same_dependencies(level_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version_kinds(&dtypes_registry, &repo, &ag, &mut ver_graph, level_delta_idx, btreemap!{
+        0 => Payload::Delta((vec![1], vec![6])),
+    }, btreemap!{0 => PartitionKind::Level});
+
+    let composition_map = ag_control.get_composition_map(&repo, &ver_graph, level_delta_idx, btreeset![0, 1]).unwrap();
+    assert_eq!(composition_map[&0].len(), 2);
+    assert!(composition_map[&0].iter().all(|hunk| hunk.partition.kind == PartitionKind::Level));
+    assert_eq!(composition_map[&1].len(), 1);
+    assert_eq!(composition_map[&1][0].partition.kind, PartitionKind::Leaf);
+    assert_eq!(
+        get_blob_states(&repo, &ver_graph, level_delta_idx, btreeset![0, 1]),
+        btreemap!{
+            0 => vec![4, 6],
+            1 => vec![0, 1, 2, 3],
+        });
+
+    // Likewise for partition 1 as a neighborhood.
+    let neighborhood_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version_kinds(&dtypes_registry, &repo, &ag, &mut ver_graph, neighborhood_idx, btreemap!{
+        1 => Payload::State(vec![7, 8, 9]),
+    }, btreemap!{1 => PartitionKind::Neighborhood});
+    let neighborhood_delta_idx = ver_graph.new_child_same_dependencies(neighborhood_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version_kinds(&dtypes_registry, &repo, &ag, &mut ver_graph, neighborhood_delta_idx, btreemap!{
+        1 => Payload::Delta((vec![0], vec![10])),
+    }, btreemap!{1 => PartitionKind::Neighborhood});
+    assert_eq!(
+        get_blob_states(&repo, &ver_graph, neighborhood_delta_idx, btreeset![0, 1]),
+        btreemap!{
+            0 => vec![0, 1, 2, 3],
+            1 => vec![10, 8, 9],
+        });
+
+    // A kind change starting with a delta has no state to compose onto.
+    let bad_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version_kinds(&dtypes_registry, &repo, &ag, &mut ver_graph, bad_idx, btreemap!{
+        1 => Payload::Delta((vec![0], vec![11])),
+    }, btreemap!{1 => PartitionKind::Neighborhood});
+    assert!(ag_control.get_composition_map(&repo, &ver_graph, bad_idx, btreeset![1]).is_err());
+    assert!(ag_control.get_composition_map(&repo, &ver_graph, bad_idx, btreeset![0]).is_ok());
+}
+
+fn test_squash(backend: Backend) {
+    use crate::datatype::squash::{
+        squash,
+        SquashRevisionPath,
+    };
+
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(backend, &dtypes_registry);
+
+    let (ag, idxs) = install_fixture(&dtypes_registry, &repo, &single_blob_ag_fixture).unwrap();
+
+    let mut ver_graph = VersionGraph::new_from_source_artifacts(&ag);
+    let part_idx = commit_arbitrary_partitioning(
+        &dtypes_registry, &repo, &ag, &idxs, &mut ver_graph, btreeset![0, 1, 2]);
+
+    let blob_art_idx = idxs["Test Blob"];
+    let root_idx = ver_graph.versions.add_node(
+        Version::new(&ag[blob_art_idx], RepresentationKind::State));
+    ver_graph.versions.add_edge(part_idx, root_idx,
+        VersionRelation::Dependence(
+            &ag[ag.artifacts.find_edge(idxs["Partitioning"], blob_art_idx).unwrap()])).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, root_idx, btreemap!{
+        0 => Payload::State(vec![0, 1, 2, 3]),
+        1 => Payload::State(vec![0, 1, 2, 3]),
+        2 => Payload::State(vec![0, 1, 2, 3]),
+    });
+
+    let a_idx = ver_graph.new_child_same_dependencies(root_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, a_idx, btreemap!{
+        0 => Payload::Delta((vec![0], vec![10])),
+    });
+    let b_idx = ver_graph.new_child_same_dependencies(a_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, b_idx, btreemap!{
+        0 => Payload::Delta((vec![1], vec![11])),
+    });
+    let c_idx = ver_graph.new_child_same_dependencies(b_idx, RepresentationKind::Delta).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, c_idx, btreemap!{
+        1 => Payload::Delta((vec![2], vec![12])),
+    });
+
+    let expected = get_blob_states(&repo, &ver_graph, c_idx, btreeset![0, 1, 2]);
+    let ag_control = ArtifactGraphDtype::store(&repo);
+
+    let delta_idx = squash::<BlobDatatype, _>(
+        &dtypes_registry, &repo, &ag, &mut ver_graph, &[a_idx, b_idx, c_idx],
+        RepresentationKind::Delta, None).unwrap();
+    assert_eq!(ver_graph.get_parents(delta_idx), vec![root_idx]);
+    assert_eq!(get_blob_states(&repo, &ver_graph, delta_idx, btreeset![0, 1, 2]), expected);
+    let hunks = ag_control.get_hunks(&repo, &ver_graph[delta_idx], &ver_graph[part_idx], None).unwrap()
+        .into_iter()
+        .map(|hunk| (hunk.partition.index, hunk.representation))
+        .collect::<BTreeMap<_, _>>();
+    assert_eq!(hunks, btreemap!{
+        0 => RepresentationKind::State,
+        1 => RepresentationKind::Delta,
+    });
+
+    let state_idx = squash::<BlobDatatype, _>(
+        &dtypes_registry, &repo, &ag, &mut ver_graph, &[root_idx, a_idx, b_idx, c_idx],
+        RepresentationKind::State, None).unwrap();
+    assert!(ver_graph.get_parents(state_idx).is_empty());
+    assert_eq!(get_blob_states(&repo, &ver_graph, state_idx, btreeset![0, 1, 2]), expected);
+
+    assert!(squash::<BlobDatatype, _>(
+        &dtypes_registry, &repo, &ag, &mut ver_graph, &[root_idx, a_idx],
+        RepresentationKind::Delta, None).is_err(),
+        "Delta squash of a root version succeeded");
+    assert!(squash::<BlobDatatype, _>(
+        &dtypes_registry, &repo, &ag, &mut ver_graph, &[a_idx, c_idx],
+        RepresentationKind::State, None).is_err(),
+        "Squash of versions which are not a chain succeeded");
+    assert!(squash::<BlobDatatype, _>(
+        &dtypes_registry, &repo, &ag, &mut ver_graph, &[a_idx, b_idx],
+        RepresentationKind::State, Some(&SquashRevisionPath {
+            ref_version: root_idx,
+            branch: "master".into(),
+            path: "head".into(),
+        })).is_err(),
+        "Squash moved a branch's HEAD");
+}
+
+fn test_snapshot(backend: Backend) {
+    use crate::datatype::snapshot::{
+        snapshot_artifact,
+        SnapshotPolicy,
+    };
+    use crate::datatype::squash::squash;
+
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(backend, &dtypes_registry);
+
+    let (ag, idxs) = install_fixture(&dtypes_registry, &repo, &single_blob_ag_fixture).unwrap();
+
+    let mut ver_graph = VersionGraph::new_from_source_artifacts(&ag);
+    let part_idx = commit_arbitrary_partitioning(
+        &dtypes_registry, &repo, &ag, &idxs, &mut ver_graph, btreeset![0, 1]);
+
+    let blob_art_idx = idxs["Test Blob"];
+    let root_idx = ver_graph.versions.add_node(
+        Version::new(&ag[blob_art_idx], RepresentationKind::State));
+    ver_graph.versions.add_edge(part_idx, root_idx,
+        VersionRelation::Dependence(
+            &ag[ag.artifacts.find_edge(idxs["Partitioning"], blob_art_idx).unwrap()])).unwrap();
+    commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, root_idx, btreemap!{
+        0 => Payload::State(vec![0, 1, 2, 3]),
+        1 => Payload::State(vec![0, 1, 2, 3]),
+    });
+
+    let mut chain = vec![root_idx];
+    for i in 0..3 {
+        let v_idx = ver_graph.new_child_same_dependencies(
+            *chain.last().unwrap(), RepresentationKind::Delta).unwrap();
+        let mut hunks = btreemap!{
+            0 => Payload::Delta((vec![i], vec![10 + i as u8])),
+        };
+        if i == 0 {
+            hunks.insert(1, Payload::Delta((vec![0], vec![20])));
+        }
+        commit_blob_version(&dtypes_registry, &repo, &ag, &mut ver_graph, v_idx, hunks);
+        chain.push(v_idx);
+    }
+    let tip_idx = chain[3];
+    let expected = get_blob_states(&repo, &ver_graph, tip_idx, btreeset![0, 1]);
+
+    let mut ag_control = ArtifactGraphDtype::store(&repo);
+    let artifact = &ag[blob_art_idx];
+    assert_eq!(ag_control.get_snapshot_policy(&repo, artifact).unwrap(), None);
+    assert_eq!(
+        snapshot_artifact::<BlobDatatype, _>(&dtypes_registry, &repo, &ver_graph, artifact).unwrap(),
+        0);
+
+    let policy = SnapshotPolicy {
+        max_deltas: Some(2),
+        max_bytes: None,
+    };
+    ag_control.write_snapshot_policy(&repo, artifact, &policy).unwrap();
+    assert_eq!(ag_control.get_snapshot_policy(&repo, artifact).unwrap(), Some(policy));
+
+    // Only partition 0 of the second delta version reaches two deltas.
+    assert_eq!(
+        snapshot_artifact::<BlobDatatype, _>(&dtypes_registry, &repo, &ver_graph, artifact).unwrap(),
+        1);
+    assert_eq!(get_blob_states(&repo, &ver_graph, tip_idx, btreeset![0, 1]), expected);
+
+    let composition_map = ag_control.get_composition_map(&repo, &ver_graph, tip_idx, btreeset![0, 1]).unwrap();
+    assert_eq!(composition_map[&0].len(), 2);
+    assert_eq!(composition_map[&0][1].version.id, ver_graph[chain[2]].id);
+    assert_eq!(composition_map[&0][1].representation, RepresentationKind::State);
+    assert_eq!(composition_map[&1].len(), 2);
+
+    // Snapshotting is idempotent.
+    assert_eq!(
+        snapshot_artifact::<BlobDatatype, _>(&dtypes_registry, &repo, &ver_graph, artifact).unwrap(),
+        0);
+
+    // Squashes onto a snapshotted base copy hunks composed on the snapshot.
+    let squash_hunks = |ver_graph: &VersionGraph, v_idx| ag_control
+        .get_hunks(&repo, &ver_graph[v_idx], &ver_graph[part_idx], None).unwrap()
+        .into_iter()
+        .map(|hunk| (hunk.partition.index, hunk.representation))
+        .collect::<BTreeMap<_, _>>();
+    let squash_idx = squash::<BlobDatatype, _>(
+        &dtypes_registry, &repo, &ag, &mut ver_graph, &[tip_idx],
+        RepresentationKind::Delta, None).unwrap();
+    assert_eq!(get_blob_states(&repo, &ver_graph, squash_idx, btreeset![0, 1]), expected);
+    assert_eq!(squash_hunks(&ver_graph, squash_idx), btreemap!{0 => RepresentationKind::Delta});
+
+    // Squashes across a snapshot compose the tip's state, since its
+    // composition no longer passes through the base's hunks.
+    let squash_idx = squash::<BlobDatatype, _>(
+        &dtypes_registry, &repo, &ag, &mut ver_graph, &[chain[2], tip_idx],
+        RepresentationKind::Delta, None).unwrap();
+    assert_eq!(ver_graph.get_parents(squash_idx), vec![chain[1]]);
+    assert_eq!(get_blob_states(&repo, &ver_graph, squash_idx, btreeset![0, 1]), expected);
+    assert_eq!(squash_hunks(&ver_graph, squash_idx), btreemap!{0 => RepresentationKind::State});
+}
+
 macro_rules! backend_test_suite {
     ( $backend_name:ident, $backend:path ) => {
         mod $backend_name {
@@ -621,6 +1489,11 @@ macro_rules! backend_test_suite {
                 super::test_create_get_artifact_graph($backend);
             }
 
+            #[test]
+            fn test_validate_artifact_graph() {
+                super::test_validate_artifact_graph($backend);
+            }
+
             #[test]
             fn test_create_get_version_graph() {
                 super::test_create_get_version_graph($backend);
@@ -630,6 +1503,41 @@ macro_rules! backend_test_suite {
             fn test_production() {
                 super::test_production($backend);
             }
+
+            #[test]
+            fn test_merge() {
+                super::test_merge($backend);
+            }
+
+            #[test]
+            fn test_ancestry() {
+                super::test_ancestry($backend);
+            }
+
+            #[test]
+            fn test_diff() {
+                super::test_diff($backend);
+            }
+
+            #[test]
+            fn test_rebase() {
+                super::test_rebase($backend);
+            }
+
+            #[test]
+            fn test_partition_kinds() {
+                super::test_partition_kinds($backend);
+            }
+
+            #[test]
+            fn test_squash() {
+                super::test_squash($backend);
+            }
+
+            #[test]
+            fn test_snapshot() {
+                super::test_snapshot($backend);
+            }
         }
     }
 }
@@ -640,3 +1548,63 @@ backend_test_suite!(debug_filesystem, Backend::DebugFilesystem);
 
 #[cfg(feature="backend-postgres")]
 backend_test_suite!(postgres, Backend::Postgres);
+
+/// Round-trip payloads of each representation through each encoding of the
+/// shared Postgres `hunk_payload` table.
+#[cfg(feature="backend-postgres")]
+#[test]
+fn test_postgres_payload_encodings() {
+    use crate::store::postgres::datatype::payload::{
+        read_payload,
+        write_payload,
+        PayloadEncoding,
+    };
+
+    let dtypes_registry = crate::datatype::testing::init_dtypes_registry::<TestDatatypes>();
+    let repo = crate::repo::testing::init_repo(Backend::Postgres, &dtypes_registry);
+
+    let (ag, idxs) = install_fixture(&dtypes_registry, &repo, &single_blob_ag_fixture).unwrap();
+
+    let mut ver_graph = VersionGraph::new_from_source_artifacts(&ag);
+    let part_idx = commit_arbitrary_partitioning(
+        &dtypes_registry, &repo, &ag, &idxs, &mut ver_graph, btreeset![0, 1, 2, 3]);
+
+    let blob_art_idx = idxs["Test Blob"];
+    let blob_ver_idx = ver_graph.versions.add_node(
+        Version::new(&ag[blob_art_idx], RepresentationKind::State));
+    ver_graph.versions.add_edge(part_idx, blob_ver_idx,
+        VersionRelation::Dependence(
+            &ag[ag.artifacts.find_edge(idxs["Partitioning"], blob_art_idx).unwrap()])).unwrap();
+
+    let mut model_ctrl = ArtifactGraphDtype::store(&repo);
+    model_ctrl.create_staging_version(&repo, &ver_graph, blob_ver_idx).unwrap();
+
+    let payloads: BTreeMap<_, (PayloadEncoding, Payload<Vec<u8>, (Vec<usize>, Vec<u8>)>)> = btreemap!{
+        0 => (PayloadEncoding::Jsonb, Payload::State(vec![0, 1, 2, 3])),
+        1 => (PayloadEncoding::Jsonb, Payload::Delta((vec![1], vec![11]))),
+        2 => (PayloadEncoding::Bytea, Payload::State(vec![0, 255, 2, 3])),
+        3 => (PayloadEncoding::Bytea, Payload::Delta((vec![2], vec![12]))),
+    };
+
+    let partitions = model_ctrl
+        .iter_version_partitions(&dtypes_registry, &repo, &ver_graph, blob_ver_idx).unwrap()
+        .collect::<Vec<_>>();
+    for partition in partitions {
+        let (encoding, payload) = &payloads[&partition.index];
+        let hunk = Hunk {
+            id: BlobDatatype::hash_payload(payload).into(),
+            version: &ver_graph[blob_ver_idx],
+            partition,
+            representation: match payload {
+                Payload::State(_) => RepresentationKind::State,
+                Payload::Delta(_) => RepresentationKind::Delta,
+            },
+            completion: PartCompletion::Complete,
+            precedence: None,
+        };
+
+        model_ctrl.create_hunk(&repo, &hunk).unwrap();
+        write_payload(&repo, &hunk, payload, *encoding).unwrap();
+        assert_eq!(&read_payload::<Vec<u8>, (Vec<usize>, Vec<u8>)>(&repo, &hunk).unwrap(), payload);
+    }
+}
@@ -2,13 +2,23 @@ use heraclitus_macros::{
     DatatypeMarker,
     stored_datatype_controller,
 };
+use serde_json::json;
 
-use crate::RepresentationKind;
+use crate::{
+    ModelError,
+    RepresentationKind,
+};
 use super::{
     DatatypeMeta,
-    InterfaceControllerEnum,
+    InterfaceController,
     Reflection,
 };
+use super::diff::{
+    ContentChange,
+    ContentDiff,
+    ContentDiffController,
+    DiffableState,
+};
 
 
 #[derive(Default, DatatypeMarker)]
@@ -19,19 +29,21 @@ impl DatatypeMeta for BlobDatatype {
     const VERSION: u64 = 1;
 }
 
-impl<T: InterfaceControllerEnum> super::Model<T> for BlobDatatype {
+impl<T: InterfaceController<ContentDiffController>> super::Model<T> for BlobDatatype {
     fn reflection(&self) -> Reflection<T> {
         Reflection {
             representations: enumset::enum_set!(
                         RepresentationKind::State |
                         RepresentationKind::Delta |
                     ),
-            implements: vec![],
+            implements: vec![
+                <T as InterfaceController<ContentDiffController>>::VARIANT,
+            ],
             dependencies: vec![],
         }
     }
 
-    datatype_controllers!(BlobDatatype, ());
+    datatype_controllers!(BlobDatatype, (ContentDiffController));
 }
 
 pub(crate) type StateType = Vec<u8>;
@@ -44,12 +56,97 @@ impl crate::datatype::ComposableState for BlobDatatype {
     fn compose_state(
         state: &mut Self::StateType,
         delta: &Self::DeltaType,
-    ) {
+    ) -> Result<(), crate::Error> {
+        Self::validate_delta(state, delta)?;
+
         for (&idx, &val) in delta.0.iter().zip(delta.1.iter()) {
             state[idx] = val;
         }
+
+        Ok(())
+    }
+
+    fn validate_delta(
+        state: &Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), crate::Error> {
+        if delta.0.len() != delta.1.len() {
+            return Err(ModelError::Other("Blob delta has a different number of indices and bytes".into()).into());
+        }
+        if let Some(idx) = delta.0.iter().find(|&&idx| idx >= state.len()) {
+            return Err(ModelError::Other(format!(
+                "Blob delta index {} is outside blob of {} bytes", idx, state.len())).into());
+        }
+
+        Ok(())
     }
 }
 
 #[stored_datatype_controller(BlobDatatype)]
 pub trait Storage: super::Storage {}
+
+impl super::merge::MergeStates for BlobDatatype {
+    /// Merge equal length blobs byte-wise, taking each byte changed from the
+    /// base, so long as no two blobs change it differently.
+    fn merge_states(
+        base: Option<&Self::StateType>,
+        states: &[Self::StateType],
+    ) -> Result<Option<Self::StateType>, crate::Error> {
+        let base = match base {
+            Some(base) if states.iter().all(|state| state.len() == base.len()) => base,
+            _ => return Ok(None),
+        };
+
+        let mut merged = base.clone();
+        for (i, byte) in merged.iter_mut().enumerate() {
+            let mut changes = states.iter()
+                .map(|state| state[i])
+                .filter(|&b| b != base[i]);
+            if let Some(change) = changes.next() {
+                if changes.any(|b| b != change) {
+                    return Ok(None);
+                }
+                *byte = change;
+            }
+        }
+
+        Ok(Some(merged))
+    }
+}
+
+impl DiffableState for StateType {
+    /// Diff blobs as maximal ranges of differing bytes. Bytes beyond the end
+    /// of the shorter blob are added or removed.
+    fn diff_states(from: Option<&Self>, to: Option<&Self>) -> ContentDiff {
+        let empty = vec![];
+        let from = from.unwrap_or(&empty);
+        let to = to.unwrap_or(&empty);
+        let range = |blob: &Self, start: usize, end: usize| json!({
+            "range": [start, end],
+            "bytes": &blob[start..end],
+        });
+
+        let common = from.len().min(to.len());
+        let mut changes = vec![];
+        let mut i = 0;
+        while i < common {
+            if from[i] == to[i] {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < common && from[i] != to[i] {
+                i += 1;
+            }
+            changes.push(ContentChange::Modified(range(from, start, i), range(to, start, i)));
+        }
+        if to.len() > common {
+            changes.push(ContentChange::Added(range(to, common, to.len())));
+        }
+        if from.len() > common {
+            changes.push(ContentChange::Removed(range(from, common, from.len())));
+        }
+
+        ContentDiff {changes}
+    }
+}
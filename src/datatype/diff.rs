@@ -0,0 +1,240 @@
+//! Comparing the partitions of two versions of an artifact.
+//!
+//! `diff` reports which partitions differ between two versions, along with
+//! the compositions of hunks for each partition in either version. Consumers
+//! such as producers can then restrict work to the changed partitions.
+//!
+//! Datatypes whose states implement `DiffableState` also provide the
+//! `ContentDiff` interface, with which `diff_contents` describes what changed
+//! within each partition.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use heraclitus_macros::interface;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    Composition,
+    Error,
+    Identity,
+    ModelError,
+    PartitionIndex,
+    VersionGraph,
+    VersionGraphIndex,
+};
+use super::{
+    DatatypeEnum,
+    DatatypeMarker,
+    DatatypesRegistry,
+    InterfaceController,
+};
+use super::artifact_graph::{
+    ArtifactGraphDtype,
+    Storage as ArtifactGraphStorage,
+};
+use super::partitioning::PartitioningState;
+use crate::repo::Repository;
+
+
+/// A change to an item of a state, such as an element of a collection or a
+/// range of a sequence. Items are datatype-specific.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum ContentChange {
+    /// An item present only in the newer state.
+    Added(Value),
+    /// An item present only in the older state.
+    Removed(Value),
+    /// An item changed from the first value to the second.
+    Modified(Value, Value),
+}
+
+/// Semantic diff of two states of a datatype. `Display` renders a
+/// human-readable summary, one change per line.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct ContentDiff {
+    pub changes: Vec<ContentChange>,
+}
+
+impl ContentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for ContentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            match change {
+                ContentChange::Added(item) => writeln!(f, "+ {}", item)?,
+                ContentChange::Removed(item) => writeln!(f, "- {}", item)?,
+                ContentChange::Modified(from, to) => writeln!(f, "~ {} -> {}", from, to)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// States which can be semantically diffed.
+pub trait DiffableState: Sized {
+    /// Diff states `from` and `to`, either of which is `None` if the
+    /// partition has no state.
+    fn diff_states(from: Option<&Self>, to: Option<&Self>) -> ContentDiff;
+}
+
+#[interface]
+pub trait ContentDiffController {
+    /// Diff the composite states of two compositions of a partition. Empty
+    /// compositions have no state.
+    fn diff_compositions(
+        &self,
+        repo: &Repository,
+        from: &Composition,
+        to: &Composition,
+    ) -> Result<ContentDiff, Error>;
+}
+
+impl<S: 'static, MC> ContentDiffController for MC
+        where
+            S: DiffableState,
+            MC: super::Storage<StateType = S> {
+    fn diff_compositions(
+        &self,
+        repo: &Repository,
+        from: &Composition,
+        to: &Composition,
+    ) -> Result<ContentDiff, Error> {
+        let state = |composition: &Composition| if composition.is_empty() {
+            Ok(None)
+        } else {
+            self.get_composite_state(repo, composition).map(Some)
+        };
+
+        Ok(S::diff_states(state(from)?.as_ref(), state(to)?.as_ref()))
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PartitionChange {
+    /// The partition has a state only in the newer version.
+    Added,
+    /// The partition has a state only in the older version.
+    Removed,
+    /// The partition has different compositions in the two versions.
+    Modified,
+}
+
+#[derive(Debug)]
+pub struct PartitionDiff<'ag: 'vg1 + 'vg2, 'vg1, 'vg2> {
+    pub index: PartitionIndex,
+    pub change: PartitionChange,
+    /// Composition of the partition in the older version, empty if `Added`.
+    pub from: Composition<'ag, 'vg1, 'vg2>,
+    /// Composition of the partition in the newer version, empty if `Removed`.
+    pub to: Composition<'ag, 'vg1, 'vg2>,
+}
+
+/// Find partitions that differ between versions `from` and `to` of an
+/// artifact, in ascending partition order.
+///
+/// Partitions are compared by the hunks composing them, so partitions whose
+/// states are equal but were reached by different hunks are `Modified`.
+/// Partitions absent from a version's partitioning, or never populated in
+/// it, have no state in that version.
+pub fn diff<'ag: 'r, 'r, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    ver_graph: &'r VersionGraph<'ag>,
+    from: VersionGraphIndex,
+    to: VersionGraphIndex,
+) -> Result<Vec<PartitionDiff<'ag, 'r, 'r>>, Error>
+        where
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState>
+{
+    let ag_control = ArtifactGraphDtype::store(repo);
+
+    if ver_graph[from].artifact.id != ver_graph[to].artifact.id {
+        return Err(ModelError::Other("Diffed versions are not versions of the same artifact".into()).into());
+    }
+
+    let mut partition_ids = BTreeSet::new();
+    for &v_idx in &[from, to] {
+        let (part_idx, _) = ver_graph.get_partitioning(v_idx)
+            .ok_or_else(|| ModelError::MalformedGraph("Version has no partitioning".into()))?;
+        partition_ids.extend(ag_control.get_partitioning_partitions(
+            dtypes_registry, repo, ver_graph, part_idx)?.keys().cloned());
+    }
+
+    let mut from_map = ag_control.get_partitioned_composition_map(
+        dtypes_registry, repo, ver_graph, from, partition_ids.clone())?;
+    let mut to_map = ag_control.get_partitioned_composition_map(
+        dtypes_registry, repo, ver_graph, to, partition_ids.clone())?;
+
+    let hunk_ids = |composition: &Composition| composition.iter()
+        .map(|hunk| hunk.id)
+        .collect::<Vec<Identity>>();
+
+    let mut diffs = vec![];
+    for p_id in partition_ids {
+        let from_comp = from_map.remove(&p_id).unwrap_or_default();
+        let to_comp = to_map.remove(&p_id).unwrap_or_default();
+
+        let change = match (from_comp.is_empty(), to_comp.is_empty()) {
+            (true, true) => continue,
+            (true, false) => PartitionChange::Added,
+            (false, true) => PartitionChange::Removed,
+            (false, false) => {
+                if hunk_ids(&from_comp) == hunk_ids(&to_comp) {
+                    continue;
+                }
+                PartitionChange::Modified
+            },
+        };
+
+        diffs.push(PartitionDiff {
+            index: p_id,
+            change,
+            from: from_comp,
+            to: to_comp,
+        });
+    }
+
+    Ok(diffs)
+}
+
+/// Diff the contents of partitions changed between versions `from` and `to`
+/// of an artifact, keyed by partition. `None` if the artifact's datatype does
+/// not provide the `ContentDiff` interface.
+pub fn diff_contents<'ag, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    ver_graph: &VersionGraph<'ag>,
+    from: VersionGraphIndex,
+    to: VersionGraphIndex,
+) -> Result<Option<BTreeMap<PartitionIndex, ContentDiff>>, Error>
+        where
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ContentDiffController>
+{
+    let diff_control: Box<dyn ContentDiffController> = match dtypes_registry
+            .get_model_interface::<dyn ContentDiffController>(&ver_graph[to].artifact.dtype_uuid) {
+        Some(gen) => gen(repo),
+        None => return Ok(None),
+    };
+
+    diff(dtypes_registry, repo, ver_graph, from, to)?.into_iter()
+        .map(|partition_diff| Ok((
+            partition_diff.index,
+            diff_control.diff_compositions(repo, &partition_diff.from, &partition_diff.to)?)))
+        .collect::<Result<BTreeMap<_, _>, Error>>()
+        .map(Some)
+}
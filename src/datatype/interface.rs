@@ -47,6 +47,13 @@ lazy_static! {
         },
         extends: HashSet::new(),
     };
+
+    pub static ref INTERFACE_CONTENT_DIFF_DESC: InterfaceDescription = InterfaceDescription {
+        interface: Interface {
+            name: "ContentDiff",
+        },
+        extends: HashSet::new(),
+    };
 }
 
 
@@ -150,7 +157,65 @@ mod tests {
     use super::*;
 
     use enumset::enum_set;
-    use maplit::hashmap;
+    use maplit::{hashmap, hashset};
+
+    use crate::datatype::{
+        DependencyTypeRestriction,
+        InterfaceRegistry,
+    };
+    use crate::datatype::testing::init_default_dtypes_registry;
+
+    #[test]
+    fn test_interface_extension() {
+        let descriptions = vec![
+            InterfaceDescription {
+                interface: Interface {name: "A"},
+                extends: HashSet::new(),
+            },
+            InterfaceDescription {
+                interface: Interface {name: "B"},
+                extends: hashset!["A"],
+            },
+            InterfaceDescription {
+                interface: Interface {name: "C"},
+                extends: hashset!["B"],
+            },
+            InterfaceDescription {
+                interface: Interface {name: "D"},
+                extends: HashSet::new(),
+            },
+        ];
+        let mut ifaces = InterfaceRegistry::new();
+        ifaces.register_interfaces(&descriptions.iter().collect::<Vec<_>>());
+
+        let [a, b, c, d] = [
+            ifaces.get_index("A"),
+            ifaces.get_index("B"),
+            ifaces.get_index("C"),
+            ifaces.get_index("D"),
+        ];
+        assert!(ifaces.extends(c, a));
+        assert!(ifaces.extends(b, b));
+        assert!(!ifaces.extends(a, c));
+        assert!(!ifaces.extends(d, a));
+        assert_eq!(ifaces.sub_interfaces(a), hashset![a, b, c]);
+    }
+
+    #[test]
+    fn test_implements_interface() {
+        let dtypes_registry = init_default_dtypes_registry();
+        let unary = dtypes_registry.get_datatype("UnaryPartitioning").unwrap();
+        let blob = dtypes_registry.get_datatype("Blob").unwrap();
+
+        assert!(dtypes_registry.implements_interface(unary, "Partitioning"));
+        assert!(!dtypes_registry.implements_interface(blob, "Partitioning"));
+        assert!(!dtypes_registry.implements_interface(blob, "Unknown"));
+
+        let restriction = DependencyTypeRestriction::ImplementsInterface(hashset!["Partitioning"]);
+        assert!(dtypes_registry.satisfies_type_restriction(unary, &restriction));
+        assert!(!dtypes_registry.satisfies_type_restriction(blob, &restriction));
+        assert!(dtypes_registry.iter_implementors("Partitioning").all(|dtype| dtype.name != "Blob"));
+    }
 
     #[test]
     fn test_production_representation_capability_matching() {
@@ -0,0 +1,331 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+
+use heraclitus_macros::{
+    DatatypeMarker,
+    stored_datatype_controller,
+};
+
+use crate::{
+    Error,
+    ModelError,
+    RepresentationKind,
+};
+use super::{
+    DatatypeMeta,
+    InterfaceControllerEnum,
+    Reflection,
+};
+
+
+/// JSON documents whose deltas are RFC 6902 JSON Patches.
+#[derive(Default, DatatypeMarker)]
+pub struct JsonDatatype;
+
+impl DatatypeMeta for JsonDatatype {
+    const NAME: &'static str = "Json";
+    const VERSION: u64 = 1;
+}
+
+impl<T: InterfaceControllerEnum> super::Model<T> for JsonDatatype {
+    fn reflection(&self) -> Reflection<T> {
+        Reflection {
+            representations: enumset::enum_set!(
+                        RepresentationKind::State |
+                        RepresentationKind::Delta |
+                    ),
+            implements: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    datatype_controllers!(JsonDatatype, ());
+}
+
+/// A JSON value that can be hashed.
+///
+/// Hashing is over the serialized value, which is canonical because
+/// `serde_json` object maps are sorted by key.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct JsonValue(pub Value);
+
+impl Hash for JsonValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_string().hash(state);
+    }
+}
+
+impl From<Value> for JsonValue {
+    fn from(value: Value) -> Self {
+        JsonValue(value)
+    }
+}
+
+/// A JSON Patch operation. Paths are JSON Pointers (RFC 6901).
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add {
+        path: String,
+        value: JsonValue,
+    },
+    Remove {
+        path: String,
+    },
+    Replace {
+        path: String,
+        value: JsonValue,
+    },
+    Move {
+        from: String,
+        path: String,
+    },
+    Copy {
+        from: String,
+        path: String,
+    },
+    Test {
+        path: String,
+        value: JsonValue,
+    },
+}
+
+pub type StateType = JsonValue;
+pub type DeltaType = Vec<PatchOperation>;
+
+impl crate::datatype::ComposableState for JsonDatatype {
+    type StateType = crate::datatype::json::StateType;
+    type DeltaType = crate::datatype::json::DeltaType;
+
+    fn compose_state(
+        state: &mut Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        apply_patch(&mut state.0, delta)
+    }
+
+    fn validate_delta(
+        state: &Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        apply_patch(&mut state.0.clone(), delta)
+    }
+}
+
+#[stored_datatype_controller(JsonDatatype)]
+pub trait Storage: super::Storage {}
+
+
+fn patch_error(message: &str, path: &str) -> Error {
+    Error::Model(ModelError::Other(format!("JSON patch {}: {}", message, path)))
+}
+
+/// Split a non-root JSON Pointer into its parent pointer and unescaped last
+/// reference token.
+fn split_pointer(path: &str) -> Result<(&str, String), Error> {
+    match path.rfind('/') {
+        Some(idx) if path.starts_with('/') =>
+            Ok((&path[..idx], path[idx + 1..].replace("~1", "/").replace("~0", "~"))),
+        _ => Err(patch_error("malformed pointer", path)),
+    }
+}
+
+fn parse_index(token: &str, path: &str) -> Result<usize, Error> {
+    if token.is_empty()
+            || !token.bytes().all(|b| b.is_ascii_digit())
+            || (token.len() > 1 && token.starts_with('0')) {
+        return Err(patch_error("malformed array index", path));
+    }
+    token.parse().map_err(|_| patch_error("malformed array index", path))
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), Error> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+
+    let (parent, token) = split_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(token, value);
+        },
+        Some(Value::Array(vec)) => {
+            let idx = if token == "-" { vec.len() } else { parse_index(&token, path)? };
+            if idx > vec.len() {
+                return Err(patch_error("array index out of bounds", path));
+            }
+            vec.insert(idx, value);
+        },
+        _ => return Err(patch_error("parent does not exist", path)),
+    }
+
+    Ok(())
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, Error> {
+    let (parent, token) = split_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(Value::Object(map)) =>
+            map.remove(&token).ok_or_else(|| patch_error("target does not exist", path)),
+        Some(Value::Array(vec)) => {
+            let idx = parse_index(&token, path)?;
+            if idx >= vec.len() {
+                return Err(patch_error("array index out of bounds", path));
+            }
+            Ok(vec.remove(idx))
+        },
+        _ => Err(patch_error("parent does not exist", path)),
+    }
+}
+
+/// Apply a JSON Patch to a document.
+///
+/// The patch is applied atomically: if any operation fails the document is
+/// left unmodified.
+pub fn apply_patch(doc: &mut Value, patch: &[PatchOperation]) -> Result<(), Error> {
+    let mut patched = doc.clone();
+
+    for operation in patch {
+        match operation {
+            PatchOperation::Add {path, value} => add(&mut patched, path, value.0.clone())?,
+            PatchOperation::Remove {path} => { remove(&mut patched, path)?; },
+            PatchOperation::Replace {path, value} => {
+                let target = patched.pointer_mut(path)
+                    .ok_or_else(|| patch_error("target does not exist", path))?;
+                *target = value.0.clone();
+            },
+            PatchOperation::Move {from, path} => {
+                if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                    return Err(patch_error("cannot move a value into its own child", path));
+                }
+                let value = remove(&mut patched, from)?;
+                add(&mut patched, path, value)?;
+            },
+            PatchOperation::Copy {from, path} => {
+                let value = patched.pointer(from)
+                    .cloned()
+                    .ok_or_else(|| patch_error("source does not exist", from))?;
+                add(&mut patched, path, value)?;
+            },
+            PatchOperation::Test {path, value} => {
+                if patched.pointer(path) != Some(&value.0) {
+                    return Err(patch_error("test failed", path));
+                }
+            },
+        }
+    }
+
+    *doc = patched;
+    Ok(())
+}
+
+fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn diff_into(old: &Value, new: &Value, path: &str, patch: &mut DeltaType) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let child = format!("{}/{}", path, escape_token(key));
+                match new_map.get(key) {
+                    Some(new_value) => diff_into(old_value, new_value, &child, patch),
+                    None => patch.push(PatchOperation::Remove {path: child}),
+                }
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    patch.push(PatchOperation::Add {
+                        path: format!("{}/{}", path, escape_token(key)),
+                        value: new_value.clone().into(),
+                    });
+                }
+            }
+        },
+        _ if old == new => {},
+        _ => patch.push(PatchOperation::Replace {
+            path: path.to_owned(),
+            value: new.clone().into(),
+        }),
+    }
+}
+
+/// Compute a JSON Patch transforming `old` into `new`.
+///
+/// Objects are diffed by key; any other differing values, including arrays,
+/// are replaced wholesale.
+pub fn diff(old: &Value, new: &Value) -> DeltaType {
+    let mut patch = vec![];
+    diff_into(old, new, "", &mut patch);
+    patch
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_patch() {
+        let mut doc = json!({"a": {"b": [1, 2]}, "c": "d"});
+        let patch: DeltaType = serde_json::from_value(json!([
+            {"op": "add", "path": "/a/b/1", "value": 3},
+            {"op": "add", "path": "/a/b/-", "value": 4},
+            {"op": "remove", "path": "/c"},
+            {"op": "copy", "from": "/a/b", "path": "/e~1f"},
+            {"op": "move", "from": "/a", "path": "/g"},
+            {"op": "replace", "path": "/g/b/0", "value": 0},
+            {"op": "test", "path": "/e~1f/1", "value": 3},
+        ])).unwrap();
+
+        apply_patch(&mut doc, &patch).unwrap();
+        assert_eq!(doc, json!({"e/f": [1, 3, 2, 4], "g": {"b": [0, 3, 2, 4]}}));
+
+        let failing: DeltaType = serde_json::from_value(json!([
+            {"op": "remove", "path": "/g"},
+            {"op": "test", "path": "/g", "value": null},
+        ])).unwrap();
+        assert!(apply_patch(&mut doc, &failing).is_err());
+        assert!(doc.get("g").is_some(), "Failed patch should not modify document");
+    }
+
+    #[test]
+    fn test_validate_delta() {
+        use crate::datatype::ComposableState;
+
+        let state = JsonValue(json!({"a": [1]}));
+        let applicable: DeltaType = serde_json::from_value(json!([
+            {"op": "add", "path": "/a/-", "value": 2},
+        ])).unwrap();
+        assert!(JsonDatatype::validate_delta(&state, &applicable).is_ok());
+
+        let inapplicable: DeltaType = serde_json::from_value(json!([
+            {"op": "remove", "path": "/b"},
+        ])).unwrap();
+        assert!(JsonDatatype::validate_delta(&state, &inapplicable).is_err());
+        assert!(JsonDatatype::compose_state(&mut state.clone(), &inapplicable).is_err(),
+            "Inapplicable patch was composed");
+    }
+
+    #[test]
+    fn test_diff_composes() {
+        let old = json!({"a": 1, "b": {"c": [1, 2], "d~/": true}, "e": null});
+        let new = json!({"a": 1, "b": {"c": [2], "f": "g"}, "h": 0});
+
+        let mut patched = old.clone();
+        apply_patch(&mut patched, &diff(&old, &new)).unwrap();
+        assert_eq!(patched, new);
+        assert!(diff(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use heraclitus_macros::{
+    DatatypeMarker,
+    stored_datatype_controller,
+};
+
+use crate::{
+    Error,
+    ModelError,
+    PartitionIndex,
+    RepresentationKind,
+};
+use super::{
+    DatatypeMeta,
+    InterfaceControllerEnum,
+    Reflection,
+};
+use super::json::JsonValue;
+use super::partitioning::Partitioning;
+use super::partitioning::hash::KeyPartitioning;
+use super::repartition::{
+    MergePartitions,
+    SplitPartitions,
+};
+
+
+/// Maps of string keys to JSON or byte values, whose deltas are sparse puts
+/// and tombstones.
+///
+/// Keys may be spread across the partitions of the artifact's partitioning,
+/// such as a `HashPartitioning`, so that each hunk holds only the keys of its
+/// partition and only partitions with changed keys need new hunks. See
+/// `partition_state` and `partition_delta`.
+#[derive(Default, DatatypeMarker)]
+pub struct KeyValueDatatype;
+
+impl DatatypeMeta for KeyValueDatatype {
+    const NAME: &'static str = "KeyValue";
+    const VERSION: u64 = 1;
+}
+
+impl<T: InterfaceControllerEnum> super::Model<T> for KeyValueDatatype {
+    fn reflection(&self) -> Reflection<T> {
+        Reflection {
+            representations: enumset::enum_set!(
+                        RepresentationKind::State |
+                        RepresentationKind::Delta |
+                    ),
+            implements: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    datatype_controllers!(KeyValueDatatype, ());
+}
+
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum EntryValue {
+    Json(JsonValue),
+    Bytes(Vec<u8>),
+}
+
+pub type StateType = BTreeMap<String, EntryValue>;
+/// Puts of new values, or tombstones (`None`) for removed keys.
+pub type DeltaType = BTreeMap<String, Option<EntryValue>>;
+
+impl crate::datatype::ComposableState for KeyValueDatatype {
+    type StateType = crate::datatype::key_value::StateType;
+    type DeltaType = crate::datatype::key_value::DeltaType;
+
+    fn compose_state(
+        state: &mut Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), crate::Error> {
+        for (key, value) in delta {
+            match value {
+                Some(value) => state.insert(key.clone(), value.clone()),
+                None => state.remove(key),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[stored_datatype_controller(KeyValueDatatype)]
+pub trait Storage: super::Storage {}
+
+
+/// Split a state into states for each partition of a partitioning. Every
+/// partition is included, even if empty.
+pub fn partition_state(
+    state: StateType,
+    partitioning: &dyn KeyPartitioning,
+) -> Result<BTreeMap<PartitionIndex, StateType>, Error> {
+    let mut partitioned: BTreeMap<_, StateType> = partitioning.get_partition_ids().iter()
+        .map(|&p_idx| (p_idx, StateType::new()))
+        .collect();
+
+    for (key, value) in state {
+        let p_idx = partitioning.partition_for_key(key.as_bytes())?;
+        partitioned.get_mut(&p_idx)
+            .ok_or_else(|| ModelError::Other(format!("Key assigned to unknown partition {}", p_idx)))?
+            .insert(key, value);
+    }
+
+    Ok(partitioned)
+}
+
+/// Split a delta into deltas for each partition of a partitioning. Only
+/// partitions with changed keys are included.
+pub fn partition_delta(
+    delta: DeltaType,
+    partitioning: &dyn KeyPartitioning,
+) -> Result<BTreeMap<PartitionIndex, DeltaType>, Error> {
+    let mut partitioned = BTreeMap::<_, DeltaType>::new();
+
+    for (key, value) in delta {
+        partitioned.entry(partitioning.partition_for_key(key.as_bytes())?)
+            .or_default()
+            .insert(key, value);
+    }
+
+    Ok(partitioned)
+}
+
+impl MergePartitions for KeyValueDatatype {
+    fn merge_partitions(
+        states: BTreeMap<PartitionIndex, StateType>,
+    ) -> Result<StateType, crate::Error> {
+        Ok(states.into_iter().flat_map(|(_, state)| state).collect())
+    }
+}
+
+impl<P: KeyPartitioning> SplitPartitions<P> for KeyValueDatatype {
+    fn split_partitions(
+        state: StateType,
+        partitioning: &P,
+    ) -> Result<BTreeMap<PartitionIndex, StateType>, crate::Error> {
+        partition_state(state, partitioning)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use maplit::btreemap;
+
+    use crate::datatype::ComposableState;
+    use crate::datatype::partitioning::hash::HashPartitioningState;
+
+    #[test]
+    fn test_partitioned_composition() {
+        let partitioning = HashPartitioningState::new(3).unwrap();
+        let state: StateType = (0..32)
+            .map(|i| (format!("key{}", i), EntryValue::Bytes(vec![i])))
+            .collect();
+        let delta: DeltaType = btreemap!{
+            "key1".into() => None,
+            "key2".into() => Some(EntryValue::Json(JsonValue(serde_json::json!({"a": 1})))),
+            "new".into() => Some(EntryValue::Bytes(vec![])),
+        };
+
+        let mut expected = state.clone();
+        KeyValueDatatype::compose_state(&mut expected, &delta).unwrap();
+
+        let mut part_states = partition_state(state, &partitioning).unwrap();
+        assert_eq!(part_states.keys().cloned().collect::<BTreeSet<_>>(), partitioning.get_partition_ids());
+        let part_deltas = partition_delta(delta, &partitioning).unwrap();
+        assert!(part_deltas.len() <= 3);
+
+        for (p_idx, part_delta) in &part_deltas {
+            KeyValueDatatype::compose_state(part_states.get_mut(p_idx).unwrap(), part_delta).unwrap();
+        }
+        let composed: StateType = part_states.into_iter().flat_map(|(_, s)| s).collect();
+        assert_eq!(composed, expected);
+    }
+}
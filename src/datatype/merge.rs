@@ -0,0 +1,313 @@
+//! Merging divergent versions of an artifact.
+//!
+//! `merge` creates a version whose parents are several versions of the same
+//! artifact. Partitions changed since the parents' merge base by only one
+//! parent inherit that parent's hunks by precedence, so no data is copied.
+//! Partitions changed by several parents conflict. `merge_with` instead
+//! resolves conflicts with the datatype's `MergeStates` hook, writing the
+//! merged states as new `State` hunks.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::{
+    ArtifactGraph,
+    Composition,
+    CompositionMap,
+    Error,
+    HashType,
+    Hunk,
+    Identity,
+    ModelError,
+    PartCompletion,
+    Partition,
+    PartitionIndex,
+    RepresentationKind,
+    VersionGraph,
+    VersionGraphIndex,
+    VersionRelation,
+};
+use super::{
+    ComposableState,
+    DatatypeEnum,
+    DatatypeMarker,
+    DatatypesRegistry,
+    InterfaceController,
+    Payload,
+    Storage,
+};
+use super::artifact_graph::{
+    ArtifactGraphDtype,
+    Storage as ArtifactGraphStorage,
+};
+use super::interface::{
+    CustomProductionPolicyController,
+    ProducerController,
+};
+use super::partitioning::PartitioningState;
+use crate::repo::Repository;
+
+
+/// Hook to merge the states of a partition changed by several versions
+/// since their merge base.
+pub trait MergeStates: ComposableState {
+    /// `base` is `None` if the partition had no state in the merge base.
+    /// Returns `None` if the states can not be merged.
+    fn merge_states(
+        base: Option<&Self::StateType>,
+        states: &[Self::StateType],
+    ) -> Result<Option<Self::StateType>, Error>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MergeOutcome {
+    /// The committed merge version.
+    Merged(VersionGraphIndex),
+    /// Partitions changed by several parents that could not be merged. No
+    /// version is created.
+    Conflicted(BTreeSet<PartitionIndex>),
+}
+
+/// How a merge version populates a partition.
+enum Resolution<P> {
+    /// Inherit the hunks of this parent version.
+    Precedence(VersionGraphIndex),
+    /// Write a merged state payload with this hash.
+    State(HashType, P),
+}
+
+/// Merge versions `parents` of an artifact, which must share a partitioning
+/// version, into a new committed `Delta` version.
+pub fn merge<'ag, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    art_graph: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    parents: &[VersionGraphIndex],
+) -> Result<MergeOutcome, Error>
+        where
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ProducerController> +
+                InterfaceController<CustomProductionPolicyController>
+{
+    merge_versions::<T, (), _, _>(
+        dtypes_registry,
+        repo,
+        art_graph,
+        ver_graph,
+        parents,
+        |_, _| Ok(None),
+        |_, _| Ok(()))
+}
+
+/// Merge versions `parents` of an artifact like `merge`, resolving
+/// conflicting partitions with `D::merge_states`.
+pub fn merge_with<'ag, D, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    art_graph: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    parents: &[VersionGraphIndex],
+) -> Result<MergeOutcome, Error>
+        where
+            D: DatatypeMarker + MergeStates,
+            <D as DatatypeMarker>::Store: Storage<
+                StateType = <D as ComposableState>::StateType,
+                DeltaType = <D as ComposableState>::DeltaType>,
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ProducerController> +
+                InterfaceController<CustomProductionPolicyController>
+{
+    let dtype_reader = D::store(repo);
+    let mut dtype_writer = D::store(repo);
+
+    merge_versions(
+        dtypes_registry,
+        repo,
+        art_graph,
+        ver_graph,
+        parents,
+        |base, compositions| {
+            // A parent lacking the partition can not be merged with others
+            // that changed it.
+            if compositions.iter().any(|composition| composition.is_empty()) {
+                return Ok(None);
+            }
+            let base = if base.is_empty() {
+                None
+            } else {
+                Some(dtype_reader.get_composite_state(repo, base)?)
+            };
+            let states = compositions.iter()
+                .map(|composition| dtype_reader.get_composite_state(repo, composition))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(D::merge_states(base.as_ref(), &states)?.map(|state| {
+                let payload = Payload::State(state);
+                (D::hash_payload(&payload), payload)
+            }))
+        },
+        |hunk, payload| dtype_writer.write_hunk(repo, hunk, payload))
+}
+
+fn merge_versions<'ag, T, P, R, W>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    art_graph: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    parents: &[VersionGraphIndex],
+    mut resolve: R,
+    mut write: W,
+) -> Result<MergeOutcome, Error>
+        where
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ProducerController> +
+                InterfaceController<CustomProductionPolicyController>,
+            R: FnMut(&Composition, &[&Composition]) -> Result<Option<(HashType, P)>, Error>,
+            W: FnMut(&Hunk, &P) -> Result<(), Error>,
+{
+    let mut ag_control = ArtifactGraphDtype::store(repo);
+
+    if parents.len() < 2 {
+        return Err(ModelError::Other("A merge must have at least two parents".into()).into());
+    }
+    let artifact_id = ver_graph[parents[0]].artifact.id;
+    if parents.iter().any(|&p_idx| ver_graph[p_idx].artifact.id != artifact_id) {
+        return Err(ModelError::Other("Merge parents are not versions of the same artifact".into()).into());
+    }
+    let (part_idx, _) = ver_graph.get_partitioning(parents[0])
+        .ok_or_else(|| ModelError::MalformedGraph("Version has no partitioning".into()))?;
+    if parents.iter().any(|&p_idx| ver_graph.get_partitioning(p_idx).map(|(idx, _)| idx) != Some(part_idx)) {
+        return Err(ModelError::Other("Merge parents have different partitionings".into()).into());
+    }
+
+    let partitions = ag_control.get_partitioning_partitions(dtypes_registry, repo, ver_graph, part_idx)?;
+    let base_idx = ver_graph.merge_base(parents);
+
+    let resolutions = {
+        let ver_graph: &VersionGraph<'ag> = ver_graph;
+        let partition_ids: BTreeSet<_> = partitions.keys().cloned().collect();
+
+        let base_map = match base_idx {
+            Some(base_idx) => ag_control.get_partitioned_composition_map(
+                dtypes_registry, repo, ver_graph, base_idx, partition_ids.clone())?,
+            None => CompositionMap::new(),
+        };
+        let parent_maps = parents.iter()
+            .map(|&p_idx| ag_control.get_partitioned_composition_map(
+                dtypes_registry, repo, ver_graph, p_idx, partition_ids.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let empty = Composition::new();
+        let hunk_ids = |composition: &Composition| composition.iter()
+            .map(|hunk| hunk.id)
+            .collect::<Vec<Identity>>();
+
+        let mut resolutions = BTreeMap::new();
+        let mut conflicts = BTreeSet::new();
+
+        for p_id in &partition_ids {
+            let base_composition = base_map.get(p_id).unwrap_or(&empty);
+            let base_ids = hunk_ids(base_composition);
+
+            // Parents whose compositions differ from the merge base's, with
+            // only the first of those with identical compositions.
+            let mut changed: Vec<(usize, Vec<Identity>)> = vec![];
+            for (i, parent_map) in parent_maps.iter().enumerate() {
+                let ids = hunk_ids(parent_map.get(p_id).unwrap_or(&empty));
+                if ids != base_ids && !changed.iter().any(|(_, other)| *other == ids) {
+                    changed.push((i, ids));
+                }
+            }
+
+            match changed.len() {
+                0 => {},
+                1 => {
+                    resolutions.insert(*p_id, Resolution::Precedence(parents[changed[0].0]));
+                },
+                _ => {
+                    let compositions = changed.iter()
+                        .map(|(i, _)| parent_maps[*i].get(p_id).unwrap_or(&empty))
+                        .collect::<Vec<_>>();
+                    match resolve(base_composition, &compositions)? {
+                        Some((hash, payload)) => {
+                            resolutions.insert(*p_id, Resolution::State(hash, payload));
+                        },
+                        None => {
+                            conflicts.insert(*p_id);
+                        },
+                    }
+                },
+            }
+        }
+
+        if !conflicts.is_empty() {
+            return Ok(MergeOutcome::Conflicted(conflicts));
+        }
+
+        resolutions
+    };
+
+    let merge_idx = ver_graph.new_child_same_dependencies(parents[0], RepresentationKind::Delta)?;
+    for &p_idx in &parents[1..] {
+        ver_graph.versions.add_edge(p_idx, merge_idx, VersionRelation::Parent)?;
+    }
+
+    ag_control.create_staging_version(repo, ver_graph, merge_idx)?;
+
+    let ver_hash = {
+        let mut ver_hash = DefaultHasher::new();
+
+        // Partitions are in ascending order, so the version hash is consistent.
+        for (p_id, resolution) in &resolutions {
+            let partition = Partition {
+                partitioning: &ver_graph[part_idx],
+                index: *p_id,
+                kind: partitions[p_id],
+            };
+            let hunk = match resolution {
+                Resolution::Precedence(p_idx) => Hunk {
+                    id: ver_graph[*p_idx].id.hash.into(),
+                    version: &ver_graph[merge_idx],
+                    partition,
+                    representation: RepresentationKind::Delta,
+                    completion: PartCompletion::Complete,
+                    precedence: Some(ver_graph[*p_idx].id.uuid),
+                },
+                Resolution::State(hash, _) => Hunk {
+                    id: (*hash).into(),
+                    version: &ver_graph[merge_idx],
+                    partition,
+                    representation: RepresentationKind::State,
+                    completion: PartCompletion::Complete,
+                    precedence: None,
+                },
+            };
+            hunk.id.hash.hash(&mut ver_hash);
+
+            ag_control.create_hunk(repo, &hunk)?;
+            if let Resolution::State(_, payload) = resolution {
+                write(&hunk, payload)?;
+            }
+        }
+
+        ver_hash.finish()
+    };
+    ver_graph[merge_idx].id.hash = ver_hash;
+
+    ag_control.commit_version(
+        dtypes_registry,
+        repo,
+        art_graph,
+        ver_graph,
+        merge_idx)?;
+
+    Ok(MergeOutcome::Merged(merge_idx))
+}
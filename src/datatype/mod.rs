@@ -14,7 +14,7 @@ use heraclitus_macros::{
 
 pub use heraclitus_core::datatype::*;
 
-use crate::{Composition, Error, Hunk};
+use crate::{Composition, Error, Hunk, ModelError};
 use crate::repo::Repository;
 use self::interface::{
     ProducerController,
@@ -24,13 +24,27 @@ use self::interface::{
 
 #[macro_use]
 pub mod macros;
+#[cfg(feature = "datatype-arrow")]
+pub mod arrow_table;
 pub mod artifact_graph;
 #[macro_use]
 pub mod blob;
+pub mod diff;
 pub mod interface;
+pub mod json;
+pub mod key_value;
+pub mod merge;
+pub mod ndarray;
+pub mod octree;
 pub mod partitioning;
 pub mod producer;
+pub mod rebase;
 pub mod reference;
+pub mod repartition;
+pub mod snapshot;
+pub mod squash;
+pub mod table;
+pub mod text;
 pub mod tracking_branch_producer;
 
 
@@ -54,10 +68,24 @@ pub trait ComposableState {
         s.finish()
     }
 
+    /// Apply a delta to a state. Stored deltas may not apply to the state
+    /// they are composed with, in which case an error is returned and the
+    /// state may be partially composed.
     fn compose_state(
         state: &mut Self::StateType,
         delta: &Self::DeltaType,
-    );
+    ) -> Result<(), Error>;
+
+    /// Check that a delta applies to a state without composing it. Deltas
+    /// written onto a base other than the one they were made for, such as
+    /// when rebasing, are checked with this first. Datatypes whose deltas
+    /// may not apply to every state should override this.
+    fn validate_delta(
+        _state: &Self::StateType,
+        _delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 pub trait StateOnly {
@@ -73,7 +101,7 @@ impl<T> ComposableState for T where T: StateOnly {
     fn compose_state(
         _state: &mut Self::StateType,
         _delta: &Self::DeltaType,
-    ) {
+    ) -> Result<(), Error> {
         unimplemented!()
     }
 }
@@ -130,16 +158,19 @@ pub trait Storage: StoreOrBackend<Datatype: ComposableState> {
     ) -> Result<Self::StateType, Error> {
             let mut hunk_iter = composition.iter().rev();
 
-            let mut state = match self.read_hunk(repo, hunk_iter.next().expect("TODO"))? {
+            let root = hunk_iter.next()
+                .ok_or_else(|| ModelError::MalformedGraph("Composition is empty".into()))?;
+            let mut state = match self.read_hunk(repo, root)? {
                 Payload::State(state) => state,
-                _ => panic!("Composition rooted in non-state hunk"),
+                _ => return Err(ModelError::MalformedGraph("Composition rooted in non-state hunk".into()).into()),
             };
 
             for hunk in hunk_iter {
                 match self.read_hunk(repo, hunk)? {
-                    Payload::State(_) => panic!("TODO: shouldn't have non-root state"),
+                    Payload::State(_) =>
+                        return Err(ModelError::MalformedGraph("Composition has a non-root state hunk".into()).into()),
                     Payload::Delta(ref delta) => {
-                        Self::Datatype::compose_state(&mut state, delta);
+                        Self::Datatype::compose_state(&mut state, delta)?;
                     }
                 }
             }
@@ -187,7 +218,8 @@ interface_controller_enum!(DefaultInterfaceController, (
         (ArtifactMeta, artifact_graph::ArtifactMeta, &*artifact_graph::INTERFACE_ARTIFACT_META_DESC),
         (Partitioning, partitioning::PartitioningState, &*interface::INTERFACE_PARTITIONING_DESC),
         (Producer, ProducerController, &*interface::INTERFACE_PRODUCER_DESC),
-        (CustomProductionPolicy, CustomProductionPolicyController, &*interface::INTERFACE_CUSTOM_PRODUCTION_POLICY_DESC)
+        (CustomProductionPolicy, CustomProductionPolicyController, &*interface::INTERFACE_CUSTOM_PRODUCTION_POLICY_DESC),
+        (ContentDiff, diff::ContentDiffController, &*interface::INTERFACE_CONTENT_DIFF_DESC)
     ));
 
 datatype_enum!(DefaultDatatypes, DefaultInterfaceController, (
@@ -195,7 +227,15 @@ datatype_enum!(DefaultDatatypes, DefaultInterfaceController, (
         (Ref, reference::Ref),
         (UnaryPartitioning, partitioning::UnaryPartitioning),
         (ArbitraryPartitioning, partitioning::arbitrary::ArbitraryPartitioning),
+        (GridPartitioning, partitioning::grid::GridPartitioning),
+        (HashPartitioning, partitioning::hash::HashPartitioning),
         (Blob, blob::BlobDatatype),
+        (Text, text::TextDatatype),
+        (Json, json::JsonDatatype),
+        (Table, table::TableDatatype),
+        (KeyValue, key_value::KeyValueDatatype),
+        (NdArray, ndarray::NdArrayDatatype),
+        (PointOctree, octree::PointOctree),
         (NoopProducer, producer::NoopProducer),
         (TrackingBranchProducer, tracking_branch_producer::TrackingBranchProducer),
     ));
@@ -214,3 +254,61 @@ pub mod testing {
         heraclitus_core::datatype::testing::init_dtypes_registry::<DefaultDatatypes>()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heraclitus_macros::Datatype;
+
+    use crate::RepresentationKind;
+
+
+    #[derive(Clone, Debug, Hash, PartialEq)]
+    #[derive(Deserialize, Serialize)]
+    pub struct Tally(u64);
+
+    fn add_to_tally(state: &mut Tally, delta: &u64) {
+        state.0 += delta;
+    }
+
+    #[derive(Default, Datatype)]
+    #[datatype(
+        name = "Tally",
+        version = 2,
+        representations(State, Delta),
+        dependencies(source(datatype = "Blob", cardinality = 1)),
+        state = "Tally",
+        delta = "u64",
+        compose = "add_to_tally",
+    )]
+    pub struct TallyDatatype;
+
+    #[test]
+    fn test_derive_datatype() {
+        assert_eq!(<TallyDatatype as DatatypeMeta>::NAME, "Tally");
+        assert_eq!(<TallyDatatype as DatatypeMeta>::VERSION, 2);
+
+        let reflection: Reflection<DefaultInterfaceController> = TallyDatatype.reflection();
+        assert_eq!(
+            reflection.representations,
+            enumset::enum_set!(RepresentationKind::State | RepresentationKind::Delta |));
+        assert!(reflection.implements.is_empty());
+        assert_eq!(reflection.dependencies.len(), 1);
+
+        let mut state = Tally(3);
+        TallyDatatype::compose_state(&mut state, &4).unwrap();
+        assert_eq!(state, Tally(7));
+    }
+
+    #[test]
+    fn test_with_datatypes() {
+        let dtypes_registry = DatatypesRegistry::<DefaultDatatypes>::with_datatypes(&["Blob", "Ref"]).unwrap();
+        for name in CORE_DATATYPES.iter().chain(&["Blob"]) {
+            assert!(dtypes_registry.get_datatype(name).is_some(), "{} was not registered", name);
+        }
+        assert!(dtypes_registry.get_datatype("Json").is_none());
+
+        assert!(DatatypesRegistry::<DefaultDatatypes>::with_datatypes(&["Missing"]).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use heraclitus_macros::{
+    DatatypeMarker,
+    stored_datatype_controller,
+};
+
+use crate::{
+    Error,
+    ModelError,
+    PartitionIndex,
+    RepresentationKind,
+};
+use super::{
+    DatatypeMeta,
+    InterfaceControllerEnum,
+    Reflection,
+};
+use super::partitioning::{
+    Partitioning,
+    UnaryPartitioningState,
+    UNARY_PARTITION_INDEX,
+};
+use super::partitioning::grid::GridPartitioningState;
+use super::repartition::{
+    MergePartitions,
+    SplitPartitions,
+};
+
+
+/// Chunked N-dimensional arrays, similar to N5 or Zarr.
+///
+/// Each block of the array is stored as hunks in the matching partition of
+/// the artifact's grid partitioning (see `ArrayMetadata::grid`). State hunks
+/// hold full blocks, while delta hunks hold sparse element updates.
+#[derive(Default, DatatypeMarker)]
+pub struct NdArrayDatatype;
+
+impl DatatypeMeta for NdArrayDatatype {
+    const NAME: &'static str = "NdArray";
+    const VERSION: u64 = 1;
+}
+
+impl<T: InterfaceControllerEnum> super::Model<T> for NdArrayDatatype {
+    fn reflection(&self) -> Reflection<T> {
+        Reflection {
+            representations: enumset::enum_set!(
+                        RepresentationKind::State |
+                        RepresentationKind::Delta |
+                    ),
+            implements: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    datatype_controllers!(NdArrayDatatype, ());
+}
+
+/// Element type of an array. Elements are stored little-endian.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum ElementType {
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+}
+
+impl ElementType {
+    /// Size of an element in bytes.
+    pub fn size_of(self) -> usize {
+        match self {
+            ElementType::Uint8 | ElementType::Int8 => 1,
+            ElementType::Uint16 | ElementType::Int16 => 2,
+            ElementType::Uint32 | ElementType::Int32 | ElementType::Float32 => 4,
+            ElementType::Uint64 | ElementType::Int64 | ElementType::Float64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct ArrayMetadata {
+    pub element_type: ElementType,
+    pub shape: Vec<u64>,
+    pub block_size: Vec<u64>,
+}
+
+impl ArrayMetadata {
+    /// The grid partitioning of this array's blocks. Array artifacts should be
+    /// partitioned by a grid partitioning artifact with this state.
+    pub fn grid(&self) -> GridPartitioningState {
+        GridPartitioningState {
+            extent: self.shape.clone(),
+            block_size: self.block_size.clone(),
+        }
+    }
+}
+
+/// A full block of elements in row-major order.
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct Block {
+    pub metadata: ArrayMetadata,
+    pub block_coords: Vec<u64>,
+    pub data: Vec<u8>,
+}
+
+/// Sparse element updates to a block. The `i`th element of `data` replaces
+/// the element at row-major offset `offsets[i]` within the block.
+#[derive(Clone, Debug, Default, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct BlockDelta {
+    pub offsets: Vec<u64>,
+    pub data: Vec<u8>,
+}
+
+impl crate::datatype::ComposableState for NdArrayDatatype {
+    type StateType = Block;
+    type DeltaType = BlockDelta;
+
+    fn compose_state(
+        state: &mut Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        Self::validate_delta(state, delta)?;
+
+        let size = state.metadata.element_type.size_of();
+        for (&offset, element) in delta.offsets.iter().zip(delta.data.chunks_exact(size)) {
+            let start = offset as usize * size;
+            state.data[start..start + size].copy_from_slice(element);
+        }
+
+        Ok(())
+    }
+
+    fn validate_delta(
+        state: &Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        let size = state.metadata.element_type.size_of();
+        if delta.offsets.len() * size != delta.data.len() {
+            return Err(ModelError::Other(format!(
+                "Block delta has {} bytes of data for {} elements",
+                delta.data.len(), delta.offsets.len())).into());
+        }
+        let elements = (state.data.len() / size) as u64;
+        if let Some(&offset) = delta.offsets.iter().find(|&&offset| offset >= elements) {
+            return Err(ModelError::Other(format!(
+                "Block delta offset {} is outside block of {} elements",
+                offset, elements)).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[stored_datatype_controller(NdArrayDatatype)]
+pub trait Storage: super::Storage {}
+
+
+impl Block {
+    /// A zeroed block.
+    pub fn new(metadata: ArrayMetadata, block_coords: Vec<u64>) -> Self {
+        let len = metadata.grid().block_shape(&block_coords).iter().product::<u64>() as usize
+            * metadata.element_type.size_of();
+        Block {
+            metadata,
+            block_coords,
+            data: vec![0; len],
+        }
+    }
+
+    /// Array coordinates of this block's first element.
+    fn origin(&self) -> Vec<u64> {
+        self.block_coords.iter().zip(self.metadata.block_size.iter())
+            .map(|(&c, &b)| c * b)
+            .collect()
+    }
+
+    /// Copy the elements of another block of the same array where it
+    /// overlaps this block.
+    pub fn copy_from(&mut self, other: &Block) {
+        let size = self.metadata.element_type.size_of();
+        let origin = self.origin();
+        let shape = self.metadata.grid().block_shape(&self.block_coords);
+        let other_origin = other.origin();
+        let other_shape = other.metadata.grid().block_shape(&other.block_coords);
+
+        // Half-open range of overlapping array coordinates in each dimension.
+        let ranges = origin.iter().zip(shape.iter())
+            .zip(other_origin.iter().zip(other_shape.iter()))
+            .map(|((&o, &s), (&oo, &os))| (o.max(oo), (o + s).min(oo + os)))
+            .collect::<Vec<_>>();
+        if ranges.iter().any(|&(lo, hi)| lo >= hi) {
+            return;
+        }
+
+        let offset = |coords: &[u64], origin: &[u64], shape: &[u64]| {
+            coords.iter().zip(origin.iter()).zip(shape.iter())
+                .fold(0, |off, ((&c, &o), &s)| off * s + (c - o)) as usize * size
+        };
+
+        let mut coords = ranges.iter().map(|&(lo, _)| lo).collect::<Vec<_>>();
+        'elements: loop {
+            let dst = offset(&coords, &origin, &shape);
+            let src = offset(&coords, &other_origin, &other_shape);
+            self.data[dst..dst + size].copy_from_slice(&other.data[src..src + size]);
+
+            // Increment coordinates as an odometer, last dimension fastest.
+            for (coord, &(lo, hi)) in coords.iter_mut().zip(ranges.iter()).rev() {
+                *coord += 1;
+                if *coord < hi {
+                    continue 'elements;
+                }
+                *coord = lo;
+            }
+
+            break;
+        }
+    }
+}
+
+/// Merges into a single block spanning the whole array.
+impl MergePartitions for NdArrayDatatype {
+    fn merge_partitions(
+        states: BTreeMap<PartitionIndex, Block>,
+    ) -> Result<Block, Error> {
+        let metadata = states.values().next()
+            .map(|block| block.metadata.clone())
+            .ok_or_else(|| ModelError::Other("Cannot merge an array with no blocks".into()))?;
+        if states.values().any(|block| block.metadata != metadata) {
+            return Err(ModelError::Other("Array blocks have differing metadata".into()).into());
+        }
+
+        let dims = metadata.shape.len();
+        let mut merged = Block::new(
+            ArrayMetadata {
+                block_size: metadata.shape.clone(),
+                ..metadata
+            },
+            vec![0; dims]);
+        for block in states.values() {
+            merged.copy_from(block);
+        }
+
+        Ok(merged)
+    }
+}
+
+impl SplitPartitions<UnaryPartitioningState> for NdArrayDatatype {
+    fn split_partitions(
+        state: Block,
+        _partitioning: &UnaryPartitioningState,
+    ) -> Result<BTreeMap<PartitionIndex, Block>, Error> {
+        let mut split = BTreeMap::new();
+        split.insert(UNARY_PARTITION_INDEX, state);
+        Ok(split)
+    }
+}
+
+impl SplitPartitions<GridPartitioningState> for NdArrayDatatype {
+    fn split_partitions(
+        state: Block,
+        partitioning: &GridPartitioningState,
+    ) -> Result<BTreeMap<PartitionIndex, Block>, Error> {
+        partitioning.validate()?;
+        if partitioning.extent != state.metadata.shape {
+            return Err(ModelError::Other("Grid extent does not match array shape".into()).into());
+        }
+
+        let metadata = ArrayMetadata {
+            block_size: partitioning.block_size.clone(),
+            ..state.metadata.clone()
+        };
+
+        partitioning.get_partition_ids().into_iter()
+            .map(|p_idx| {
+                let block_coords = partitioning.partition_block_coords(p_idx)?;
+                let mut block = Block::new(metadata.clone(), block_coords);
+                block.copy_from(&state);
+                Ok((p_idx, block))
+            })
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::datatype::ComposableState;
+
+    #[test]
+    fn test_compose_sparse_updates() {
+        let metadata = ArrayMetadata {
+            element_type: ElementType::Uint16,
+            shape: vec![2, 2],
+            block_size: vec![2, 2],
+        };
+        let mut block = Block {
+            metadata,
+            block_coords: vec![0, 0],
+            data: vec![0; 8],
+        };
+        let delta = BlockDelta {
+            offsets: vec![1, 3],
+            data: vec![1, 2, 3, 4],
+        };
+
+        NdArrayDatatype::compose_state(&mut block, &delta).unwrap();
+
+        assert_eq!(block.data, vec![0, 0, 1, 2, 0, 0, 3, 4]);
+    }
+
+    #[test]
+    fn test_validate_delta() {
+        let block = Block::new(
+            ArrayMetadata {
+                element_type: ElementType::Uint16,
+                shape: vec![2, 2],
+                block_size: vec![2, 2],
+            },
+            vec![0, 0]);
+
+        assert!(NdArrayDatatype::validate_delta(&block, &BlockDelta {
+            offsets: vec![3],
+            data: vec![1, 2],
+        }).is_ok());
+        assert!(NdArrayDatatype::validate_delta(&block, &BlockDelta {
+            offsets: vec![1, 3],
+            data: vec![1, 2, 3],
+        }).is_err(), "Delta with a partial element was accepted");
+        assert!(NdArrayDatatype::validate_delta(&block, &BlockDelta {
+            offsets: vec![4],
+            data: vec![1, 2],
+        }).is_err(), "Delta with an offset outside the block was accepted");
+        assert!(NdArrayDatatype::compose_state(&mut block.clone(), &BlockDelta {
+            offsets: vec![u64::max_value()],
+            data: vec![1, 2],
+        }).is_err(), "Delta with an offset outside the block was composed");
+    }
+
+    #[test]
+    fn test_repartition_grid() {
+        let metadata = ArrayMetadata {
+            element_type: ElementType::Uint8,
+            shape: vec![3, 4],
+            block_size: vec![2, 2],
+        };
+        let blocks = metadata.grid().get_partition_ids().into_iter()
+            .map(|p_idx| {
+                let mut block = Block::new(metadata.clone(), metadata.grid().partition_block_coords(p_idx).unwrap());
+                let origin = block.origin();
+                let shape = metadata.grid().block_shape(&block.block_coords);
+                for i in 0..shape[0] {
+                    for j in 0..shape[1] {
+                        block.data[(i * shape[1] + j) as usize] = ((origin[0] + i) * 4 + origin[1] + j) as u8;
+                    }
+                }
+                (p_idx, block)
+            })
+            .collect();
+
+        let merged = NdArrayDatatype::merge_partitions(blocks).unwrap();
+        assert_eq!(merged.data, (0..12).collect::<Vec<u8>>());
+
+        let fine = GridPartitioningState {extent: vec![3, 4], block_size: vec![3, 1]};
+        let split = NdArrayDatatype::split_partitions(merged, &fine).unwrap();
+        assert_eq!(split.len(), 4);
+        assert_eq!(split[&1].data, vec![1, 5, 9]);
+
+        let invalid = GridPartitioningState {extent: vec![3, 4], block_size: vec![0, 1]};
+        assert!(NdArrayDatatype::split_partitions(split[&0].clone(), &invalid).is_err());
+    }
+}
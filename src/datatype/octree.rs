@@ -0,0 +1,498 @@
+//! Self-partitioning point octrees.
+//!
+//! An octree artifact is its own partitioning: its partitions are the leaf
+//! nodes of the tree, identified by locational codes, plus the unary
+//! partition (`UNARY_PARTITION_INDEX`), which holds the tree structure. Since
+//! locational codes always have a leading marker bit, no node has the unary
+//! partition's index.
+//!
+//! Because the structure is in the unary partition, resolving the partitions
+//! of an octree version through its (self) partitioning only requires
+//! composing that one partition, so no special casing is needed beyond
+//! marking octree artifacts as `self_partitioning`.
+//!
+//! Nodes are split and merged across versions by structure deltas in the
+//! unary partition, accompanied by state hunks for the leaves they create.
+//! See `rebalance`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use maplit::btreeset;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use heraclitus_macros::{
+    DatatypeMarker,
+    stored_datatype_controller,
+};
+
+use crate::{
+    Error,
+    ModelError,
+    PartitionIndex,
+    PartitionKind,
+    RepresentationKind,
+};
+use super::{
+    DatatypeMeta,
+    InterfaceController,
+    Model,
+    Reflection,
+};
+use super::partitioning::{
+    Partitioning,
+    PartitioningState,
+    UNARY_PARTITION_INDEX,
+};
+
+
+#[derive(Default, DatatypeMarker)]
+pub struct PointOctree;
+
+impl DatatypeMeta for PointOctree {
+    const NAME: &'static str = "PointOctree";
+    const VERSION: u64 = 1;
+}
+
+impl<T: InterfaceController<PartitioningState>> Model<T> for PointOctree {
+    fn reflection(&self) -> Reflection<T> {
+        Reflection {
+            representations: enumset::enum_set!(
+                    RepresentationKind::State |
+                    RepresentationKind::Delta |
+                ),
+            implements: vec![
+                <T as InterfaceController<PartitioningState>>::VARIANT,
+            ],
+            dependencies: vec![],
+        }
+    }
+
+    datatype_controllers!(PointOctree, (PartitioningState));
+}
+
+
+/// Integer point coordinates, each less than `2^depth` of the octree.
+pub type Point = [u32; 3];
+pub type PointId = u64;
+
+/// Locational code of the root node.
+pub const ROOT_CODE: PartitionIndex = 1;
+/// Maximum depth for which locational codes fit in a `PartitionIndex`.
+pub const MAX_DEPTH: u32 = 21;
+
+/// Level of a node from its locational code. The root is level 0.
+pub fn level(code: PartitionIndex) -> u32 {
+    (63 - code.leading_zeros()) / 3
+}
+
+/// Locational codes of a node's eight children.
+pub fn children(code: PartitionIndex) -> Vec<PartitionIndex> {
+    (0..8).map(|octant| (code << 3) | octant).collect()
+}
+
+/// Locational code of the node at `level` containing a point in an octree of
+/// some `depth`.
+pub fn node_code(point: &Point, level: u32, depth: u32) -> PartitionIndex {
+    let shift = depth - level;
+    let mut code = 1;
+    for bit in (0..level).rev() {
+        let octant = point.iter().enumerate()
+            .fold(0, |octant, (dim, &coord)| {
+                octant | (u64::from((coord >> shift >> bit) & 1) << dim)
+            });
+        code = (code << 3) | octant;
+    }
+    code
+}
+
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct OctreeStructure {
+    pub depth: u32,
+    pub leaves: BTreeSet<PartitionIndex>,
+}
+
+impl OctreeStructure {
+    pub fn new(depth: u32) -> Result<Self, Error> {
+        if depth > MAX_DEPTH {
+            return Err(ModelError::Other(format!(
+                "Octree depth {} exceeds maximum of {}", depth, MAX_DEPTH)).into());
+        }
+
+        Ok(OctreeStructure {
+            depth,
+            leaves: btreeset![ROOT_CODE],
+        })
+    }
+
+    /// The leaf containing a point, if the point is within bounds.
+    pub fn leaf_for_point(&self, point: &Point) -> Option<PartitionIndex> {
+        if point.iter().any(|&c| u64::from(c) >= 1 << self.depth) {
+            return None;
+        }
+
+        (0..=self.depth)
+            .map(|level| node_code(point, level, self.depth))
+            .find(|code| self.leaves.contains(code))
+    }
+
+    pub fn split(&mut self, code: PartitionIndex) -> Result<(), Error> {
+        if code == 0 || level(code) >= self.depth {
+            return Err(ModelError::Other(format!(
+                "Cannot split node {:#b} at maximum depth", code)).into());
+        }
+        if !self.leaves.remove(&code) {
+            return Err(ModelError::Other(format!(
+                "Cannot split node {:#b}, which is not a leaf", code)).into());
+        }
+        self.leaves.extend(children(code));
+
+        Ok(())
+    }
+
+    pub fn merge(&mut self, parent: PartitionIndex) -> Result<(), Error> {
+        let siblings = children(parent);
+        if parent == 0 || level(parent) >= self.depth ||
+                !siblings.iter().all(|child| self.leaves.contains(child)) {
+            return Err(ModelError::Other(format!(
+                "Cannot merge node {:#b}, whose children are not all leaves", parent)).into());
+        }
+        for child in siblings {
+            self.leaves.remove(&child);
+        }
+        self.leaves.insert(parent);
+
+        Ok(())
+    }
+
+    /// Apply the splits, then merges, of a structure delta.
+    fn apply(
+        &mut self,
+        splits: &BTreeSet<PartitionIndex>,
+        merges: &BTreeSet<PartitionIndex>,
+    ) -> Result<(), Error> {
+        for &code in splits {
+            self.split(code)?;
+        }
+        for &code in merges.iter().rev() {
+            self.merge(code)?;
+        }
+
+        Ok(())
+    }
+
+    /// Group point changes by the leaves containing them, as leaf deltas.
+    pub fn leaf_deltas(
+        &self,
+        inserted: BTreeMap<PointId, Point>,
+        removed: BTreeMap<PointId, Point>,
+    ) -> Result<BTreeMap<PartitionIndex, OctreeDelta>, Error> {
+        let mut changes = BTreeMap::<_, (BTreeMap<_, _>, BTreeSet<_>)>::new();
+        let leaf = |point: &Point| self.leaf_for_point(point)
+            .ok_or_else(|| Error::from(ModelError::Other(format!(
+                "Point {:?} is out of octree bounds", point))));
+
+        for (id, point) in removed {
+            changes.entry(leaf(&point)?).or_default().1.insert(id);
+        }
+        for (id, point) in inserted {
+            changes.entry(leaf(&point)?).or_default().0.insert(id, point);
+        }
+
+        Ok(changes.into_iter()
+            .map(|(code, (inserted, removed))| (code, OctreeDelta::Leaf {inserted, removed}))
+            .collect())
+    }
+}
+
+#[derive(Clone, Debug, Default, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct OctreeLeaf {
+    pub points: BTreeMap<PointId, Point>,
+}
+
+/// State of a partition of an octree: the structure for the unary partition,
+/// or the points of a leaf for all others.
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum OctreeState {
+    Structure(OctreeStructure),
+    Leaf(OctreeLeaf),
+}
+
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum OctreeDelta {
+    /// Splits are applied in ascending order, so may include children of
+    /// other split nodes, then merges in descending order.
+    Structure {
+        splits: BTreeSet<PartitionIndex>,
+        merges: BTreeSet<PartitionIndex>,
+    },
+    Leaf {
+        inserted: BTreeMap<PointId, Point>,
+        removed: BTreeSet<PointId>,
+    },
+}
+
+impl Partitioning for OctreeState {
+    /// Only meaningful for the structure state, for which this is the leaves
+    /// and the unary partition.
+    fn get_partition_ids(&self) -> BTreeSet<PartitionIndex> {
+        match self {
+            OctreeState::Structure(structure) => {
+                let mut ids = structure.leaves.clone();
+                ids.insert(UNARY_PARTITION_INDEX);
+                ids
+            },
+            OctreeState::Leaf(_) => BTreeSet::new(),
+        }
+    }
+
+    /// The structure partition is arbitrary, while leaf nodes are leaves.
+    fn get_partition_kinds(&self) -> BTreeMap<PartitionIndex, PartitionKind> {
+        self.get_partition_ids().into_iter()
+            .map(|p_idx| (p_idx, if p_idx == UNARY_PARTITION_INDEX {
+                PartitionKind::Arbitrary
+            } else {
+                PartitionKind::Leaf
+            }))
+            .collect()
+    }
+}
+
+impl crate::datatype::ComposableState for PointOctree {
+    type StateType = OctreeState;
+    type DeltaType = OctreeDelta;
+
+    fn compose_state(
+        state: &mut Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        Self::validate_delta(state, delta)?;
+
+        match (state, delta) {
+            (OctreeState::Structure(structure), OctreeDelta::Structure {splits, merges}) => {
+                structure.apply(splits, merges)?;
+            },
+            (OctreeState::Leaf(leaf), OctreeDelta::Leaf {inserted, removed}) => {
+                removed.iter().for_each(|id| { leaf.points.remove(id); });
+                leaf.points.extend(inserted.iter().map(|(&id, &point)| (id, point)));
+            },
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    /// Structure deltas must split only leaves and merge only nodes whose
+    /// children are all leaves. Leaf states do not know the depth of their
+    /// octree, so inserted points are only checked against `MAX_DEPTH`.
+    fn validate_delta(
+        state: &Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        match (state, delta) {
+            (OctreeState::Structure(structure), OctreeDelta::Structure {splits, merges}) => {
+                structure.clone().apply(splits, merges)
+            },
+            (OctreeState::Leaf(_), OctreeDelta::Leaf {inserted, ..}) => {
+                match inserted.values().find(|point| point.iter().any(|&c| c >= 1 << MAX_DEPTH)) {
+                    Some(point) => Err(ModelError::Other(format!(
+                        "Point {:?} is out of octree bounds", point)).into()),
+                    None => Ok(()),
+                }
+            },
+            _ => Err(ModelError::Other("Octree delta does not match state kind".into()).into()),
+        }
+    }
+}
+
+#[stored_datatype_controller(PointOctree)]
+pub trait Storage: super::Storage {}
+
+
+/// Structural changes to an octree for a new version.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rebalance {
+    /// Structure after rebalancing.
+    pub structure: OctreeStructure,
+    /// Delta for the unary partition of the new version.
+    pub delta: OctreeDelta,
+    /// States for the leaf partitions created in the new version.
+    pub leaves: BTreeMap<PartitionIndex, OctreeLeaf>,
+}
+
+fn split_leaf(
+    depth: u32,
+    code: PartitionIndex,
+    leaf: OctreeLeaf,
+) -> Result<BTreeMap<PartitionIndex, OctreeLeaf>, Error> {
+    let child_level = level(code) + 1;
+    let mut split: BTreeMap<_, _> = children(code).into_iter()
+        .map(|child| (child, OctreeLeaf::default()))
+        .collect();
+
+    for (id, point) in leaf.points {
+        split.get_mut(&node_code(&point, child_level, depth))
+            .ok_or_else(|| ModelError::Other(format!(
+                "Point {:?} is not in a child of leaf {:#b}", point, code)))?
+            .points.insert(id, point);
+    }
+
+    Ok(split)
+}
+
+/// Split leaves with more than `max_points` points and merge sibling leaves
+/// with together at most half that many.
+///
+/// `leaves` must have the state of every leaf of `structure`.
+pub fn rebalance(
+    structure: &OctreeStructure,
+    leaves: &BTreeMap<PartitionIndex, OctreeLeaf>,
+    max_points: usize,
+) -> Result<Rebalance, Error> {
+    let depth = structure.depth;
+    let mut new_structure = structure.clone();
+    let mut splits = BTreeSet::new();
+    let mut merges = BTreeSet::new();
+    let mut new_leaves = BTreeMap::new();
+
+    let overfull = |code: PartitionIndex, leaf: &OctreeLeaf| {
+        leaf.points.len() > max_points && level(code) < depth
+    };
+    let mut to_split: Vec<(PartitionIndex, OctreeLeaf)> = leaves.iter()
+        .filter(|&(&code, leaf)| overfull(code, leaf))
+        .map(|(&code, leaf)| (code, leaf.clone()))
+        .collect();
+    while let Some((code, leaf)) = to_split.pop() {
+        new_structure.split(code)?;
+        splits.insert(code);
+        for (child, child_leaf) in split_leaf(depth, code, leaf)? {
+            if overfull(child, &child_leaf) {
+                to_split.push((child, child_leaf));
+            } else {
+                new_leaves.insert(child, child_leaf);
+            }
+        }
+    }
+
+    let parents: BTreeSet<_> = structure.leaves.iter()
+        .filter(|&&code| code != ROOT_CODE)
+        .map(|&code| code >> 3)
+        .collect();
+    for parent in parents {
+        let siblings = children(parent);
+        if !siblings.iter().all(|c| structure.leaves.contains(c) && !splits.contains(c)) {
+            continue;
+        }
+
+        let merged = OctreeLeaf {
+            points: siblings.iter()
+                .filter_map(|c| leaves.get(c))
+                .flat_map(|leaf| leaf.points.iter().map(|(&id, &point)| (id, point)))
+                .collect(),
+        };
+        if merged.points.len() <= max_points / 2 {
+            new_structure.merge(parent)?;
+            merges.insert(parent);
+            new_leaves.insert(parent, merged);
+        }
+    }
+
+    Ok(Rebalance {
+        structure: new_structure,
+        delta: OctreeDelta::Structure {splits, merges},
+        leaves: new_leaves,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::datatype::ComposableState;
+
+    #[test]
+    fn test_locational_codes() {
+        assert_eq!(level(ROOT_CODE), 0);
+        assert_eq!(node_code(&[5, 0, 7], 0, 3), ROOT_CODE);
+        assert_eq!(node_code(&[5, 0, 7], 1, 3), 0b1_101);
+        assert_eq!(node_code(&[5, 0, 7], 3, 3), 0b1_101_100_101);
+        assert_eq!(level(0b1_101_100_101), 3);
+        assert!(children(ROOT_CODE).contains(&0b1_101));
+    }
+
+    #[test]
+    fn test_rebalance() {
+        let structure = OctreeStructure::new(2).unwrap();
+        let points: BTreeMap<PointId, Point> = vec![
+                (0, [0, 0, 0]),
+                (1, [1, 1, 1]),
+                (2, [0, 1, 0]),
+                (3, [3, 3, 3]),
+            ].into_iter().collect();
+        let leaves = maplit::btreemap!{ROOT_CODE => OctreeLeaf {points: points.clone()}};
+
+        let split = rebalance(&structure, &leaves, 2).unwrap();
+        let mut state = OctreeState::Structure(structure.clone());
+        PointOctree::compose_state(&mut state, &split.delta).unwrap();
+        assert_eq!(state, OctreeState::Structure(split.structure.clone()));
+
+        // The first octant is split again, the rest remain level 1 leaves.
+        assert!(!split.structure.leaves.contains(&ROOT_CODE));
+        assert!(!split.structure.leaves.contains(&0b1_000));
+        assert_eq!(split.structure.leaves.len(), 7 + 8);
+        assert_eq!(split.structure.leaf_for_point(&[1, 1, 1]), Some(0b1_000_111));
+        assert_eq!(
+            state.get_partition_ids(),
+            split.leaves.keys().cloned().chain(Some(UNARY_PARTITION_INDEX)).collect());
+        let total: usize = split.leaves.values().map(|l| l.points.len()).sum();
+        assert_eq!(total, points.len());
+
+        let merged = rebalance(&split.structure, &split.leaves, 8).unwrap();
+        PointOctree::compose_state(&mut state, &merged.delta).unwrap();
+        assert_eq!(state, OctreeState::Structure(merged.structure.clone()));
+        assert!(merged.structure.leaves.contains(&0b1_000));
+        assert_eq!(merged.leaves[&0b1_000].points.len(), 3);
+    }
+
+    #[test]
+    fn test_validate_delta() {
+        assert!(OctreeStructure::new(MAX_DEPTH + 1).is_err());
+
+        let structure = OctreeStructure::new(1).unwrap();
+        let state = OctreeState::Structure(structure.clone());
+        let structure_delta = |splits, merges| OctreeDelta::Structure {splits, merges};
+
+        assert!(PointOctree::validate_delta(&state, &structure_delta(btreeset![ROOT_CODE], btreeset![])).is_ok());
+        assert!(PointOctree::validate_delta(&state, &structure_delta(btreeset![ROOT_CODE], btreeset![ROOT_CODE])).is_ok());
+        assert!(PointOctree::validate_delta(&state, &structure_delta(btreeset![0b1_000], btreeset![])).is_err(),
+            "Split of a missing leaf was accepted");
+        assert!(PointOctree::validate_delta(&state, &structure_delta(btreeset![], btreeset![ROOT_CODE])).is_err(),
+            "Merge of a leaf was accepted");
+        let mut split = structure.clone();
+        split.split(ROOT_CODE).unwrap();
+        assert!(split.split(0b1_000).is_err(), "Split beyond maximum depth was accepted");
+
+        let leaf = OctreeState::Leaf(OctreeLeaf::default());
+        let leaf_delta = |point: Point| OctreeDelta::Leaf {
+            inserted: maplit::btreemap!{0 => point},
+            removed: btreeset![],
+        };
+        assert!(PointOctree::validate_delta(&leaf, &leaf_delta([1, 1, 1])).is_ok());
+        assert!(PointOctree::validate_delta(&leaf, &leaf_delta([1 << MAX_DEPTH, 0, 0])).is_err(),
+            "Out of bounds point was accepted");
+        assert!(PointOctree::validate_delta(&leaf, &structure_delta(btreeset![], btreeset![])).is_err(),
+            "Structure delta for a leaf was accepted");
+        assert!(PointOctree::compose_state(&mut state.clone(), &leaf_delta([0, 0, 0])).is_err(),
+            "Leaf delta for the structure was composed");
+
+        assert!(structure.leaf_deltas(maplit::btreemap!{0 => [2, 0, 0]}, maplit::btreemap!{}).is_err(),
+            "Out of bounds point was assigned a leaf");
+    }
+}
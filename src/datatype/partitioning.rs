@@ -1,4 +1,4 @@
-use std::collections::{BTreeSet};
+use std::collections::{BTreeMap, BTreeSet};
 
 use maplit::btreeset;
 
@@ -9,7 +9,9 @@ use heraclitus_macros::{
 use crate::{
     RepresentationKind,
     Error,
+    ModelError,
     PartitionIndex,
+    PartitionKind,
     VersionGraph,
     VersionGraphIndex,
 };
@@ -27,6 +29,15 @@ pub trait Partitioning {
     fn get_partition_ids(
         &self,
     ) -> BTreeSet<PartitionIndex>;
+
+    /// Kinds of each partition. By default all partitions are leaves.
+    fn get_partition_kinds(
+        &self,
+    ) -> BTreeMap<PartitionIndex, PartitionKind> {
+        self.get_partition_ids().into_iter()
+            .map(|p_idx| (p_idx, PartitionKind::Leaf))
+            .collect()
+    }
 }
 
 state_interface!(PartitioningState, Partitioning);
@@ -111,6 +122,14 @@ pub mod arbitrary {
     use super::*;
 
     use heraclitus_macros::stored_datatype_controller;
+    use serde_json::json;
+
+    use crate::datatype::diff::{
+        ContentChange,
+        ContentDiff,
+        ContentDiffController,
+        DiffableState,
+    };
 
 
     #[derive(Default, DatatypeMarker)]
@@ -118,26 +137,29 @@ pub mod arbitrary {
 
     impl DatatypeMeta for ArbitraryPartitioning {
         const NAME: &'static str = "ArbitraryPartitioning";
-        const VERSION: u64 = 1;
+        const VERSION: u64 = 2;
     }
 
-    impl<T: InterfaceController<PartitioningState>> Model<T> for ArbitraryPartitioning {
+    impl<T> Model<T> for ArbitraryPartitioning
+            where T: InterfaceController<PartitioningState> + InterfaceController<ContentDiffController> {
         fn reflection(&self) -> Reflection<T> {
             Reflection {
                 representations: enumset::enum_set!(
                         RepresentationKind::State |
+                        RepresentationKind::Delta |
                     ),
                 implements: vec![
                     <T as InterfaceController<PartitioningState>>::VARIANT,
+                    <T as InterfaceController<ContentDiffController>>::VARIANT,
                 ],
                 dependencies: vec![],
             }
         }
 
-        datatype_controllers!(ArbitraryPartitioning, (PartitioningState));
+        datatype_controllers!(ArbitraryPartitioning, (PartitioningState, ContentDiffController));
     }
 
-    #[derive(Debug, Hash, PartialEq)]
+    #[derive(Clone, Debug, Hash, PartialEq)]
     #[derive(Deserialize, Serialize)]
     pub struct ArbitraryPartitioningState {
         pub partition_ids: BTreeSet<PartitionIndex>,
@@ -149,10 +171,556 @@ pub mod arbitrary {
         }
     }
 
-    impl crate::datatype::StateOnly for ArbitraryPartitioning {
-        type StateOnlyType = ArbitraryPartitioningState;
+    /// Partition IDs added and removed. Removals are applied first, so an ID
+    /// in both is present after composition.
+    #[derive(Clone, Debug, Default, Hash, PartialEq)]
+    #[derive(Deserialize, Serialize)]
+    pub struct ArbitraryPartitioningDelta {
+        pub additions: BTreeSet<PartitionIndex>,
+        pub removals: BTreeSet<PartitionIndex>,
+    }
+
+    impl crate::datatype::ComposableState for ArbitraryPartitioning {
+        type StateType = ArbitraryPartitioningState;
+        type DeltaType = ArbitraryPartitioningDelta;
+
+        fn compose_state(
+            state: &mut Self::StateType,
+            delta: &Self::DeltaType,
+        ) -> Result<(), Error> {
+            for p_idx in &delta.removals {
+                state.partition_ids.remove(p_idx);
+            }
+            state.partition_ids.extend(delta.additions.iter().cloned());
+
+            Ok(())
+        }
+    }
+
+    impl DiffableState for ArbitraryPartitioningState {
+        /// Diff partition IDs added and removed.
+        fn diff_states(from: Option<&Self>, to: Option<&Self>) -> ContentDiff {
+            let empty = BTreeSet::new();
+            let from = from.map_or(&empty, |state| &state.partition_ids);
+            let to = to.map_or(&empty, |state| &state.partition_ids);
+
+            ContentDiff {
+                changes: to.difference(from).map(|p_idx| ContentChange::Added(json!(p_idx)))
+                    .chain(from.difference(to).map(|p_idx| ContentChange::Removed(json!(p_idx))))
+                    .collect(),
+            }
+        }
     }
 
     #[stored_datatype_controller(ArbitraryPartitioning)]
     pub trait Storage: crate::datatype::Storage {}
+
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        use crate::datatype::ComposableState;
+
+        #[test]
+        fn test_compose_partition_changes() {
+            let mut state = ArbitraryPartitioningState {partition_ids: btreeset![0, 1, 2]};
+            let delta = ArbitraryPartitioningDelta {
+                additions: btreeset![2, 5],
+                removals: btreeset![1, 2],
+            };
+
+            ArbitraryPartitioning::compose_state(&mut state, &delta).unwrap();
+
+            assert_eq!(state.get_partition_ids(), btreeset![0, 2, 5]);
+        }
+
+        #[test]
+        fn test_diff_partition_ids() {
+            let from = ArbitraryPartitioningState {partition_ids: btreeset![0, 1, 2]};
+            let to = ArbitraryPartitioningState {partition_ids: btreeset![1, 2, 3, 4]};
+
+            assert_eq!(
+                ArbitraryPartitioningState::diff_states(Some(&from), Some(&to)).changes,
+                vec![
+                    ContentChange::Added(json!(3)),
+                    ContentChange::Added(json!(4)),
+                    ContentChange::Removed(json!(0)),
+                ]);
+            assert_eq!(
+                ArbitraryPartitioningState::diff_states(None, Some(&from)).to_string(),
+                "+ 0\n+ 1\n+ 2\n");
+        }
+    }
+}
+
+
+/// Regular N-dimensional grids of blocks.
+///
+/// Partition indices are the row-major indices of blocks in the grid.
+pub mod grid {
+    use super::*;
+
+    use heraclitus_macros::stored_datatype_controller;
+
+
+    #[derive(Default, DatatypeMarker)]
+    pub struct GridPartitioning;
+
+    impl DatatypeMeta for GridPartitioning {
+        const NAME: &'static str = "GridPartitioning";
+        const VERSION: u64 = 1;
+    }
+
+    impl<T: InterfaceController<PartitioningState>> Model<T> for GridPartitioning {
+        fn reflection(&self) -> Reflection<T> {
+            Reflection {
+                representations: enumset::enum_set!(
+                        RepresentationKind::State |
+                    ),
+                implements: vec![
+                    <T as InterfaceController<PartitioningState>>::VARIANT,
+                ],
+                dependencies: vec![],
+            }
+        }
+
+        datatype_controllers!(GridPartitioning, (PartitioningState));
+    }
+
+    #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+    #[derive(Deserialize, Serialize)]
+    pub struct GridPartitioningState {
+        pub extent: Vec<u64>,
+        pub block_size: Vec<u64>,
+    }
+
+    impl GridPartitioningState {
+        /// Check that the extent and block size have the same dimensionality,
+        /// that blocks are not empty, and that the number of blocks fits in a
+        /// partition index.
+        pub fn validate(&self) -> Result<(), Error> {
+            if self.extent.len() != self.block_size.len() {
+                return Err(ModelError::Other(
+                    "Grid extent and block size dimensionality do not match".into()).into());
+            }
+            if self.block_size.iter().any(|&b| b == 0) {
+                return Err(ModelError::Other("Grid block size is zero".into()).into());
+            }
+            self.unchecked_grid_shape().iter()
+                .try_fold(1u64, |count, &g| count.checked_mul(g))
+                .ok_or_else(|| ModelError::Other("Grid has too many blocks".into()))?;
+
+            Ok(())
+        }
+
+        fn unchecked_grid_shape(&self) -> Vec<u64> {
+            self.extent.iter().zip(self.block_size.iter())
+                .map(|(&e, &b)| e / b + if e % b == 0 {0} else {1})
+                .collect()
+        }
+
+        /// Number of blocks along each dimension.
+        pub fn grid_shape(&self) -> Result<Vec<u64>, Error> {
+            self.validate()?;
+
+            Ok(self.unchecked_grid_shape())
+        }
+
+        pub fn num_blocks(&self) -> Result<u64, Error> {
+            // Validation checks that this product does not overflow.
+            Ok(self.grid_shape()?.iter().product())
+        }
+
+        /// Partition index of a block: its row-major index in the grid.
+        pub fn block_partition_index(&self, block_coords: &[u64]) -> Result<PartitionIndex, Error> {
+            let grid_shape = self.grid_shape()?;
+            if block_coords.len() != grid_shape.len() ||
+                    block_coords.iter().zip(grid_shape.iter()).any(|(c, g)| c >= g) {
+                return Err(ModelError::Other(format!(
+                    "Block {:?} is outside the grid", block_coords)).into());
+            }
+
+            Ok(block_coords.iter().zip(grid_shape.iter())
+                .fold(0, |p_idx, (&c, &g)| p_idx * g + c))
+        }
+
+        /// Block coordinates of a partition index.
+        pub fn partition_block_coords(&self, p_idx: PartitionIndex) -> Result<Vec<u64>, Error> {
+            if p_idx >= self.num_blocks()? {
+                return Err(ModelError::Other(format!(
+                    "Grid partition {} has no block", p_idx)).into());
+            }
+
+            let grid_shape = self.grid_shape()?;
+            let mut remainder = p_idx;
+            let mut coords = vec![0; grid_shape.len()];
+            for (coord, &g) in coords.iter_mut().zip(grid_shape.iter()).rev() {
+                *coord = remainder % g;
+                remainder /= g;
+            }
+
+            Ok(coords)
+        }
+
+        /// Shape of a block, which is truncated at the upper grid boundary.
+        /// Blocks outside the grid are empty.
+        pub fn block_shape(&self, block_coords: &[u64]) -> Vec<u64> {
+            block_coords.iter().zip(self.extent.iter()).zip(self.block_size.iter())
+                .map(|((&c, &e), &b)| c.checked_mul(b).map_or(0, |start| b.min(e.saturating_sub(start))))
+                .collect()
+        }
+
+        /// Partitions of all blocks intersecting the half-open bounding box
+        /// from `min` (inclusive) to `max` (exclusive).
+        pub fn intersecting_partitions(
+            &self,
+            min: &[u64],
+            max: &[u64],
+        ) -> Result<BTreeSet<PartitionIndex>, Error> {
+            let grid_shape = self.grid_shape()?;
+            if min.len() != grid_shape.len() || max.len() != grid_shape.len() {
+                return Err(ModelError::Other("Bounding box dimensionality does not match grid".into()).into());
+            }
+
+            // Half-open range of intersecting block coordinates in each dimension.
+            let ranges = min.iter().zip(max.iter()).zip(self.block_size.iter()).zip(grid_shape.iter())
+                .map(|(((&lo, &hi), &b), &g)| (lo / b, (hi / b + if hi % b == 0 {0} else {1}).min(g)))
+                .collect::<Vec<_>>();
+
+            let mut partitions = BTreeSet::new();
+            if min.iter().zip(max.iter()).any(|(lo, hi)| lo >= hi) ||
+                    ranges.iter().any(|&(lo, hi)| lo >= hi) {
+                return Ok(partitions);
+            }
+
+            let mut coords = ranges.iter().map(|&(lo, _)| lo).collect::<Vec<_>>();
+            'blocks: loop {
+                // Ranges are bounded by the grid shape, so all coordinates
+                // have a partition.
+                partitions.insert(self.block_partition_index(&coords)?);
+
+                // Increment coordinates as an odometer, last dimension fastest.
+                for (coord, &(lo, hi)) in coords.iter_mut().zip(ranges.iter()).rev() {
+                    *coord += 1;
+                    if *coord < hi {
+                        continue 'blocks;
+                    }
+                    *coord = lo;
+                }
+
+                break;
+            }
+
+            Ok(partitions)
+        }
+    }
+
+    impl Partitioning for GridPartitioningState {
+        /// Invalid grids have no partitions.
+        fn get_partition_ids(&self) -> BTreeSet<PartitionIndex> {
+            (0..self.num_blocks().unwrap_or(0)).collect()
+        }
+    }
+
+    impl crate::datatype::StateOnly for GridPartitioning {
+        type StateOnlyType = GridPartitioningState;
+    }
+
+    #[stored_datatype_controller(GridPartitioning)]
+    pub trait Storage: crate::datatype::Storage {}
+
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_grid_indexing() {
+            let grid = GridPartitioningState {
+                extent: vec![10, 7, 3],
+                block_size: vec![4, 4, 4],
+            };
+
+            assert_eq!(grid.grid_shape().unwrap(), vec![3, 2, 1]);
+            assert_eq!(grid.num_blocks().unwrap(), 6);
+            assert_eq!(grid.get_partition_ids(), (0..6).collect::<BTreeSet<_>>());
+            for p_idx in 0..6 {
+                let coords = grid.partition_block_coords(p_idx).unwrap();
+                assert_eq!(grid.block_partition_index(&coords).unwrap(), p_idx);
+            }
+            assert_eq!(grid.block_partition_index(&[2, 1, 0]).unwrap(), 5);
+            assert!(grid.block_partition_index(&[3, 0, 0]).is_err());
+            assert!(grid.partition_block_coords(6).is_err());
+            assert_eq!(grid.block_shape(&[2, 1, 0]), vec![2, 3, 3]);
+        }
+
+        #[test]
+        fn test_intersecting_partitions() {
+            let grid = GridPartitioningState {
+                extent: vec![10, 7],
+                block_size: vec![4, 4],
+            };
+
+            assert_eq!(grid.intersecting_partitions(&[0, 0], &[1, 1]).unwrap(), btreeset![0]);
+            assert_eq!(grid.intersecting_partitions(&[3, 3], &[5, 5]).unwrap(), btreeset![0, 1, 2, 3]);
+            assert_eq!(grid.intersecting_partitions(&[5, 0], &[100, 4]).unwrap(), btreeset![2, 4]);
+            assert!(grid.intersecting_partitions(&[2, 2], &[2, 6]).unwrap().is_empty());
+            assert!(grid.intersecting_partitions(&[12, 0], &[20, 7]).unwrap().is_empty());
+            assert!(grid.intersecting_partitions(&[0], &[1]).is_err());
+        }
+
+        #[test]
+        fn test_invalid_grids() {
+            let zero_block = GridPartitioningState {
+                extent: vec![10, 7],
+                block_size: vec![4, 0],
+            };
+            assert!(zero_block.validate().is_err());
+            assert!(zero_block.num_blocks().is_err());
+            assert!(zero_block.get_partition_ids().is_empty());
+            assert!(zero_block.intersecting_partitions(&[0, 0], &[1, 1]).is_err());
+
+            let mismatched = GridPartitioningState {
+                extent: vec![10, 7],
+                block_size: vec![4],
+            };
+            assert!(mismatched.validate().is_err());
+            assert!(mismatched.grid_shape().is_err());
+
+            let overflowing = GridPartitioningState {
+                extent: vec![u64::max_value(), u64::max_value()],
+                block_size: vec![1, 1],
+            };
+            assert!(overflowing.validate().is_err());
+            assert!(overflowing.num_blocks().is_err());
+            assert!(overflowing.get_partition_ids().is_empty());
+            assert!(overflowing.partition_block_coords(0).is_err());
+
+            let grid = GridPartitioningState {
+                extent: vec![10, 7],
+                block_size: vec![4, 4],
+            };
+            assert_eq!(grid.block_shape(&[5, 0]), vec![0, 4]);
+            assert_eq!(grid.block_shape(&[u64::max_value(), 1]), vec![0, 3]);
+        }
+    }
+}
+
+
+/// Partitioning of keys by ranges of a stable 64-bit hash.
+///
+/// Each partition covers a contiguous range of the hash space, so partitions
+/// can later be split without reassigning keys of any other partition.
+pub mod hash {
+    use super::*;
+
+    use heraclitus_macros::stored_datatype_controller;
+
+
+    #[derive(Default, DatatypeMarker)]
+    pub struct HashPartitioning;
+
+    impl DatatypeMeta for HashPartitioning {
+        const NAME: &'static str = "HashPartitioning";
+        const VERSION: u64 = 1;
+    }
+
+    impl<T: InterfaceController<PartitioningState>> Model<T> for HashPartitioning {
+        fn reflection(&self) -> Reflection<T> {
+            Reflection {
+                representations: enumset::enum_set!(
+                        RepresentationKind::State |
+                        RepresentationKind::Delta |
+                    ),
+                implements: vec![
+                    <T as InterfaceController<PartitioningState>>::VARIANT,
+                ],
+                dependencies: vec![],
+            }
+        }
+
+        datatype_controllers!(HashPartitioning, (PartitioningState));
+    }
+
+    /// Hash a key with 64-bit FNV-1a. Unlike `DefaultHasher`, this is stable
+    /// across builds, so may be used to persistently assign keys to partitions.
+    pub fn hash_key(key: &[u8]) -> u64 {
+        key.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    }
+
+    /// Partitionings that assign each key to one of their partitions.
+    pub trait KeyPartitioning: Partitioning {
+        fn partition_for_key(&self, key: &[u8]) -> Result<PartitionIndex, Error>;
+    }
+
+    /// Map from the (inclusive) start of each hash range to its partition. A
+    /// range extends to the start of the next range.
+    #[derive(Clone, Debug, Hash, PartialEq)]
+    #[derive(Deserialize, Serialize)]
+    pub struct HashPartitioningState {
+        pub ranges: BTreeMap<u64, PartitionIndex>,
+    }
+
+    /// New ranges, each splitting the range containing its start.
+    pub type HashPartitioningDelta = BTreeMap<u64, PartitionIndex>;
+
+    impl HashPartitioningState {
+        /// Evenly divide the hash space into partitions `0..num_partitions`.
+        pub fn new(num_partitions: u64) -> Result<Self, Error> {
+            if num_partitions == 0 {
+                return Err(ModelError::Other("Hash partitioning must have a partition".into()).into());
+            }
+            let width = u64::max_value() / num_partitions;
+            Ok(HashPartitioningState {
+                ranges: (0..num_partitions).map(|p_idx| (p_idx * width, p_idx)).collect(),
+            })
+        }
+
+        pub fn partition_for_hash(&self, hash: u64) -> Result<PartitionIndex, Error> {
+            self.ranges.range(..=hash).next_back()
+                .map(|(_, &p_idx)| p_idx)
+                .ok_or_else(|| ModelError::Other("Hash ranges do not start at zero".into()).into())
+        }
+
+        /// Split the range of a partition in half, assigning the upper half
+        /// to a new partition. Returns the delta for this split, or `None` if
+        /// the partition is not splittable.
+        pub fn split(&mut self, p_idx: PartitionIndex) -> Option<HashPartitioningDelta> {
+            let start = *self.ranges.iter().find(|(_, &p)| p == p_idx)?.0;
+            if start == u64::max_value() {
+                return None;
+            }
+            let end = self.ranges.range(start + 1..).next()
+                .map_or(u64::max_value(), |(&next, _)| next - 1);
+            if end == start {
+                return None;
+            }
+
+            let new_idx = self.ranges.values().max().expect("Hash ranges are empty") + 1;
+            let mut delta = HashPartitioningDelta::new();
+            delta.insert(start + (end - start) / 2 + 1, new_idx);
+            self.ranges.extend(delta.iter().map(|(&s, &p)| (s, p)));
+
+            Some(delta)
+        }
+    }
+
+    impl Partitioning for HashPartitioningState {
+        fn get_partition_ids(&self) -> BTreeSet<PartitionIndex> {
+            self.ranges.values().cloned().collect()
+        }
+    }
+
+    impl KeyPartitioning for UnaryPartitioningState {
+        fn partition_for_key(&self, _key: &[u8]) -> Result<PartitionIndex, Error> {
+            Ok(UNARY_PARTITION_INDEX)
+        }
+    }
+
+    impl KeyPartitioning for HashPartitioningState {
+        fn partition_for_key(&self, key: &[u8]) -> Result<PartitionIndex, Error> {
+            self.partition_for_hash(hash_key(key))
+        }
+    }
+
+    impl crate::datatype::ComposableState for HashPartitioning {
+        type StateType = HashPartitioningState;
+        type DeltaType = HashPartitioningDelta;
+
+        fn compose_state(
+            state: &mut Self::StateType,
+            delta: &Self::DeltaType,
+        ) -> Result<(), Error> {
+            Self::validate_delta(state, delta)?;
+
+            state.ranges.extend(delta.iter().map(|(&start, &p_idx)| (start, p_idx)));
+
+            Ok(())
+        }
+
+        /// New ranges must split existing ones, so may not start where a
+        /// range already starts, and must be assigned new partitions.
+        fn validate_delta(
+            state: &Self::StateType,
+            delta: &Self::DeltaType,
+        ) -> Result<(), Error> {
+            let partitions = state.get_partition_ids();
+            let mut new_partitions = BTreeSet::new();
+            for (start, p_idx) in delta {
+                if state.ranges.contains_key(start) {
+                    return Err(ModelError::Other(format!(
+                        "Hash range starting at {} already exists", start)).into());
+                }
+                if partitions.contains(p_idx) || !new_partitions.insert(p_idx) {
+                    return Err(ModelError::Other(format!(
+                        "Hash partition {} is already assigned a range", p_idx)).into());
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    #[stored_datatype_controller(HashPartitioning)]
+    pub trait Storage: crate::datatype::Storage {}
+
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        use maplit::btreemap;
+
+        use crate::datatype::ComposableState;
+
+        #[test]
+        fn test_split_preserves_other_partitions() {
+            let mut partitioning = HashPartitioningState::new(3).unwrap();
+            assert_eq!(partitioning.get_partition_ids(), btreeset![0, 1, 2]);
+
+            let keys = (0..64).map(|i| format!("key{}", i)).collect::<Vec<_>>();
+            let before = keys.iter()
+                .map(|k| partitioning.partition_for_key(k.as_bytes()).unwrap())
+                .collect::<Vec<_>>();
+
+            let mut composed = partitioning.clone();
+            let delta = partitioning.split(1).unwrap();
+            HashPartitioning::compose_state(&mut composed, &delta).unwrap();
+            assert_eq!(composed, partitioning);
+            assert_eq!(partitioning.get_partition_ids(), btreeset![0, 1, 2, 3]);
+
+            for (key, &old) in keys.iter().zip(before.iter()) {
+                let new = partitioning.partition_for_key(key.as_bytes()).unwrap();
+                if old == 1 {
+                    assert!(new == 1 || new == 3);
+                } else {
+                    assert_eq!(new, old);
+                }
+            }
+        }
+
+        #[test]
+        fn test_validate_delta() {
+            assert!(HashPartitioningState::new(0).is_err());
+
+            let state = HashPartitioningState::new(2).unwrap();
+            let middle = *state.ranges.keys().last().unwrap();
+            assert!(HashPartitioning::validate_delta(&state, &btreemap!{1 => 2}).is_ok());
+            assert!(HashPartitioning::validate_delta(&state, &btreemap!{middle => 2}).is_err(),
+                "Delta replacing an existing range was accepted");
+            assert!(HashPartitioning::validate_delta(&state, &btreemap!{1 => 1}).is_err(),
+                "Delta reusing an existing partition was accepted");
+            assert!(HashPartitioning::validate_delta(&state, &btreemap!{1 => 2, middle + 1 => 2}).is_err(),
+                "Delta assigning a partition two ranges was accepted");
+            assert!(HashPartitioning::compose_state(&mut state.clone(), &btreemap!{0 => 2}).is_err(),
+                "Delta replacing the first range was composed");
+
+            let missing_zero = HashPartitioningState {ranges: btreemap!{5 => 0}};
+            assert!(missing_zero.partition_for_hash(4).is_err());
+            assert_eq!(missing_zero.partition_for_hash(5).unwrap(), 0);
+        }
+    }
 }
@@ -111,7 +111,7 @@ pub(crate) mod tests {
     use uuid::Uuid;
 
     use crate::{
-        ArtifactRelation, Hunk, Identity, IdentifiableGraph,
+        ArtifactRelation, Hunk, Identity, IdentifiableGraph, ModelError,
         PartCompletion, Version, VersionRelation};
     use crate::datatype::{
         ComposableState,
@@ -195,14 +195,18 @@ pub(crate) mod tests {
             let input_ver = *ver_graph.get_related_versions(
                 v_idx,
                 &input_relation,
-                Direction::Incoming).get(0).expect("TODO");
+                Direction::Incoming).get(0)
+                .ok_or_else(|| ModelError::IncompatibleProducer {
+                    producer: ver_graph[v_idx].artifact.id.uuid,
+                    reason: "Producer version has no input version".into(),
+                })?;
 
             // Set own hash to input version.
             // TODO: not yet clear what producer version hash should be.
             ver_graph[v_idx].id.hash = ver_graph[input_ver].id.hash;
 
-            let (art_idx, _) = art_graph.get_by_id(&ver_graph[v_idx].artifact.id)
-                .expect("TODO2");
+            let (art_idx, art) = art_graph.get_by_id(&ver_graph[v_idx].artifact.id)
+                .ok_or_else(|| ModelError::UnknownArtifact(ver_graph[v_idx].artifact.id.uuid))?;
 
             // Find output relation and artifact.
             let output_art_relation_needle = ArtifactRelation::ProducedFrom("output".into());
@@ -210,7 +214,10 @@ pub(crate) mod tests {
                 .edges_directed(art_idx, Direction::Outgoing)
                 .find(|e| e.weight() == &output_art_relation_needle)
                 .map(|e| (e.weight(), e.target()))
-                .expect("TODO3");
+                .ok_or_else(|| ModelError::IncompatibleProducer {
+                    producer: art.id.uuid,
+                    reason: "Producer has no output artifact".into(),
+                })?;
             let output_art = &art_graph[output_art_idx];
 
             // Create output version.
@@ -226,11 +233,16 @@ pub(crate) mod tests {
             // This producer requires that the output use the same partitioning
             // as the input.
             // TODO: How should such constraints be formalized?
-            let (input_ver_part_idx, _) = ver_graph.get_partitioning(input_ver).unwrap();
-            let (input_art_part_idx, _) = art_graph.get_by_id(&ver_graph[input_ver_part_idx].artifact.id).expect("TODO");
+            let (input_ver_part_idx, _) = ver_graph.get_partitioning(input_ver)
+                .ok_or_else(|| ModelError::MalformedGraph("Input version has no partitioning".into()))?;
+            let (input_art_part_idx, _) = art_graph.get_by_id(&ver_graph[input_ver_part_idx].artifact.id)
+                .ok_or_else(|| ModelError::UnknownArtifact(ver_graph[input_ver_part_idx].artifact.id.uuid))?;
             // TODO: should check that this is the same the producer's partitioning.
             let output_part_art_rel_idx = art_graph.artifacts.find_edge(input_art_part_idx, output_art_idx)
-                .expect("TODO");
+                .ok_or_else(|| ModelError::IncompatibleProducer {
+                    producer: art.id.uuid,
+                    reason: "Output does not share the input's partitioning".into(),
+                })?;
             let output_part_art_rel = &art_graph[output_part_art_rel_idx];
             // TODO: check this is actually a partitioning rel.
             ver_graph.versions.add_edge(input_ver_part_idx, ver_blob_idx,
@@ -246,7 +258,8 @@ pub(crate) mod tests {
                 let parent_output_idx = *ver_graph.get_related_versions(
                     parent_ver_idx,
                     &VersionRelation::Dependence(output_art_relation),
-                    Direction::Outgoing).get(0).expect("TODO: parent should have output");
+                    Direction::Outgoing).get(0)
+                    .ok_or_else(|| ModelError::MalformedGraph("Parent producer version has no output".into()))?;
                 ver_graph.versions.add_edge(parent_output_idx, ver_blob_idx,
                     VersionRelation::Parent)?;
             }
@@ -260,7 +273,7 @@ pub(crate) mod tests {
             ag_control.create_staging_version(
                 repo,
                 ver_graph,
-                ver_blob_idx.clone()).unwrap();
+                ver_blob_idx.clone())?;
 
             let mut ver_hash = DefaultHasher::new();
             // Get input hunks.
@@ -271,12 +284,12 @@ pub(crate) mod tests {
                     repo,
                     &ver_graph[input_ver],
                     &ver_graph[input_ver_part_idx],
-                    None).expect("TODO");
+                    None)?;
 
                 // Create output hunks computed from input hunks.
                 let mut blob_control = BlobDatatype::store(repo);
                 for input_hunk in &input_hunks {
-                    let input_blob = blob_control.read_hunk(repo, input_hunk).expect("TODO");
+                    let input_blob = blob_control.read_hunk(repo, input_hunk)?;
                     let output_blob = match input_blob {
                         Payload::State(ref blob) =>
                             Payload::State(blob.iter().cloned().map(|b| !b).collect::<Vec<u8>>()),
@@ -296,11 +309,11 @@ pub(crate) mod tests {
                     };
                     output_hunk.id.hash.hash(&mut ver_hash);
 
-                    ag_control.create_hunk(repo, &output_hunk).expect("TODO");
+                    ag_control.create_hunk(repo, &output_hunk)?;
                     blob_control.write_hunk(
                         repo,
                         &output_hunk,
-                        &output_blob).expect("TODO");
+                        &output_blob)?;
                 }
             }
 
@@ -0,0 +1,287 @@
+//! Replaying versions of an artifact onto other versions.
+//!
+//! `rebase` replays a chain of `Delta` or `CumulativeDelta` versions onto a
+//! new parent version, creating a new version for each with copies of its
+//! hunks. `cherry_pick` replays a single version. Partitions changed both by
+//! the replayed versions and between their original parent and the new
+//! parent conflict, since the replayed hunks were made against a different
+//! state. Replayed deltas that do not apply to the new parent's state also
+//! conflict, since deltas are not checked when they are written.
+
+use std::collections::BTreeSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::{
+    ArtifactGraph,
+    Composition,
+    CompositionMap,
+    Error,
+    HashType,
+    Hunk,
+    Identity,
+    ModelError,
+    PartCompletion,
+    Partition,
+    PartitionIndex,
+    PartitionKind,
+    RepresentationKind,
+    VersionGraph,
+    VersionGraphIndex,
+};
+use super::{
+    ComposableState,
+    DatatypeEnum,
+    DatatypeMarker,
+    DatatypesRegistry,
+    InterfaceController,
+    Payload,
+    Storage,
+};
+use super::artifact_graph::{
+    ArtifactGraphDtype,
+    Storage as ArtifactGraphStorage,
+};
+use super::interface::{
+    CustomProductionPolicyController,
+    ProducerController,
+};
+use super::partitioning::PartitioningState;
+use super::snapshot::is_snapshot;
+use crate::repo::Repository;
+
+
+#[derive(Debug, PartialEq)]
+pub enum RebaseOutcome {
+    /// The committed replayed versions, in the order of the replayed chain.
+    Rebased(Vec<VersionGraphIndex>),
+    /// Partitions changed both by the replayed versions and since their
+    /// original parent, or whose replayed deltas do not apply to the new
+    /// parent. No versions are created.
+    Conflicted(BTreeSet<PartitionIndex>),
+}
+
+/// A hunk to be copied to a replayed version.
+struct HunkCopy<P> {
+    index: PartitionIndex,
+    kind: PartitionKind,
+    hash: HashType,
+    representation: RepresentationKind,
+    completion: PartCompletion,
+    payload: P,
+}
+
+/// Whether the copies of a partition's hunks, replayed in order, apply to
+/// its state in a composition of the new parent.
+fn copies_apply<S: Storage>(
+    store: &S,
+    repo: &Repository,
+    onto: Option<&Composition>,
+    p_idx: PartitionIndex,
+    copies: &[(RepresentationKind, Vec<HunkCopy<Payload<S::StateType, S::DeltaType>>>)],
+) -> Result<bool, Error> {
+    let mut state = match onto {
+        Some(composition) if !composition.is_empty() =>
+            Some(store.get_composite_state(repo, composition)?),
+        _ => None,
+    };
+
+    for copy in copies.iter().flat_map(|(_, hunk_copies)| hunk_copies).filter(|copy| copy.index == p_idx) {
+        match (state.as_mut(), &copy.payload) {
+            // Later copies were made against this state, as in the
+            // original chain.
+            (_, Payload::State(_)) => return Ok(true),
+            (Some(state), Payload::Delta(delta)) => {
+                if S::Datatype::validate_delta(state, delta).is_err() ||
+                        S::Datatype::compose_state(state, delta).is_err() {
+                    return Ok(false);
+                }
+            },
+            (None, Payload::Delta(_)) => return Ok(false),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Replay `versions`, a chain of versions of an artifact each the only
+/// parent of the next, onto version `onto`, which must share their
+/// partitioning.
+pub fn rebase<'ag, D, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    art_graph: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    versions: &[VersionGraphIndex],
+    onto: VersionGraphIndex,
+) -> Result<RebaseOutcome, Error>
+        where
+            D: DatatypeMarker,
+            <D as DatatypeMarker>::Store: Storage,
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ProducerController> +
+                InterfaceController<CustomProductionPolicyController>
+{
+    let mut ag_control = ArtifactGraphDtype::store(repo);
+    let mut dtype_control = D::store(repo);
+
+    let (first, rest) = versions.split_first()
+        .ok_or_else(|| ModelError::Other("No versions to rebase".into()))?;
+    let artifact_id = ver_graph[onto].artifact.id;
+    let (part_idx, _) = ver_graph.get_partitioning(onto)
+        .ok_or_else(|| ModelError::MalformedGraph("Version has no partitioning".into()))?;
+
+    let base_parents = ver_graph.get_parents(*first);
+    if base_parents.len() != 1 {
+        return Err(ModelError::Other("Rebased versions must have one parent".into()).into());
+    }
+    let base_idx = base_parents[0];
+    for (&v_idx, &parent_idx) in rest.iter().zip(versions.iter()) {
+        if ver_graph.get_parents(v_idx) != [parent_idx] {
+            return Err(ModelError::Other("Rebased versions are not a chain".into()).into());
+        }
+    }
+    for &v_idx in versions {
+        let version = &ver_graph[v_idx];
+        if version.artifact.id != artifact_id {
+            return Err(ModelError::Other("Rebased versions are not versions of the onto artifact".into()).into());
+        }
+        if version.representation == RepresentationKind::State {
+            return Err(ModelError::Other("Only delta versions can be rebased".into()).into());
+        }
+        if ver_graph.get_partitioning(v_idx).map(|(idx, _)| idx) != Some(part_idx) {
+            return Err(ModelError::Other("Rebased versions have a different partitioning".into()).into());
+        }
+    }
+
+    let partitions = ag_control.get_partitioning_partitions(dtypes_registry, repo, ver_graph, part_idx)?;
+
+    let copies = {
+        let ver_graph: &VersionGraph<'ag> = ver_graph;
+        let partition_ids: BTreeSet<_> = partitions.keys().cloned().collect();
+
+        let mut copies = vec![];
+        let mut changed = BTreeSet::new();
+        for &v_idx in versions {
+            let mut hunks = ag_control.get_hunks(repo, &ver_graph[v_idx], &ver_graph[part_idx], None)?;
+            hunks.sort_by_key(|hunk| hunk.partition.index);
+
+            let mut hunk_copies = vec![];
+            for hunk in &hunks {
+                // Snapshots are of the original ancestry, so are not copied.
+                if is_snapshot(hunk, &hunks) {
+                    continue;
+                }
+                if hunk.precedence.is_some() {
+                    return Err(ModelError::Other("Merge versions can not be rebased".into()).into());
+                }
+                changed.insert(hunk.partition.index);
+                hunk_copies.push(HunkCopy {
+                    index: hunk.partition.index,
+                    kind: hunk.partition.kind,
+                    hash: hunk.id.hash,
+                    representation: hunk.representation,
+                    completion: hunk.completion,
+                    payload: dtype_control.read_hunk(repo, hunk)?,
+                });
+            }
+            copies.push((ver_graph[v_idx].representation, hunk_copies));
+        }
+
+        let composition_map = |v_idx| ag_control.get_partitioned_composition_map(
+            dtypes_registry, repo, ver_graph, v_idx, partition_ids.clone());
+        let base_map: CompositionMap = composition_map(base_idx)?;
+        let onto_map: CompositionMap = composition_map(onto)?;
+
+        let empty = Composition::new();
+        let hunk_ids = |composition: &Composition| composition.iter()
+            .map(|hunk| hunk.id)
+            .collect::<Vec<Identity>>();
+        let mut conflicts = BTreeSet::new();
+        for p_id in changed {
+            let conflicted = hunk_ids(base_map.get(&p_id).unwrap_or(&empty)) !=
+                    hunk_ids(onto_map.get(&p_id).unwrap_or(&empty)) ||
+                !copies_apply(&dtype_control, repo, onto_map.get(&p_id), p_id, &copies)?;
+            if conflicted {
+                conflicts.insert(p_id);
+            }
+        }
+
+        if !conflicts.is_empty() {
+            return Ok(RebaseOutcome::Conflicted(conflicts));
+        }
+
+        copies
+    };
+
+    let mut rebased = vec![];
+    let mut parent_idx = onto;
+    for (representation, hunk_copies) in copies {
+        let new_idx = ver_graph.new_child_same_dependencies(parent_idx, representation)?;
+
+        ag_control.create_staging_version(repo, ver_graph, new_idx)?;
+
+        let ver_hash = {
+            let mut ver_hash = DefaultHasher::new();
+
+            // Hunks are in ascending partition order, so the version hash is
+            // consistent.
+            for copy in &hunk_copies {
+                let hunk = Hunk {
+                    id: copy.hash.into(),
+                    version: &ver_graph[new_idx],
+                    partition: Partition {
+                        partitioning: &ver_graph[part_idx],
+                        index: copy.index,
+                        kind: copy.kind,
+                    },
+                    representation: copy.representation,
+                    completion: copy.completion,
+                    precedence: None,
+                };
+                hunk.id.hash.hash(&mut ver_hash);
+
+                ag_control.create_hunk(repo, &hunk)?;
+                dtype_control.write_hunk(repo, &hunk, &copy.payload)?;
+            }
+
+            ver_hash.finish()
+        };
+        ver_graph[new_idx].id.hash = ver_hash;
+
+        ag_control.commit_version(
+            dtypes_registry,
+            repo,
+            art_graph,
+            ver_graph,
+            new_idx)?;
+
+        rebased.push(new_idx);
+        parent_idx = new_idx;
+    }
+
+    Ok(RebaseOutcome::Rebased(rebased))
+}
+
+/// Replay version `v_idx` onto version `onto`. See `rebase`.
+pub fn cherry_pick<'ag, D, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    art_graph: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    v_idx: VersionGraphIndex,
+    onto: VersionGraphIndex,
+) -> Result<RebaseOutcome, Error>
+        where
+            D: DatatypeMarker,
+            <D as DatatypeMarker>::Store: Storage,
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ProducerController> +
+                InterfaceController<CustomProductionPolicyController>
+{
+    rebase::<D, T>(dtypes_registry, repo, art_graph, ver_graph, &[v_idx], onto)
+}
@@ -0,0 +1,188 @@
+//! Moving artifact data from one partitioning to another.
+//!
+//! Datatypes supporting repartitioning implement `MergePartitions`, to
+//! combine the composite states of all partitions of a version, and
+//! `SplitPartitions` for each partitioning state type they can be sliced by.
+//! `repartition` then uses these to write a new version under the new
+//! partitioning.
+
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::{
+    Artifact,
+    ArtifactGraph,
+    Error,
+    Hunk,
+    IdentifiableGraph,
+    ModelError,
+    PartCompletion,
+    Partition,
+    PartitionIndex,
+    RepresentationKind,
+    Version,
+    VersionGraph,
+    VersionGraphIndex,
+    VersionRelation,
+};
+use super::{
+    ComposableState,
+    DatatypeEnum,
+    DatatypeMarker,
+    DatatypesRegistry,
+    InterfaceController,
+    Payload,
+    Storage,
+};
+use super::artifact_graph::{
+    ArtifactGraphDtype,
+    Storage as ArtifactGraphStorage,
+};
+use super::interface::{
+    CustomProductionPolicyController,
+    ProducerController,
+};
+use super::partitioning::{
+    Partitioning,
+    PartitioningState,
+};
+use crate::repo::Repository;
+
+
+/// Hook to merge the composite states of each partition of a version into
+/// a single state independent of any partitioning.
+pub trait MergePartitions: ComposableState {
+    fn merge_partitions(
+        states: BTreeMap<PartitionIndex, Self::StateType>,
+    ) -> Result<Self::StateType, Error>;
+}
+
+/// Hook to split a merged state into states for each partition of a
+/// partitioning with state `P`.
+pub trait SplitPartitions<P: Partitioning>: MergePartitions {
+    /// Partitions with no data may be omitted.
+    fn split_partitions(
+        state: Self::StateType,
+        partitioning: &P,
+    ) -> Result<BTreeMap<PartitionIndex, Self::StateType>, Error>;
+}
+
+/// Write the composite state of version `v_idx` as a new state version of
+/// `target_art` partitioned by version `target_part_idx`, whose composite
+/// state is `target_partitioning`.
+///
+/// If `target_art` is the artifact of `v_idx`, the new version is its child.
+/// Only the partitioning dependency is added to the new version, which is
+/// committed before returning its index.
+pub fn repartition<'ag, D, P, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    art_graph: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    v_idx: VersionGraphIndex,
+    target_art: &'ag Artifact,
+    target_part_idx: VersionGraphIndex,
+    target_partitioning: &P,
+) -> Result<VersionGraphIndex, Error>
+        where
+            D: DatatypeMarker + SplitPartitions<P>,
+            <D as DatatypeMarker>::Store: Storage<
+                StateType = <D as ComposableState>::StateType,
+                DeltaType = <D as ComposableState>::DeltaType>,
+            P: Partitioning,
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ProducerController> +
+                InterfaceController<CustomProductionPolicyController>
+{
+    let mut ag_control = ArtifactGraphDtype::store(repo);
+    let mut dtype_control = D::store(repo);
+
+    let partitions = target_partitioning.get_partition_kinds();
+    let target_partitions = ag_control.get_partitioning_partitions(
+        dtypes_registry, repo, ver_graph, target_part_idx)?;
+    if partitions != target_partitions {
+        return Err(Error::Model(ModelError::Other(
+            "Target partitioning state does not match its version".into())));
+    }
+
+    let merged = {
+        let (source_part_idx, _) = ver_graph.get_partitioning(v_idx)
+            .ok_or_else(|| ModelError::Other("Version has no partitioning".into()))?;
+        let source_partition_ids = ag_control.get_partitioning_partitions(
+            dtypes_registry, repo, ver_graph, source_part_idx)?
+            .keys().cloned().collect();
+        let composition_map = ag_control.get_partitioned_composition_map(
+            dtypes_registry, repo, ver_graph, v_idx, source_partition_ids)?;
+
+        let mut states = BTreeMap::new();
+        for (p_idx, composition) in &composition_map {
+            states.insert(*p_idx, dtype_control.get_composite_state(repo, composition)?);
+        }
+
+        D::merge_partitions(states)?
+    };
+    let split = D::split_partitions(merged, target_partitioning)?;
+    if split.keys().any(|p_idx| !partitions.contains_key(p_idx)) {
+        return Err(Error::Model(ModelError::Other(
+            "Split state into partitions absent from target partitioning".into())));
+    }
+
+    // Add the new version and its partitioning dependence.
+    let (target_part_art_idx, _) = art_graph.get_by_id(&ver_graph[target_part_idx].artifact.id)
+        .ok_or_else(|| ModelError::NotFound(ver_graph[target_part_idx].artifact.id.uuid))?;
+    let (target_art_idx, _) = art_graph.get_by_id(&target_art.id)
+        .ok_or_else(|| ModelError::NotFound(target_art.id.uuid))?;
+    let part_rel_idx = art_graph.artifacts.find_edge(target_part_art_idx, target_art_idx)
+        .ok_or_else(|| ModelError::Other("Target artifact is not partitioned by target partitioning".into()))?;
+
+    let new_idx = ver_graph.versions.add_node(Version::new(target_art, RepresentationKind::State));
+    ver_graph.versions.add_edge(
+        target_part_idx,
+        new_idx,
+        VersionRelation::Dependence(&art_graph[part_rel_idx]))?;
+    if ver_graph[v_idx].artifact.id == target_art.id {
+        ver_graph.versions.add_edge(v_idx, new_idx, VersionRelation::Parent)?;
+    }
+
+    ag_control.create_staging_version(repo, ver_graph, new_idx)?;
+
+    let ver_hash = {
+        let mut ver_hash = DefaultHasher::new();
+
+        // Partitions are in ascending order, so the version hash is consistent.
+        for (p_idx, state) in split {
+            let payload = Payload::State(state);
+            let hunk = Hunk {
+                id: D::hash_payload(&payload).into(),
+                version: &ver_graph[new_idx],
+                partition: Partition {
+                    partitioning: &ver_graph[target_part_idx],
+                    index: p_idx,
+                    kind: partitions[&p_idx],
+                },
+                representation: RepresentationKind::State,
+                completion: PartCompletion::Complete,
+                precedence: None,
+            };
+            hunk.id.hash.hash(&mut ver_hash);
+
+            ag_control.create_hunk(repo, &hunk)?;
+            dtype_control.write_hunk(repo, &hunk, &payload)?;
+        }
+
+        ver_hash.finish()
+    };
+    ver_graph[new_idx].id.hash = ver_hash;
+
+    ag_control.commit_version(
+        dtypes_registry,
+        repo,
+        art_graph,
+        ver_graph,
+        new_idx)?;
+
+    Ok(new_idx)
+}
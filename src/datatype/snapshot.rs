@@ -0,0 +1,214 @@
+//! Bounding the length of delta chains by snapshotting partitions.
+//!
+//! Composing a partition's state walks its ancestry until reaching a `State`
+//! hunk, so long chains of `Delta` versions make reads increasingly slow.
+//! A `SnapshotPolicy` for an artifact bounds the number of deltas or bytes
+//! of hunks in a composition. Partitions whose compositions exceed the policy
+//! are snapshotted by adding a `State` hunk with the composite state to the
+//! version alongside its hunk for that partition. The snapshot hunk
+//! supersedes the version's other hunks when composing, but does not change
+//! the version's identity.
+//!
+//! `snapshot_version` applies a policy to a newly committed version.
+//! `snapshot_artifact` applies an artifact's stored policy to all of its
+//! versions in a version graph, for example as a background job.
+
+use std::collections::BTreeSet;
+
+use heraclitus_core::daggy;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    Artifact,
+    Error,
+    Hunk,
+    ModelError,
+    PartCompletion,
+    Partition,
+    PartitionIndex,
+    RepresentationKind,
+    VersionGraph,
+    VersionGraphIndex,
+};
+use super::{
+    ComposableState,
+    DatatypeEnum,
+    DatatypeMarker,
+    DatatypesRegistry,
+    InterfaceController,
+    Payload,
+    Storage,
+};
+use super::artifact_graph::{
+    ArtifactGraphDtype,
+    Storage as ArtifactGraphStorage,
+};
+use super::partitioning::PartitioningState;
+use crate::repo::Repository;
+
+
+/// Per-artifact policy for when to snapshot partitions. A partition is
+/// snapshotted if its composition exceeds any bound.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct SnapshotPolicy {
+    /// Snapshot once a composition has this many non-`State` hunks.
+    pub max_deltas: Option<usize>,
+    /// Snapshot once the payloads of a composition's hunks total this many
+    /// bytes. Payload sizes are measured by their `serde_json` serialization
+    /// rather than their stored size, which varies by backend.
+    pub max_bytes: Option<u64>,
+}
+
+impl SnapshotPolicy {
+    pub fn is_exceeded_by_deltas(&self, deltas: usize) -> bool {
+        self.max_deltas.map_or(false, |max| deltas >= max)
+    }
+
+    pub fn is_exceeded_by_bytes(&self, bytes: u64) -> bool {
+        self.max_bytes.map_or(false, |max| bytes >= max)
+    }
+}
+
+/// Whether a hunk is a snapshot of a partition for which its version has
+/// other hunks.
+pub fn is_snapshot(hunk: &Hunk, version_hunks: &[Hunk]) -> bool {
+    hunk.representation == RepresentationKind::State &&
+        version_hunks.iter().any(|other| other.partition.index == hunk.partition.index &&
+            other.representation != RepresentationKind::State)
+}
+
+/// Snapshot partitions of version `v_idx` whose compositions exceed `policy`.
+/// Only partitions for which the version has a hunk are snapshotted.
+///
+/// Returns the snapshotted partitions.
+pub fn snapshot_version<'ag, D, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    ver_graph: &VersionGraph<'ag>,
+    v_idx: VersionGraphIndex,
+    policy: &SnapshotPolicy,
+) -> Result<BTreeSet<PartitionIndex>, Error>
+        where
+            D: DatatypeMarker + ComposableState,
+            <D as DatatypeMarker>::Store: Storage<
+                StateType = <D as ComposableState>::StateType,
+                DeltaType = <D as ComposableState>::DeltaType>,
+            <D as ComposableState>::StateType: Serialize,
+            <D as ComposableState>::DeltaType: Serialize,
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState>
+{
+    let mut ag_control = ArtifactGraphDtype::store(repo);
+    let mut dtype_control = D::store(repo);
+
+    let version = &ver_graph[v_idx];
+    let (_, partitioning) = ver_graph.get_partitioning(v_idx)
+        .ok_or_else(|| ModelError::MalformedGraph("Version has no partitioning".into()))?;
+
+    let hunks = ag_control.get_hunks(repo, version, partitioning, None)?;
+    let partition_ids: BTreeSet<PartitionIndex> = hunks.iter()
+        .filter(|hunk| hunk.precedence.is_none())
+        .map(|hunk| hunk.partition.index)
+        .collect();
+    if partition_ids.is_empty() {
+        return Ok(BTreeSet::new());
+    }
+
+    let composition_map = ag_control.get_partitioned_composition_map(
+        dtypes_registry, repo, ver_graph, v_idx, partition_ids)?;
+
+    let mut snapshotted = BTreeSet::new();
+    for (p_id, composition) in &composition_map {
+        // Compositions are ordered newest first, so are rooted in their
+        // last hunk.
+        if composition.len() < 2 {
+            continue;
+        }
+
+        let mut exceeded = policy.is_exceeded_by_deltas(composition.len() - 1);
+        if !exceeded && policy.max_bytes.is_some() {
+            let mut bytes = 0;
+            for hunk in composition {
+                let payload = dtype_control.read_hunk(repo, hunk)?;
+                bytes += serde_json::to_vec(&payload)
+                    .map_err(|e| Error::Store(e.to_string()))?
+                    .len() as u64;
+            }
+            exceeded = policy.is_exceeded_by_bytes(bytes);
+        }
+        if !exceeded {
+            continue;
+        }
+
+        let payload = Payload::State(dtype_control.get_composite_state(repo, composition)?);
+        let hunk = Hunk {
+            id: D::hash_payload(&payload).into(),
+            version,
+            partition: Partition {
+                partitioning,
+                index: *p_id,
+                kind: composition[0].partition.kind,
+            },
+            representation: RepresentationKind::State,
+            completion: PartCompletion::Complete,
+            precedence: None,
+        };
+
+        ag_control.create_hunk(repo, &hunk)?;
+        dtype_control.write_hunk(repo, &hunk, &payload)?;
+
+        snapshotted.insert(*p_id);
+    }
+
+    Ok(snapshotted)
+}
+
+/// Snapshot all versions of `artifact` in a version graph according to its
+/// stored snapshot policy, ancestors first so that later compositions are
+/// shortened by earlier snapshots. Does nothing if the artifact has no
+/// policy.
+///
+/// Returns the number of partitions snapshotted.
+pub fn snapshot_artifact<'ag, D, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    ver_graph: &VersionGraph<'ag>,
+    artifact: &Artifact,
+) -> Result<usize, Error>
+        where
+            D: DatatypeMarker + ComposableState,
+            <D as DatatypeMarker>::Store: Storage<
+                StateType = <D as ComposableState>::StateType,
+                DeltaType = <D as ComposableState>::DeltaType>,
+            <D as ComposableState>::StateType: Serialize,
+            <D as ComposableState>::DeltaType: Serialize,
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState>
+{
+    let ag_control = ArtifactGraphDtype::store(repo);
+
+    let policy = match ag_control.get_snapshot_policy(repo, artifact)? {
+        Some(policy) => policy,
+        None => return Ok(0),
+    };
+
+    let to_visit = daggy::petgraph::algo::toposort(ver_graph.versions.graph(), None)
+        .expect("Impossible: version graphs are acyclic");
+
+    let mut count = 0;
+    for v_idx in to_visit {
+        if ver_graph[v_idx].artifact.id != artifact.id {
+            continue;
+        }
+
+        count += snapshot_version::<D, T>(dtypes_registry, repo, ver_graph, v_idx, &policy)?.len();
+    }
+
+    Ok(count)
+}
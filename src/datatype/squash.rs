@@ -0,0 +1,281 @@
+//! Collapsing a chain of versions of an artifact into one version.
+//!
+//! `squash` creates a version with the composite states of the last version
+//! of a chain, whose parent is the chain's parent. The fine-grained chain is
+//! kept, so a `Ref` branch's `HEAD` may continue to track it while a named
+//! revision path of the branch is moved to the squashed version.
+
+use std::collections::{BTreeSet, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use heraclitus_core::petgraph;
+use petgraph::visit::EdgeRef;
+
+use crate::{
+    ArtifactGraph,
+    Composition,
+    CompositionMap,
+    Error,
+    Hunk,
+    Identity,
+    ModelError,
+    PartCompletion,
+    Partition,
+    RepresentationKind,
+    Version,
+    VersionGraph,
+    VersionGraphIndex,
+    VersionRelation,
+};
+use super::{
+    ComposableState,
+    DatatypeEnum,
+    DatatypeMarker,
+    DatatypesRegistry,
+    InterfaceController,
+    Payload,
+    Storage,
+};
+use super::artifact_graph::{
+    ArtifactGraphDtype,
+    Storage as ArtifactGraphStorage,
+};
+use super::interface::{
+    CustomProductionPolicyController,
+    ProducerController,
+};
+use super::partitioning::PartitioningState;
+use super::reference::{
+    BranchRevisionTip,
+    BranchSpecifier,
+    Ref,
+    RevisionPath,
+    Storage as ReferenceStorage,
+};
+use crate::repo::Repository;
+
+
+/// A revision path of a `Ref` branch to move to a squashed version.
+pub struct SquashRevisionPath {
+    /// Version of the ref at the branch's `HEAD`, which depends on the last
+    /// version of the squashed chain.
+    pub ref_version: VersionGraphIndex,
+    pub branch: BranchSpecifier,
+    /// Name of the revision path, which may not be `HEAD`.
+    pub path: String,
+}
+
+/// Squash `versions`, a chain of versions of an artifact each the only parent
+/// of the next, into a new committed version with the same dependencies as
+/// the last version in the chain.
+///
+/// A `State` squashed version has `State` hunks for every populated
+/// partition. A `Delta` squashed version has hunks only for partitions
+/// changed by the chain: the changing hunk itself if only one hunk changed
+/// the partition, otherwise a `State` hunk.
+///
+/// If `revision_path` is given, a new ref version depending on the squashed
+/// version in place of the chain's last version becomes the tip of that
+/// revision path. The branch's `HEAD` is unchanged.
+pub fn squash<'ag, D, T>(
+    dtypes_registry: &DatatypesRegistry<T>,
+    repo: &Repository,
+    art_graph: &'ag ArtifactGraph,
+    ver_graph: &mut VersionGraph<'ag>,
+    versions: &[VersionGraphIndex],
+    representation: RepresentationKind,
+    revision_path: Option<&SquashRevisionPath>,
+) -> Result<VersionGraphIndex, Error>
+        where
+            D: DatatypeMarker + ComposableState,
+            <D as DatatypeMarker>::Store: Storage<
+                StateType = <D as ComposableState>::StateType,
+                DeltaType = <D as ComposableState>::DeltaType>,
+            T: DatatypeEnum,
+            <T as DatatypeEnum>::InterfaceControllerType :
+                InterfaceController<PartitioningState> +
+                InterfaceController<ProducerController> +
+                InterfaceController<CustomProductionPolicyController>
+{
+    let mut ag_control = ArtifactGraphDtype::store(repo);
+    let mut dtype_control = D::store(repo);
+
+    let (first, rest) = versions.split_first()
+        .ok_or_else(|| ModelError::Other("No versions to squash".into()))?;
+    let tip_idx = *versions.last().unwrap_or(first);
+    for (&v_idx, &parent_idx) in rest.iter().zip(versions.iter()) {
+        if ver_graph.get_parents(v_idx) != [parent_idx] {
+            return Err(ModelError::Other("Squashed versions are not a chain".into()).into());
+        }
+    }
+    let base_parents = ver_graph.get_parents(*first);
+    let base_idx = match base_parents[..] {
+        [] => None,
+        [base_idx] => Some(base_idx),
+        _ => return Err(ModelError::Other("Squashed versions must have at most one parent".into()).into()),
+    };
+    match (representation, base_idx) {
+        (RepresentationKind::State, _) | (RepresentationKind::Delta, Some(_)) => {},
+        (RepresentationKind::Delta, None) =>
+            return Err(ModelError::Other("A squash of a root version must be a State version".into()).into()),
+        (RepresentationKind::CumulativeDelta, _) =>
+            return Err(ModelError::Other("Squashed versions must be State or Delta versions".into()).into()),
+    }
+    let revision_path = match revision_path {
+        Some(rp) => match rp.path.parse::<RevisionPath>() {
+            Ok(RevisionPath::Head) =>
+                return Err(ModelError::Other("Squash can not move a branch's HEAD".into()).into()),
+            Ok(path) => Some((rp, path)),
+            Err(never) => never,
+        },
+        None => None,
+    };
+
+    let (part_idx, _) = ver_graph.get_partitioning(tip_idx)
+        .ok_or_else(|| ModelError::MalformedGraph("Version has no partitioning".into()))?;
+    let partitions = ag_control.get_partitioning_partitions(dtypes_registry, repo, ver_graph, part_idx)?;
+
+    let mut hunk_payloads = vec![];
+    {
+        let ver_graph: &VersionGraph<'ag> = ver_graph;
+        let partition_ids: BTreeSet<_> = partitions.keys().cloned().collect();
+
+        let tip_map = ag_control.get_partitioned_composition_map(
+            dtypes_registry, repo, ver_graph, tip_idx, partition_ids.clone())?;
+        let base_map = match base_idx {
+            Some(base_idx) => ag_control.get_partitioned_composition_map(
+                dtypes_registry, repo, ver_graph, base_idx, partition_ids.clone())?,
+            None => CompositionMap::new(),
+        };
+
+        let empty = Composition::new();
+        let hunk_ids = |composition: &[Hunk]| composition.iter()
+            .map(|hunk| hunk.id)
+            .collect::<Vec<Identity>>();
+
+        // Composition maps are ordered by partition, so the version hash is
+        // consistent.
+        for (p_id, composition) in &tip_map {
+            let base_composition = base_map.get(p_id).unwrap_or(&empty);
+            if representation == RepresentationKind::Delta {
+                let base_ids = hunk_ids(base_composition);
+                if hunk_ids(composition) == base_ids {
+                    continue;
+                }
+                // Compositions are ordered newest first. Snapshots may root
+                // the tip's composition in a different hunk than the base's,
+                // in which case a `State` hunk is written.
+                if !composition.is_empty() && hunk_ids(&composition[1..]) == base_ids {
+                    let hunk = &composition[0];
+                    hunk_payloads.push((
+                        *p_id,
+                        hunk.id.hash,
+                        hunk.representation,
+                        dtype_control.read_hunk(repo, hunk)?));
+                    continue;
+                }
+            }
+
+            let payload = Payload::State(dtype_control.get_composite_state(repo, composition)?);
+            hunk_payloads.push((*p_id, D::hash_payload(&payload), RepresentationKind::State, payload));
+        }
+    }
+
+    let squash_idx = {
+        let artifact = ver_graph[tip_idx].artifact;
+        let squash_idx = ver_graph.versions.add_node(Version::new(artifact, representation));
+
+        let dependencies: Vec<_> = ver_graph.versions.graph()
+            .edges_directed(tip_idx, petgraph::Direction::Incoming)
+            .filter(|e| match e.weight() {
+                VersionRelation::Dependence(_) => true,
+                _ => false,
+            })
+            .map(|e| (e.source(), e.weight().clone()))
+            .collect();
+        for (source, weight) in dependencies {
+            ver_graph.versions.add_edge(source, squash_idx, weight)?;
+        }
+        if let Some(base_idx) = base_idx {
+            ver_graph.versions.add_edge(base_idx, squash_idx, VersionRelation::Parent)?;
+        }
+
+        squash_idx
+    };
+
+    ag_control.create_staging_version(repo, ver_graph, squash_idx)?;
+
+    let ver_hash = {
+        let mut ver_hash = DefaultHasher::new();
+
+        for (p_id, hash, hunk_representation, payload) in &hunk_payloads {
+            let hunk = Hunk {
+                id: (*hash).into(),
+                version: &ver_graph[squash_idx],
+                partition: Partition {
+                    partitioning: &ver_graph[part_idx],
+                    index: *p_id,
+                    kind: partitions[p_id],
+                },
+                representation: *hunk_representation,
+                completion: PartCompletion::Complete,
+                precedence: None,
+            };
+            hunk.id.hash.hash(&mut ver_hash);
+
+            ag_control.create_hunk(repo, &hunk)?;
+            dtype_control.write_hunk(repo, &hunk, payload)?;
+        }
+
+        ver_hash.finish()
+    };
+    ver_graph[squash_idx].id.hash = ver_hash;
+
+    ag_control.commit_version(
+        dtypes_registry,
+        repo,
+        art_graph,
+        ver_graph,
+        squash_idx)?;
+
+    if let Some((rp, path)) = revision_path {
+        let ref_art = ver_graph[rp.ref_version].artifact;
+        let ref_idx = ver_graph.versions.add_node(Version::new(ref_art, RepresentationKind::State));
+
+        let dependencies: Vec<_> = ver_graph.versions.graph()
+            .edges_directed(rp.ref_version, petgraph::Direction::Incoming)
+            .filter(|e| match e.weight() {
+                VersionRelation::Dependence(_) => true,
+                _ => false,
+            })
+            .map(|e| (if e.source() == tip_idx {squash_idx} else {e.source()}, e.weight().clone()))
+            .collect();
+        if !dependencies.iter().any(|(source, _)| *source == squash_idx) {
+            return Err(ModelError::Other("Ref version does not depend on the squashed versions".into()).into());
+        }
+        for (source, weight) in dependencies {
+            ver_graph.versions.add_edge(source, ref_idx, weight)?;
+        }
+
+        ag_control.create_staging_version(repo, ver_graph, ref_idx)?;
+        ag_control.commit_version(
+            dtypes_registry,
+            repo,
+            art_graph,
+            ver_graph,
+            ref_idx)?;
+
+        let mut ref_control = Ref::store(repo);
+        let mut tips = HashMap::new();
+        tips.insert(
+            BranchRevisionTip {
+                name: rp.branch.clone(),
+                revision: path,
+            },
+            ver_graph[ref_idx].id.uuid);
+        ref_control.set_branch_revision_tips(repo, ref_art, &tips)?;
+    }
+
+    Ok(squash_idx)
+}
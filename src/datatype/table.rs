@@ -0,0 +1,415 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use heraclitus_macros::{
+    DatatypeMarker,
+    stored_datatype_controller,
+};
+
+use crate::{
+    Error,
+    ModelError,
+    PartitionIndex,
+    RepresentationKind,
+};
+use super::{
+    DatatypeMeta,
+    InterfaceController,
+    Reflection,
+};
+use super::artifact_graph::ArtifactMeta;
+use super::partitioning::Partitioning;
+use super::partitioning::hash::KeyPartitioning;
+use super::repartition::{
+    MergePartitions,
+    SplitPartitions,
+};
+
+
+/// Tables of keyed rows conforming to a declared column schema.
+#[derive(Default, DatatypeMarker)]
+pub struct TableDatatype;
+
+impl DatatypeMeta for TableDatatype {
+    const NAME: &'static str = "Table";
+    const VERSION: u64 = 1;
+}
+
+impl<T: InterfaceController<ArtifactMeta>> super::Model<T> for TableDatatype {
+    fn reflection(&self) -> Reflection<T> {
+        Reflection {
+            representations: enumset::enum_set!(
+                        RepresentationKind::State |
+                        RepresentationKind::Delta |
+                    ),
+            implements: vec![
+                <T as InterfaceController<ArtifactMeta>>::VARIANT,
+            ],
+            dependencies: vec![],
+        }
+    }
+
+    datatype_controllers!(TableDatatype, (ArtifactMeta));
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum ColumnType {
+    Boolean,
+    Integer,
+    Float,
+    Text,
+    Bytes,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct Column {
+    pub name: String,
+    pub column_type: ColumnType,
+    pub nullable: bool,
+}
+
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct TableSchema {
+    pub columns: Vec<Column>,
+}
+
+impl TableSchema {
+    /// Check that columns have distinct, non-empty names.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut names = BTreeSet::new();
+        for column in &self.columns {
+            if column.name.is_empty() {
+                return Err(ModelError::Other("Table column name is empty".into()).into());
+            }
+            if !names.insert(&column.name) {
+                return Err(ModelError::Other(format!("Table column {} is duplicated", column.name)).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a row has a value of the correct type for each column.
+    pub fn is_valid_row(&self, row: &Row) -> bool {
+        row.len() == self.columns.len() &&
+        self.columns.iter().zip(row.iter()).all(|(column, value)| match value.column_type() {
+            Some(column_type) => column_type == column.column_type,
+            None => column.nullable,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum CellValue {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl CellValue {
+    /// The column type this value conforms to, or `None` for nulls.
+    pub fn column_type(&self) -> Option<ColumnType> {
+        match self {
+            CellValue::Null => None,
+            CellValue::Boolean(_) => Some(ColumnType::Boolean),
+            CellValue::Integer(_) => Some(ColumnType::Integer),
+            CellValue::Float(_) => Some(ColumnType::Float),
+            CellValue::Text(_) => Some(ColumnType::Text),
+            CellValue::Bytes(_) => Some(ColumnType::Bytes),
+        }
+    }
+}
+
+impl Hash for CellValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            CellValue::Null => {},
+            CellValue::Boolean(v) => v.hash(state),
+            CellValue::Integer(v) => v.hash(state),
+            CellValue::Float(v) => v.to_bits().hash(state),
+            CellValue::Text(v) => v.hash(state),
+            CellValue::Bytes(v) => v.hash(state),
+        }
+    }
+}
+
+pub type RowKey = String;
+/// Cell values, ordered as the columns of the table schema.
+pub type Row = Vec<CellValue>;
+
+#[derive(Clone, Debug, Default, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct TableState {
+    pub schema: TableSchema,
+    pub rows: BTreeMap<RowKey, Row>,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub enum RowOperation {
+    Insert(RowKey, Row),
+    Update(RowKey, Row),
+    Delete(RowKey),
+}
+
+/// Row operations applied in order. Deltas carry the schema of their rows,
+/// which must match the schema of the state they apply to; schema changes
+/// require a new state.
+#[derive(Clone, Debug, Default, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct TableDelta {
+    pub schema: TableSchema,
+    pub operations: Vec<RowOperation>,
+}
+
+impl crate::datatype::ComposableState for TableDatatype {
+    type StateType = TableState;
+    type DeltaType = TableDelta;
+
+    fn compose_state(
+        state: &mut Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        Self::validate_delta(state, delta)?;
+
+        for operation in &delta.operations {
+            match operation {
+                RowOperation::Insert(key, row) |
+                RowOperation::Update(key, row) => {
+                    state.rows.insert(key.clone(), row.clone());
+                },
+                RowOperation::Delete(key) => {
+                    state.rows.remove(key);
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_delta(
+        state: &Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        if state.schema != delta.schema {
+            return Err(ModelError::Other("Table delta schema does not match state".into()).into());
+        }
+
+        // Keys present after each operation, where they differ from `state`.
+        let mut present = BTreeMap::new();
+        for operation in &delta.operations {
+            match operation {
+                RowOperation::Insert(key, row) |
+                RowOperation::Update(key, row) => {
+                    if !delta.schema.is_valid_row(row) {
+                        return Err(ModelError::Other(
+                            format!("Row {} does not conform to table schema", key)).into());
+                    }
+                    if let RowOperation::Update(..) = operation {
+                        let exists = present.get(key).cloned()
+                            .unwrap_or_else(|| state.rows.contains_key(key));
+                        if !exists {
+                            return Err(ModelError::Other(
+                                format!("Table delta updates missing row {}", key)).into());
+                        }
+                    }
+                    present.insert(key, true);
+                },
+                RowOperation::Delete(key) => {
+                    present.insert(key, false);
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[stored_datatype_controller(TableDatatype)]
+pub trait Storage: super::Storage {}
+
+
+impl RowOperation {
+    pub fn key(&self) -> &RowKey {
+        match self {
+            RowOperation::Insert(key, _) |
+            RowOperation::Update(key, _) |
+            RowOperation::Delete(key) => key,
+        }
+    }
+}
+
+/// Split a state into states for each partition of a partitioning by row key.
+/// Every partition is included, even if empty.
+pub fn partition_state(
+    state: TableState,
+    partitioning: &dyn KeyPartitioning,
+) -> Result<BTreeMap<PartitionIndex, TableState>, Error> {
+    let mut partitioned: BTreeMap<_, _> = partitioning.get_partition_ids().iter()
+        .map(|&p_idx| (p_idx, TableState {schema: state.schema.clone(), rows: BTreeMap::new()}))
+        .collect();
+
+    for (key, row) in state.rows {
+        let p_idx = partitioning.partition_for_key(key.as_bytes())?;
+        partitioned.get_mut(&p_idx)
+            .ok_or_else(|| ModelError::Other(format!("Row assigned to unknown partition {}", p_idx)))?
+            .rows.insert(key, row);
+    }
+
+    Ok(partitioned)
+}
+
+/// Split a delta into deltas for each partition of a partitioning by row key,
+/// preserving the order of operations. Only partitions with changed rows are
+/// included.
+pub fn partition_delta(
+    delta: TableDelta,
+    partitioning: &dyn KeyPartitioning,
+) -> Result<BTreeMap<PartitionIndex, TableDelta>, Error> {
+    let mut partitioned = BTreeMap::new();
+
+    for operation in delta.operations {
+        partitioned.entry(partitioning.partition_for_key(operation.key().as_bytes())?)
+            .or_insert_with(|| TableDelta {schema: delta.schema.clone(), operations: vec![]})
+            .operations.push(operation);
+    }
+
+    Ok(partitioned)
+}
+
+impl MergePartitions for TableDatatype {
+    fn merge_partitions(
+        states: BTreeMap<PartitionIndex, TableState>,
+    ) -> Result<TableState, Error> {
+        let mut states = states.into_iter().map(|(_, state)| state);
+        let mut merged = states.next()
+            .ok_or_else(|| ModelError::Other("Cannot merge a table with no partitions".into()))?;
+
+        for state in states {
+            if state.schema != merged.schema {
+                return Err(ModelError::Other("Table partitions have differing schemas".into()).into());
+            }
+            merged.rows.extend(state.rows);
+        }
+
+        Ok(merged)
+    }
+}
+
+impl<P: KeyPartitioning> SplitPartitions<P> for TableDatatype {
+    fn split_partitions(
+        state: TableState,
+        partitioning: &P,
+    ) -> Result<BTreeMap<PartitionIndex, TableState>, Error> {
+        partition_state(state, partitioning)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use maplit::btreemap;
+
+    use crate::datatype::ComposableState;
+
+    #[test]
+    fn test_compose_row_operations() {
+        let schema = TableSchema {
+            columns: vec![
+                Column {name: "name".into(), column_type: ColumnType::Text, nullable: false},
+                Column {name: "score".into(), column_type: ColumnType::Float, nullable: true},
+            ],
+        };
+        let mut state = TableState {
+            schema: schema.clone(),
+            rows: btreemap!{
+                "a".into() => vec![CellValue::Text("A".into()), CellValue::Float(1.0)],
+                "b".into() => vec![CellValue::Text("B".into()), CellValue::Null],
+            },
+        };
+        let delta = TableDelta {
+            schema: schema.clone(),
+            operations: vec![
+                RowOperation::Insert("c".into(), vec![CellValue::Text("C".into()), CellValue::Float(3.0)]),
+                RowOperation::Update("b".into(), vec![CellValue::Text("B".into()), CellValue::Float(2.0)]),
+                RowOperation::Delete("a".into()),
+            ],
+        };
+        assert!(delta.operations.iter().all(|op| match op {
+            RowOperation::Insert(_, row) | RowOperation::Update(_, row) => schema.is_valid_row(row),
+            RowOperation::Delete(_) => true,
+        }));
+        assert!(!schema.is_valid_row(&vec![CellValue::Null, CellValue::Null]));
+
+        assert!(TableDatatype::validate_delta(&state, &delta).is_ok());
+        TableDatatype::compose_state(&mut state, &delta).unwrap();
+
+        assert_eq!(state.rows, btreemap!{
+            "b".into() => vec![CellValue::Text("B".into()), CellValue::Float(2.0)],
+            "c".into() => vec![CellValue::Text("C".into()), CellValue::Float(3.0)],
+        });
+    }
+
+    #[test]
+    fn test_validate_delta() {
+        let schema = TableSchema {
+            columns: vec![
+                Column {name: "name".into(), column_type: ColumnType::Text, nullable: false},
+            ],
+        };
+        let state = TableState {
+            schema: schema.clone(),
+            rows: btreemap!{
+                "a".into() => vec![CellValue::Text("A".into())],
+            },
+        };
+        let delta = |operations| TableDelta {schema: schema.clone(), operations};
+
+        assert!(TableDatatype::validate_delta(&state, &delta(vec![
+            RowOperation::Insert("b".into(), vec![CellValue::Text("B".into())]),
+            RowOperation::Update("b".into(), vec![CellValue::Text("C".into())]),
+        ])).is_ok());
+        assert!(TableDatatype::validate_delta(&state, &delta(vec![
+            RowOperation::Update("b".into(), vec![CellValue::Text("B".into())]),
+        ])).is_err(), "Update of missing row was accepted");
+        assert!(TableDatatype::validate_delta(&state, &delta(vec![
+            RowOperation::Delete("a".into()),
+            RowOperation::Update("a".into(), vec![CellValue::Text("A".into())]),
+        ])).is_err(), "Update of deleted row was accepted");
+
+        let changed_schema = TableDelta {
+            schema: TableSchema {columns: vec![]},
+            operations: vec![],
+        };
+        assert!(TableDatatype::validate_delta(&state, &changed_schema).is_err(),
+            "Delta with a different schema was accepted");
+        assert!(TableDatatype::compose_state(&mut state.clone(), &changed_schema).is_err(),
+            "Delta with a different schema was composed");
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let column = |name: &str| Column {name: name.into(), column_type: ColumnType::Text, nullable: true};
+
+        assert!(TableSchema {columns: vec![column("a"), column("b")]}.validate().is_ok());
+        assert!(TableSchema {columns: vec![column("a"), column("a")]}.validate().is_err(),
+            "Schema with duplicate columns was accepted");
+        assert!(TableSchema {columns: vec![column("")]}.validate().is_err(),
+            "Schema with an unnamed column was accepted");
+    }
+}
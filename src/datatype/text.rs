@@ -0,0 +1,234 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use heraclitus_macros::{
+    DatatypeMarker,
+    stored_datatype_controller,
+};
+
+use crate::{
+    Error,
+    ModelError,
+    RepresentationKind,
+};
+use super::{
+    DatatypeMeta,
+    InterfaceControllerEnum,
+    Reflection,
+};
+
+
+/// UTF-8 text whose deltas are line-based edit scripts.
+#[derive(Default, DatatypeMarker)]
+pub struct TextDatatype;
+
+impl DatatypeMeta for TextDatatype {
+    const NAME: &'static str = "Text";
+    const VERSION: u64 = 1;
+}
+
+impl<T: InterfaceControllerEnum> super::Model<T> for TextDatatype {
+    fn reflection(&self) -> Reflection<T> {
+        Reflection {
+            representations: enumset::enum_set!(
+                        RepresentationKind::State |
+                        RepresentationKind::Delta |
+                    ),
+            implements: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    datatype_controllers!(TextDatatype, ());
+}
+
+/// A contiguous replacement of lines, analogous to a hunk in a unified diff.
+///
+/// Lines retain their terminating newline, if any, so that composition
+/// reproduces text byte-for-byte.
+#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Deserialize, Serialize)]
+pub struct LineHunk {
+    /// Index of the first line of the prior state this hunk replaces.
+    pub start: usize,
+    /// Number of lines of the prior state this hunk removes.
+    pub removed: usize,
+    /// Lines inserted in place of the removed lines.
+    pub inserted: Vec<String>,
+}
+
+pub type StateType = String;
+/// An edit script of non-overlapping line hunks, ordered by `start`. All
+/// line indices refer to the prior state.
+pub type DeltaType = Vec<LineHunk>;
+
+impl crate::datatype::ComposableState for TextDatatype {
+    type StateType = crate::datatype::text::StateType;
+    type DeltaType = crate::datatype::text::DeltaType;
+
+    fn compose_state(
+        state: &mut Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        Self::validate_delta(state, delta)?;
+
+        let composed = {
+            let lines = split_lines(state);
+            let mut composed = String::with_capacity(state.len());
+            let mut cursor = 0;
+
+            for hunk in delta {
+                lines[cursor..hunk.start].iter().for_each(|line| composed.push_str(line));
+                hunk.inserted.iter().for_each(|line| composed.push_str(line));
+                cursor = hunk.start + hunk.removed;
+            }
+            lines[cursor..].iter().for_each(|line| composed.push_str(line));
+
+            composed
+        };
+
+        *state = composed;
+
+        Ok(())
+    }
+
+    /// Hunks must be ordered, non-overlapping and within the lines of the
+    /// state.
+    fn validate_delta(
+        state: &Self::StateType,
+        delta: &Self::DeltaType,
+    ) -> Result<(), Error> {
+        let num_lines = split_lines(state).len();
+        let mut cursor = 0;
+
+        for hunk in delta {
+            if hunk.start < cursor {
+                return Err(ModelError::Other(format!(
+                    "Line hunk at {} overlaps or precedes the previous hunk", hunk.start)).into());
+            }
+            cursor = match hunk.start.checked_add(hunk.removed) {
+                Some(end) if end <= num_lines => end,
+                _ => return Err(ModelError::Other(format!(
+                    "Line hunk removing {} lines at {} is outside text of {} lines",
+                    hunk.removed, hunk.start, num_lines)).into()),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// Split text into lines, each retaining its terminating newline if any.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+
+    for (idx, _) in text.match_indices('\n') {
+        lines.push(&text[start..=idx]);
+        start = idx + 1;
+    }
+
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+
+    lines
+}
+
+/// Compute a line edit script transforming `old` into `new`.
+///
+/// This uses a longest common subsequence of lines, so is quadratic in the
+/// number of lines.
+pub fn diff(old: &str, new: &str) -> DeltaType {
+    let old = split_lines(old);
+    let new = split_lines(new);
+    let (n, m) = (old.len(), new.len());
+
+    // `lcs[i][j]` is the length of the LCS of `old[i..]` and `new[j..]`.
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut delta = Vec::new();
+    let mut current: Option<LineHunk> = None;
+    let (mut i, mut j) = (0, 0);
+
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            delta.extend(current.take());
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            current.get_or_insert_with(|| LineHunk {start: i, removed: 0, inserted: vec![]})
+                .inserted.push(new[j].to_owned());
+            j += 1;
+        } else {
+            current.get_or_insert_with(|| LineHunk {start: i, removed: 0, inserted: vec![]})
+                .removed += 1;
+            i += 1;
+        }
+    }
+    delta.extend(current);
+
+    delta
+}
+
+#[stored_datatype_controller(TextDatatype)]
+pub trait Storage: super::Storage {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::datatype::ComposableState;
+
+    #[test]
+    fn test_diff_composes() {
+        let cases = [
+            ("", "a\nb\n"),
+            ("a\nb\nc\n", ""),
+            ("a\nb\nc\n", "a\nc\n"),
+            ("a\nb\nc\n", "a\nB\nc\nd"),
+            ("a\nb", "a\nb\n"),
+            ("x\na\ny\nb\n", "a\nz\nb\nw\n"),
+        ];
+
+        for &(old, new) in &cases {
+            let delta = diff(old, new);
+            let mut state = old.to_owned();
+            TextDatatype::compose_state(&mut state, &delta).unwrap();
+            assert_eq!(state, new);
+        }
+
+        assert!(diff("a\nb\n", "a\nb\n").is_empty());
+    }
+
+    #[test]
+    fn test_validate_delta() {
+        let state: StateType = "a\nb\nc\n".into();
+        let hunk = |start, removed| LineHunk {start, removed, inserted: vec!["x\n".into()]};
+
+        assert!(TextDatatype::validate_delta(&state, &vec![hunk(0, 1), hunk(1, 0), hunk(2, 1)]).is_ok());
+        assert!(TextDatatype::validate_delta(&state, &vec![hunk(3, 0)]).is_ok());
+        assert!(TextDatatype::validate_delta(&state, &vec![hunk(0, 2), hunk(1, 1)]).is_err(),
+            "Overlapping hunks were accepted");
+        assert!(TextDatatype::validate_delta(&state, &vec![hunk(2, 1), hunk(0, 1)]).is_err(),
+            "Out of order hunks were accepted");
+        assert!(TextDatatype::validate_delta(&state, &vec![hunk(2, 2)]).is_err(),
+            "Hunk past the end of the text was accepted");
+        assert!(TextDatatype::validate_delta(&state, &vec![hunk(1, usize::max_value())]).is_err(),
+            "Hunk with an overflowing end was accepted");
+        assert!(TextDatatype::compose_state(&mut state.clone(), &vec![hunk(4, 0)]).is_err(),
+            "Hunk past the end of the text was composed");
+    }
+}
@@ -148,10 +148,14 @@ impl<RC: RepoController> CustomProductionPolicyController for TrackingBranchProd
         prod_a_idx: ArtifactGraphIndex,
     ) -> Result<Box<dyn ProductionPolicy>, Error> {
         // Get output ref artifact.
-        let ref_art_idx = art_graph.get_related_artifacts(
+        let ref_art_idx = *art_graph.get_related_artifacts(
             prod_a_idx,
             &ArtifactRelation::ProducedFrom("output".into()),
-            Direction::Outgoing)[0];
+            Direction::Outgoing).get(0)
+            .ok_or_else(|| ModelError::IncompatibleProducer {
+                producer: art_graph[prod_a_idx].id.uuid,
+                reason: "Tracking branch producer has no output ref".into(),
+            })?;
         let ref_art = &art_graph[ref_art_idx];
 
         // Get ref model controller.
@@ -193,7 +197,9 @@ impl<RC: RepoController> ProducerController for TrackingBranchProducerBackend<RC
         ver_graph: &mut VersionGraph<'ag>,
         v_idx: VersionGraphIndex,
     ) -> Result<ProductionOutput, Error> {
-        let prod_a_idx = art_graph.get_by_id(&ver_graph[v_idx].artifact.id).expect("TODO").0;
+        let prod_a_idx = art_graph.get_by_id(&ver_graph[v_idx].artifact.id)
+            .ok_or_else(|| ModelError::UnknownArtifact(ver_graph[v_idx].artifact.id.uuid))?
+            .0;
 
         // Find output relation and artifact.
         let ref_art_relation_needle = ArtifactRelation::ProducedFrom("output".into());
@@ -201,7 +207,10 @@ impl<RC: RepoController> ProducerController for TrackingBranchProducerBackend<RC
             .edges_directed(prod_a_idx, Direction::Outgoing)
             .find(|e| e.weight() == &ref_art_relation_needle)
             .map(|e| (e.weight(), e.target()))
-            .expect("TODO3");
+            .ok_or_else(|| ModelError::IncompatibleProducer {
+                producer: art_graph[prod_a_idx].id.uuid,
+                reason: "Tracking branch producer has no output ref".into(),
+            })?;
         let ref_art = &art_graph[ref_art_idx];
 
         // Create output ref version, which should have same dependencies as
@@ -226,7 +235,8 @@ impl<RC: RepoController> ProducerController for TrackingBranchProducerBackend<RC
             let parent_ref_idx = *ver_graph.get_related_versions(
                 parent_ver_idx,
                 &VersionRelation::Dependence(ref_art_relation),
-                Direction::Outgoing).get(0).expect("TODO: parent should have output");
+                Direction::Outgoing).get(0)
+                .ok_or_else(|| ModelError::MalformedGraph("Parent producer version has no output".into()))?;
             ver_graph.versions.add_edge(parent_ref_idx, ref_ver_idx,
                 VersionRelation::Parent)?;
             parent_ref_ver_idxs.insert(parent_ref_idx);
@@ -240,10 +250,14 @@ impl<RC: RepoController> ProducerController for TrackingBranchProducerBackend<RC
             Direction::Incoming);
         for tracked_ver_idx in tracked_vers {
             let tracked_ver_art_idx = art_graph.get_by_id(&ver_graph[tracked_ver_idx].artifact.id)
-                .expect("TODO: unable to find tracked ver art").0;
+                .ok_or_else(|| ModelError::UnknownArtifact(ver_graph[tracked_ver_idx].artifact.id.uuid))?
+                .0;
             let tracked_ref_rel = &art_graph[
                 art_graph.artifacts.find_edge(tracked_ver_art_idx, ref_art_idx)
-                    .expect("TODO")
+                    .ok_or_else(|| ModelError::IncompatibleProducer {
+                        producer: art_graph[prod_a_idx].id.uuid,
+                        reason: "Tracked artifact is not related to the output ref".into(),
+                    })?
             ];
             ver_graph.versions.add_edge(tracked_ver_idx, ref_ver_idx,
                 VersionRelation::Dependence(tracked_ref_rel))?;
@@ -253,7 +267,7 @@ impl<RC: RepoController> ProducerController for TrackingBranchProducerBackend<RC
         ag_control.create_staging_version(
             repo,
             ver_graph,
-            ref_ver_idx).unwrap();
+            ref_ver_idx)?;
 
         // TODO: ref hash
 
@@ -39,7 +39,7 @@ pub use heraclitus_core::*;
 extern crate self as heraclitus;
 
 
-use std::collections::{BTreeMap};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::ops::{Index, IndexMut};
@@ -54,7 +54,13 @@ use postgres_derive::{ToSql, FromSql};
 use serde_derive::{Serialize, Deserialize};
 use uuid::Uuid;
 
-use heraclitus_core::datatype::{DatatypeEnum, DatatypesRegistry};
+use heraclitus_core::datatype::{
+    DatatypeEnum,
+    DatatypesRegistry,
+    DependencyDescription,
+    InterfaceController,
+};
+use crate::datatype::interface::ProducerController;
 use crate::datatype::artifact_graph::{
     ArtifactDescription,
     ArtifactGraphDescription,
@@ -142,10 +148,10 @@ impl ArtifactGraph {
         desc: &ArtifactGraphDescription,
         dtypes_registry: &DatatypesRegistry<T>,
         uuid: Option<Uuid>,
-    ) -> (ArtifactGraph, ArtifactIndexMap) {
+    ) -> Result<(ArtifactGraph, ArtifactIndexMap), Error> {
 
         let to_visit = daggy::petgraph::algo::toposort(desc.artifacts.graph(), None)
-            .expect("TODO: not a DAG");
+            .map_err(|_| ModelError::Cycle)?;
 
         let mut ag = ArtifactGraph {
             id: Identity {
@@ -162,28 +168,29 @@ impl ArtifactGraph {
                 desc,
                 dtypes_registry,
                 &mut idx_map,
-                node_idx);
+                node_idx)?;
             ag.artifacts[idx].id.hash.hash(&mut ag_hash);
         }
 
         ag.id.hash = ag_hash.finish();
 
-        (ag, idx_map)
+        Ok((ag, idx_map))
     }
 
     pub fn apply_delta<T: DatatypeEnum>(
         &mut self,
         delta: &crate::datatype::artifact_graph::ArtifactGraphDelta,
         dtypes_registry: &DatatypesRegistry<T>,
-    ) -> ArtifactIndexMap {
+    ) -> Result<ArtifactIndexMap, Error> {
 
         for art_uuid in delta.removals() {
-            let (found_idx, _) = self.get_by_uuid(art_uuid).expect("TODO");
+            let (found_idx, _) = self.get_by_uuid(art_uuid)
+                .ok_or_else(|| ModelError::UnknownArtifact(*art_uuid))?;
             self.artifacts.remove_node(found_idx);
         }
 
         let to_visit = daggy::petgraph::algo::toposort(delta.additions().artifacts.graph(), None)
-            .expect("TODO: not a DAG");
+            .map_err(|_| ModelError::Cycle)?;
 
         let mut idx_map = ArtifactIndexMap::new();
 
@@ -192,12 +199,13 @@ impl ArtifactGraph {
                 delta.additions(),
                 dtypes_registry,
                 &mut idx_map,
-                node_idx);
+                node_idx)?;
         }
 
-        self.id.hash = self.hash_current_state().expect("TODO: existing art hash is wrong");
+        self.id.hash = self.hash_current_state()
+            .ok_or_else(|| ModelError::MalformedGraph("Existing artifact hash is wrong".into()))?;
 
-        idx_map
+        Ok(idx_map)
     }
 
     fn add_description_node<T: DatatypeEnum>(
@@ -206,25 +214,29 @@ impl ArtifactGraph {
         dtypes_registry: &DatatypesRegistry<T>,
         idx_map: &mut ArtifactIndexMap,
         node_idx: ArtifactGraphIndex,
-    ) -> ArtifactGraphIndex {
+    ) -> Result<ArtifactGraphIndex, Error> {
         let mut s = DefaultHasher::new();
 
+        // Descriptions are visited in topological order, so parents must
+        // already be mapped.
+        let unmapped = || ModelError::MalformedGraph("Artifact parent is not yet added".into());
+
         // TODO: replace with petgraph neighbors
         // Order hashing based on hash, not ID, so that artifact content
         // hashes are ID-independent.
         let mut sorted_parent_hashes = desc.artifacts.parents(node_idx)
             .iter(&desc.artifacts)
             .map(|(_, p_idx)| {
-                let new_p_idx = idx_map.get(&p_idx).expect("Graph is malformed.");
-                self.artifacts.node_weight(*new_p_idx).expect("Graph is malformed.").id.hash
+                let new_p_idx = idx_map.get(&p_idx).ok_or_else(unmapped)?;
+                Ok(self.artifacts[*new_p_idx].id.hash)
             })
-            .collect::<Vec<HashType>>();
+            .collect::<Result<Vec<HashType>, ModelError>>()?;
         sorted_parent_hashes.sort();
         for hash in &sorted_parent_hashes {
             hash.hash(&mut s);
         }
 
-        let a_desc = desc.artifacts.node_weight(node_idx).expect("Graph is malformed.");
+        let a_desc = &desc.artifacts[node_idx];
         let new_idx = match a_desc {
             ArtifactDescription::New { id, name, self_partitioning, dtype } => {
                 let id_new: Identity = id.map(|i| i.into()).unwrap_or_else(|| 0.into());
@@ -233,14 +245,20 @@ impl ArtifactGraph {
                         id: id_new,
                         name: name.clone(),
                         self_partitioning: *self_partitioning,
-                        dtype_uuid: dtypes_registry.get_datatype(&*dtype).expect("Unknown datatype.")
+                        dtype_uuid: dtypes_registry.get_datatype(&*dtype)
+                            .ok_or_else(|| ModelError::UnknownDatatype(dtype.clone()))?
                             .id().uuid,
                     };
                     art.hash(&mut s);
                     let new_hash = s.finish();
                     if let Some(PartialIdentity {hash: Some(expected_hash), ..}) = id {
-                        // TODO: hash verification should return an error
-                        assert_eq!(*expected_hash, new_hash, "ID mismatch for artifact: {:?}", art);
+                        if *expected_hash != new_hash {
+                            return Err(Error::Model(ModelError::HashMismatch {
+                                uuid: art.id.uuid,
+                                expected: *expected_hash,
+                                found: new_hash,
+                            }));
+                        }
                     }
                     art.id.hash = new_hash;
                     art
@@ -249,18 +267,17 @@ impl ArtifactGraph {
                 self.artifacts.add_node(artifact)
             },
             ArtifactDescription::Existing(uuid) => {
-                self.get_by_uuid(uuid).expect("TODO").0
+                self.get_by_uuid(uuid).ok_or_else(|| ModelError::UnknownArtifact(*uuid))?.0
             },
         };
         idx_map.insert(node_idx, new_idx);
 
         for (e_idx, p_idx) in desc.artifacts.parents(node_idx).iter(&desc.artifacts) {
-            let edge = desc.artifacts.edge_weight(e_idx).expect("Graph is malformed.").clone();
-            self.artifacts.add_edge(*idx_map.get(&p_idx).expect("Graph is malformed."), new_idx, edge)
-                     .expect("Graph is malformed.");
+            let edge = desc.artifacts[e_idx].clone();
+            self.artifacts.add_edge(*idx_map.get(&p_idx).ok_or_else(unmapped)?, new_idx, edge)?;
         }
 
-        new_idx
+        Ok(new_idx)
     }
 
     pub fn as_description<T: DatatypeEnum>(
@@ -283,17 +300,157 @@ impl ArtifactGraph {
             let source = idx_map[&edge.source()];
             let target = idx_map[&edge.target()];
 
-            desc.artifacts.add_edge(source, target, edge.weight.clone()).expect("TODO");
+            desc.artifacts.add_edge(source, target, edge.weight.clone())
+                .expect("Impossible: artifact graphs are acyclic");
         }
 
         desc
     }
 
+    /// Check the dependency relations of every artifact against those
+    /// declared by its datatype, and the outputs of every producer against
+    /// its output descriptions.
+    ///
+    /// The dependency relations of an artifact are its incoming
+    /// `DtypeDepends` relations other than its partitioning, and for
+    /// producers also its incoming `ProducedFrom` relations. Any other
+    /// `ProducedFrom` relation must be a producer output, for which
+    /// violations are reported against the producer.
+    pub fn validate<T: DatatypeEnum>(
+        &self,
+        dtypes_registry: &DatatypesRegistry<T>,
+        repo: &crate::repo::Repository,
+    ) -> Result<(), Error>
+            where T::InterfaceControllerType: InterfaceController<ProducerController> {
+
+        let producer_iface = datatype::interface::INTERFACE_PRODUCER_DESC.interface.name;
+        let is_producer = |art: &Artifact| dtypes_registry.get_datatype_by_uuid(&art.dtype_uuid)
+            .map_or(false, |dtype| dtypes_registry.implements_interface(dtype, producer_iface));
+
+        let mut violations = vec![];
+
+        for art_idx in self.artifacts.graph().node_indices() {
+            let art = &self.artifacts[art_idx];
+            let art_is_producer = is_producer(art);
+
+            let mut partitionings = 0;
+            let mut dependencies = BTreeMap::<&str, Vec<ArtifactGraphIndex>>::new();
+            for edge in self.artifacts.graph().edges_directed(art_idx, Direction::Incoming) {
+                let source = &self.artifacts[edge.source()];
+                match edge.weight() {
+                    ArtifactRelation::DtypeDepends(rel) if rel.name == datatype::interface::PARTITIONING_RELATION_NAME => {
+                        partitionings += 1;
+                        let is_partitioning = dtypes_registry.get_datatype_by_uuid(&source.dtype_uuid)
+                            .map_or(false, |dtype| dtypes_registry.implements_interface(dtype, &rel.name));
+                        if !is_partitioning {
+                            violations.push(DependencyViolation::Datatype {
+                                artifact: art.id.uuid,
+                                relation: rel.name.clone(),
+                                dependency: source.id.uuid,
+                            });
+                        }
+                    },
+                    ArtifactRelation::DtypeDepends(rel) =>
+                        dependencies.entry(rel.name.as_str()).or_default().push(edge.source()),
+                    ArtifactRelation::ProducedFrom(name) if art_is_producer =>
+                        dependencies.entry(name.as_str()).or_default().push(edge.source()),
+                    ArtifactRelation::ProducedFrom(name) => if !is_producer(source) {
+                        violations.push(DependencyViolation::Undeclared {
+                            artifact: art.id.uuid,
+                            relation: name.clone(),
+                        });
+                    },
+                }
+            }
+            if partitionings > 1 {
+                violations.push(DependencyViolation::Cardinality {
+                    artifact: art.id.uuid,
+                    relation: datatype::interface::PARTITIONING_RELATION_NAME.into(),
+                    count: partitionings,
+                });
+            }
+
+            let declared = dtypes_registry.get_model(&art.dtype_uuid).reflection().dependencies;
+            self.check_dependencies(dtypes_registry, art, &declared, &dependencies, &mut violations);
+
+            if art_is_producer {
+                let mut outputs = BTreeMap::<&str, Vec<ArtifactGraphIndex>>::new();
+                for edge in self.artifacts.graph().edges_directed(art_idx, Direction::Outgoing) {
+                    if let ArtifactRelation::ProducedFrom(name) = edge.weight() {
+                        outputs.entry(name.as_str()).or_default().push(edge.target());
+                    }
+                }
+
+                let producer = dtypes_registry.get_model_interface::<ProducerController>(&art.dtype_uuid)
+                    .map(|gen| gen(repo));
+                if let Some(producer) = producer {
+                    let declared = producer.output_descriptions();
+                    self.check_dependencies(dtypes_registry, art, &declared, &outputs, &mut violations);
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Model(ModelError::InvalidDependencies(violations)))
+        }
+    }
+
+    /// Check relations of `art` to other artifacts, grouped by relation name,
+    /// against declared dependency descriptions.
+    fn check_dependencies<T: DatatypeEnum>(
+        &self,
+        dtypes_registry: &DatatypesRegistry<T>,
+        art: &Artifact,
+        declared: &[DependencyDescription],
+        related: &BTreeMap<&str, Vec<ArtifactGraphIndex>>,
+        violations: &mut Vec<DependencyViolation>,
+    ) {
+        for description in declared {
+            let count = related.get(description.name()).map_or(0, Vec::len) as u64;
+            if !description.cardinality_restriction().allows(count) {
+                violations.push(DependencyViolation::Cardinality {
+                    artifact: art.id.uuid,
+                    relation: description.name().into(),
+                    count,
+                });
+            }
+        }
+
+        for (name, related_idxs) in related {
+            let description = match declared.iter().find(|d| d.name() == *name) {
+                Some(description) => description,
+                None => {
+                    violations.push(DependencyViolation::Undeclared {
+                        artifact: art.id.uuid,
+                        relation: (*name).into(),
+                    });
+                    continue;
+                },
+            };
+
+            for &related_idx in related_idxs {
+                let related_art = &self.artifacts[related_idx];
+                let allowed = dtypes_registry.get_datatype_by_uuid(&related_art.dtype_uuid)
+                    .map_or(false, |dtype| dtypes_registry.satisfies_type_restriction(
+                        dtype, description.datatype_restriction()));
+                if !allowed {
+                    violations.push(DependencyViolation::Datatype {
+                        artifact: art.id.uuid,
+                        relation: (*name).into(),
+                        dependency: related_art.id.uuid,
+                    });
+                }
+            }
+        }
+    }
+
     /// Compute the overall state hash, or return None if any artifact's hash
     /// is incorrect.
     fn hash_current_state(&self) -> Option<HashType> {
         let to_visit = daggy::petgraph::algo::toposort(self.artifacts.graph(), None)
-            .expect("TODO: not a DAG");
+            .expect("Impossible: artifact graphs are acyclic");
 
         let mut ag_hash = DefaultHasher::new();
 
@@ -530,34 +687,135 @@ impl<'ag> VersionGraph<'ag> {
             .collect()
     }
 
+    /// Get the parents of a version in the order their relations were
+    /// added, so that the first is the version's first parent.
     pub fn get_parents(
         &self,
         v_idx: VersionGraphIndex,
     ) -> Vec<VersionGraphIndex> {
-        self.get_related_versions(
-            v_idx,
-            &VersionRelation::Parent,
-            petgraph::Direction::Incoming)
+        let mut parent_edges = self.versions.graph()
+            .edges_directed(v_idx, petgraph::Direction::Incoming)
+            .filter(|e| e.weight() == &VersionRelation::Parent)
+            .map(|e| (e.id(), e.source()))
+            .collect::<Vec<_>>();
+        parent_edges.sort();
+
+        parent_edges.into_iter().map(|(_, p_idx)| p_idx).collect()
+    }
+
+    pub fn first_parent(
+        &self,
+        v_idx: VersionGraphIndex,
+    ) -> Option<VersionGraphIndex> {
+        self.get_parents(v_idx).into_iter().next()
+    }
+
+    /// Walk a version and its first parents back to a root version.
+    pub fn first_parent_history<'a>(
+        &'a self,
+        v_idx: VersionGraphIndex,
+    ) -> FirstParentHistory<'a, 'ag> {
+        FirstParentHistory {
+            ver_graph: self,
+            next: Some(v_idx),
+        }
+    }
+
+    /// Iterate over versions from which a version is transitively related by
+    /// `relation`, nearest first, excluding the version itself.
+    pub fn ancestors<'a>(
+        &'a self,
+        v_idx: VersionGraphIndex,
+        relation: &'a VersionRelation<'ag>,
+    ) -> RelatedVersions<'a, 'ag> {
+        RelatedVersions::new(self, v_idx, relation, petgraph::Direction::Incoming)
+    }
+
+    /// Iterate over versions transitively related to a version by
+    /// `relation`, nearest first, excluding the version itself.
+    pub fn descendants<'a>(
+        &'a self,
+        v_idx: VersionGraphIndex,
+        relation: &'a VersionRelation<'ag>,
+    ) -> RelatedVersions<'a, 'ag> {
+        RelatedVersions::new(self, v_idx, relation, petgraph::Direction::Outgoing)
+    }
+
+    /// Whether `ancestor` is an ancestor of `v_idx` through `Parent`
+    /// relations. Versions are their own ancestors.
+    pub fn is_ancestor(
+        &self,
+        ancestor: VersionGraphIndex,
+        v_idx: VersionGraphIndex,
+    ) -> bool {
+        ancestor == v_idx ||
+            self.ancestors(v_idx, &VersionRelation::Parent).any(|a_idx| a_idx == ancestor)
+    }
+
+    /// Find the lowest common ancestors of versions through `Parent`
+    /// relations: common ancestors of all of them that are not ancestors of
+    /// any other common ancestor. Versions are their own ancestors.
+    pub fn lowest_common_ancestors(
+        &self,
+        v_idxs: &[VersionGraphIndex],
+    ) -> Vec<VersionGraphIndex> {
+        let ancestors = |v_idx: VersionGraphIndex| std::iter::once(v_idx)
+            .chain(self.ancestors(v_idx, &VersionRelation::Parent))
+            .collect::<BTreeSet<_>>();
+
+        let mut common = match v_idxs.split_first() {
+            Some((first, rest)) => rest.iter()
+                .fold(ancestors(*first), |common, &v_idx| {
+                    common.intersection(&ancestors(v_idx)).cloned().collect()
+                }),
+            None => return vec![],
+        };
+
+        // Common ancestors are closed under ancestry, so any common ancestor
+        // of another has a child among them.
+        let has_common_child = common.iter()
+            .filter(|&&c_idx| self.get_related_versions(
+                    c_idx,
+                    &VersionRelation::Parent,
+                    petgraph::Direction::Outgoing)
+                .iter()
+                .any(|child_idx| common.contains(child_idx)))
+            .cloned()
+            .collect::<Vec<_>>();
+        for c_idx in has_common_child {
+            common.remove(&c_idx);
+        }
+
+        common.into_iter().collect()
+    }
+
+    /// Find a merge base of versions, which is one of their lowest common
+    /// ancestors. If there are several, which is returned is unspecified.
+    pub fn merge_base(
+        &self,
+        v_idxs: &[VersionGraphIndex],
+    ) -> Option<VersionGraphIndex> {
+        self.lowest_common_ancestors(v_idxs).into_iter().next()
     }
 
     pub fn new_child(
         &mut self,
         parent_idx: VersionGraphIndex,
         representation: RepresentationKind,
-    ) -> VersionGraphIndex {
+    ) -> Result<VersionGraphIndex, Error> {
         let parent = &self.versions[parent_idx];
         let child = Version::new(parent.artifact, representation);
         let child_idx = self.versions.add_node(child);
-        self.versions.add_edge(parent_idx, child_idx, VersionRelation::Parent).expect("TODO");
+        self.versions.add_edge(parent_idx, child_idx, VersionRelation::Parent)?;
 
-        child_idx
+        Ok(child_idx)
     }
 
     pub fn new_child_same_dependencies(
         &mut self,
         parent_idx: VersionGraphIndex,
         representation: RepresentationKind,
-    ) -> VersionGraphIndex {
+    ) -> Result<VersionGraphIndex, Error> {
         let parent = &self.versions[parent_idx];
         let child = Version::new(parent.artifact, representation);
         let child_idx = self.versions.add_node(child);
@@ -570,14 +828,13 @@ impl<'ag> VersionGraph<'ag> {
             })
             .map(|e| (e.source(), e.weight().clone()))
             .collect();
-        to_add.into_iter()
-            .for_each(|(source, weight)| {
-                self.versions.add_edge(source, child_idx, weight).expect("TODO");
-            });
+        for (source, weight) in to_add {
+            self.versions.add_edge(source, child_idx, weight)?;
+        }
 
-        self.versions.add_edge(parent_idx, child_idx, VersionRelation::Parent).expect("TODO");
+        self.versions.add_edge(parent_idx, child_idx, VersionRelation::Parent)?;
 
-        child_idx
+        Ok(child_idx)
     }
 
     pub fn get_partitioning(
@@ -602,6 +859,72 @@ impl<'ag> VersionGraph<'ag> {
     }
 }
 
+/// Breadth-first walk of versions transitively related to a version.
+pub struct RelatedVersions<'a, 'ag: 'a> {
+    ver_graph: &'a VersionGraph<'ag>,
+    relation: &'a VersionRelation<'ag>,
+    direction: petgraph::Direction,
+    queue: VecDeque<VersionGraphIndex>,
+    discovered: BTreeSet<VersionGraphIndex>,
+}
+
+impl<'a, 'ag: 'a> RelatedVersions<'a, 'ag> {
+    fn new(
+        ver_graph: &'a VersionGraph<'ag>,
+        v_idx: VersionGraphIndex,
+        relation: &'a VersionRelation<'ag>,
+        direction: petgraph::Direction,
+    ) -> Self {
+        let mut walk = RelatedVersions {
+            ver_graph,
+            relation,
+            direction,
+            queue: VecDeque::new(),
+            discovered: BTreeSet::new(),
+        };
+        walk.discovered.insert(v_idx);
+        walk.enqueue_related(v_idx);
+
+        walk
+    }
+
+    fn enqueue_related(&mut self, v_idx: VersionGraphIndex) {
+        for related_idx in self.ver_graph.get_related_versions(v_idx, self.relation, self.direction) {
+            if self.discovered.insert(related_idx) {
+                self.queue.push_back(related_idx);
+            }
+        }
+    }
+}
+
+impl<'a, 'ag: 'a> Iterator for RelatedVersions<'a, 'ag> {
+    type Item = VersionGraphIndex;
+
+    fn next(&mut self) -> Option<VersionGraphIndex> {
+        let v_idx = self.queue.pop_front()?;
+        self.enqueue_related(v_idx);
+
+        Some(v_idx)
+    }
+}
+
+/// Walk of a version and its first parents.
+pub struct FirstParentHistory<'a, 'ag: 'a> {
+    ver_graph: &'a VersionGraph<'ag>,
+    next: Option<VersionGraphIndex>,
+}
+
+impl<'a, 'ag: 'a> Iterator for FirstParentHistory<'a, 'ag> {
+    type Item = VersionGraphIndex;
+
+    fn next(&mut self) -> Option<VersionGraphIndex> {
+        let v_idx = self.next?;
+        self.next = self.ver_graph.first_parent(v_idx);
+
+        Some(v_idx)
+    }
+}
+
 impl<'ag> IdentifiableGraph for VersionGraph<'ag> {
     type N = Version<'ag>;
     type E = VersionRelation<'ag>;
@@ -708,10 +1031,38 @@ pub struct Partition<'ag: 'vg, 'vg> {
     #[serde(skip_serializing)]
     pub partitioning: &'vg Version<'ag>,
     pub index: PartitionIndex,
-    // TODO: also need to be able to handle partition types (leaf v. neighborhood, level, arbitrary)
+    pub kind: PartitionKind,
 }
 
-#[derive(Debug)]
+/// The role of a partition within its partitioning.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Deserialize, Serialize)]
+#[cfg_attr(feature="backend-postgres", derive(ToSql, FromSql))]
+#[cfg_attr(feature="backend-postgres", postgres(name = "partition_kind"))]
+pub enum PartitionKind {
+    /// A disjoint partition of the finest resolution, such as a grid block.
+    #[cfg_attr(feature="backend-postgres", postgres(name = "leaf"))]
+    Leaf,
+    /// A partition overlapping its neighboring leaves, such as a block with
+    /// a halo.
+    #[cfg_attr(feature="backend-postgres", postgres(name = "neighborhood"))]
+    Neighborhood,
+    /// A partition of a coarser resolution, such as a level of a
+    /// multiresolution pyramid.
+    #[cfg_attr(feature="backend-postgres", postgres(name = "level"))]
+    Level,
+    /// A partition with no spatial relationship to other partitions.
+    #[cfg_attr(feature="backend-postgres", postgres(name = "arbitrary"))]
+    Arbitrary,
+}
+
+impl Default for PartitionKind {
+    fn default() -> Self {
+        PartitionKind::Leaf
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 #[derive(Deserialize, Serialize)]
 #[cfg_attr(feature="backend-postgres", derive(ToSql, FromSql))]
 #[cfg_attr(feature="backend-postgres", postgres(name = "part_completion"))]
@@ -741,7 +1092,7 @@ pub struct Hunk<'ag: 'vg1 + 'vg2, 'vg1, 'vg2> {
     pub representation: RepresentationKind,
     pub completion: PartCompletion,
     /// Indicates for a merge version which ancestral version's hunk takes
-    /// precedence.
+    /// precedence. Hunks with precedence have no payload.
     pub precedence: Option<Uuid>,
 }
 
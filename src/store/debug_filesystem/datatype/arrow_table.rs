@@ -0,0 +1,91 @@
+use std::borrow::Borrow;
+use std::io::Write;
+
+use crate::{
+    Error,
+    Hunk,
+    RepresentationKind,
+};
+use crate::datatype::Payload;
+use crate::datatype::arrow_table::{
+    ArrowDatatypeBackend,
+    ArrowDelta,
+    RecordBatches,
+    Storage,
+};
+use crate::repo::Repository;
+use crate::store::debug_filesystem::{
+    DebugFilesystemRepository,
+    hunk_path,
+    read_json,
+    write_json,
+};
+
+use super::DebugFilesystemMetaController;
+
+
+/// Arrow IPC stream of a state's batches or a delta's appended batches.
+const BATCHES_FILE: &'static str = "batches.arrows";
+const DELETIONS_FILE: &'static str = "deletions.json";
+
+
+impl DebugFilesystemMetaController for ArrowDatatypeBackend<DebugFilesystemRepository> {}
+
+fn write_batches(path: std::path::PathBuf, batches: &RecordBatches) -> Result<(), Error> {
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(&batches.to_ipc()?)?;
+    Ok(())
+}
+
+fn read_batches(path: std::path::PathBuf) -> Result<RecordBatches, Error> {
+    let file = std::fs::File::open(path)?;
+    RecordBatches::from_ipc(std::io::BufReader::new(file))
+}
+
+impl crate::datatype::Storage for ArrowDatatypeBackend<DebugFilesystemRepository> {
+    fn write_hunk(
+        &mut self,
+        repo: &Repository,
+        hunk: &Hunk,
+        payload: &Payload<Self::StateType, Self::DeltaType>,
+    ) -> Result<(), Error> {
+        let rc: &DebugFilesystemRepository = repo.borrow();
+        let path = hunk_path(rc, hunk);
+
+        match (hunk.representation, payload) {
+            (RepresentationKind::State, Payload::State(batches)) =>
+                write_batches(path.join(BATCHES_FILE), batches),
+            (RepresentationKind::Delta, Payload::Delta(ArrowDelta {deletions, appended})) => {
+                write_json(path.join(DELETIONS_FILE), deletions)?;
+                write_batches(path.join(BATCHES_FILE), appended)
+            },
+            (RepresentationKind::State, _) |
+            (RepresentationKind::Delta, _) =>
+                Err(Error::Store("Attempt to write hunk with mismatched payload".into())),
+            _ => Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+        }
+    }
+
+    fn read_hunk(
+        &self,
+        repo: &Repository,
+        hunk: &Hunk,
+    ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+        let rc: &DebugFilesystemRepository = repo.borrow();
+        let path = hunk_path(rc, hunk);
+
+        match hunk.representation {
+            RepresentationKind::State =>
+                Ok(Payload::State(read_batches(path.join(BATCHES_FILE))?)),
+            RepresentationKind::Delta =>
+                Ok(Payload::Delta(ArrowDelta {
+                    deletions: read_json(path.join(DELETIONS_FILE))?,
+                    appended: read_batches(path.join(BATCHES_FILE))?,
+                })),
+            _ => Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+        }
+    }
+}
+
+impl Storage for ArrowDatatypeBackend<DebugFilesystemRepository> {}
@@ -31,6 +31,7 @@ use crate::{
     PartCompletion,
     Partition,
     PartitionIndex,
+    PartitionKind,
     repo::Repository,
     RepresentationKind,
     Version,
@@ -59,6 +60,7 @@ use crate::datatype::interface::{
     CustomProductionPolicyController,
     ProducerController,
 };
+use crate::datatype::snapshot::SnapshotPolicy;
 use crate::default_debug_filesystem_store_backend;
 use crate::store::debug_filesystem::{
     artifact_path,
@@ -75,6 +77,7 @@ const HUNK_FILE: &'static str = "hunk.json";
 const ORIGIN_FILE: &'static str = "origin.json";
 const PRODUCTION_POLICIES_FILE: &'static str = "production_policies.json";
 const PRODUCTION_SPECS_FILE: &'static str = "production_specs.json";
+const SNAPSHOT_POLICY_FILE: &'static str = "snapshot_policy.json";
 const VERSION_FILE: &'static str = "version.json";
 const VERSION_DEPENDENCIES_FILE: &'static str = "version_dependencies.json";
 const VERSION_PARENTS_FILE: &'static str = "version_parents.json";
@@ -724,6 +727,31 @@ impl Storage for ArtifactGraphDtypeBackend<DebugFilesystemRepository> {
         path.push(PRODUCTION_SPECS_FILE);
         read_json(path)
     }
+
+    fn write_snapshot_policy(
+        &mut self,
+        repo: &Repository,
+        artifact: &Artifact,
+        policy: &SnapshotPolicy,
+    ) -> Result<(), Error> {
+        let rc: &DebugFilesystemRepository = repo.borrow();
+
+        let mut path = artifact_path(rc, artifact);
+        path.push(SNAPSHOT_POLICY_FILE);
+        write_json(path, policy)
+    }
+
+    fn get_snapshot_policy(
+        &self,
+        repo: &Repository,
+        artifact: &Artifact,
+    ) -> Result<Option<SnapshotPolicy>, Error> {
+        let rc: &DebugFilesystemRepository = repo.borrow();
+
+        let mut path = artifact_path(rc, artifact);
+        path.push(SNAPSHOT_POLICY_FILE);
+        read_optional_json(path)
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -755,6 +783,8 @@ impl VersionPartial {
 #[derive(Deserialize)]
 struct PartitionPartial {
     index: PartitionIndex,
+    #[serde(default)]
+    kind: PartitionKind,
 }
 
 #[derive(Deserialize)]
@@ -778,6 +808,7 @@ impl HunkPartial {
             partition: Partition {
                 partitioning,
                 index: self.partition.index,
+                kind: self.partition.kind,
             },
             representation: self.representation,
             completion: self.completion,
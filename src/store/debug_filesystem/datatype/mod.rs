@@ -1,6 +1,8 @@
 pub use heraclitus_core::store::debug_filesystem::datatype::*;
 
 
+#[cfg(feature = "datatype-arrow")]
+pub mod arrow_table;
 pub mod artifact_graph;
 // pub mod blob;
 pub mod blob {
@@ -12,6 +14,46 @@ pub mod blob {
     default_debug_filesystem_store_backend!(BlobDatatypeBackend);
     impl Storage for BlobDatatypeBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
 }
+// pub mod json;
+pub mod json {
+    use crate::datatype::json::{
+        JsonDatatypeBackend,
+        Storage,
+    };
+    use crate::default_debug_filesystem_store_backend;
+    default_debug_filesystem_store_backend!(JsonDatatypeBackend);
+    impl Storage for JsonDatatypeBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+}
+// pub mod key_value;
+pub mod key_value {
+    use crate::datatype::key_value::{
+        KeyValueDatatypeBackend,
+        Storage,
+    };
+    use crate::default_debug_filesystem_store_backend;
+    default_debug_filesystem_store_backend!(KeyValueDatatypeBackend);
+    impl Storage for KeyValueDatatypeBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+}
+// pub mod ndarray;
+pub mod ndarray {
+    use crate::datatype::ndarray::{
+        NdArrayDatatypeBackend,
+        Storage,
+    };
+    use crate::default_debug_filesystem_store_backend;
+    default_debug_filesystem_store_backend!(NdArrayDatatypeBackend);
+    impl Storage for NdArrayDatatypeBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+}
+// pub mod octree;
+pub mod octree {
+    use crate::datatype::octree::{
+        PointOctreeBackend,
+        Storage,
+    };
+    use crate::default_debug_filesystem_store_backend;
+    default_debug_filesystem_store_backend!(PointOctreeBackend);
+    impl Storage for PointOctreeBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+}
 // pub mod partitioning;
 pub mod partitioning {
     use crate::datatype::partitioning::UnaryPartitioningBackend;
@@ -28,6 +70,26 @@ pub mod partitioning {
         default_debug_filesystem_store_backend!(ArbitraryPartitioningBackend);
         impl Storage for ArbitraryPartitioningBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
     }
+
+    pub mod grid {
+        use crate::datatype::partitioning::grid::{
+            GridPartitioningBackend,
+            Storage,
+        };
+        use crate::default_debug_filesystem_store_backend;
+        default_debug_filesystem_store_backend!(GridPartitioningBackend);
+        impl Storage for GridPartitioningBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+    }
+
+    pub mod hash {
+        use crate::datatype::partitioning::hash::{
+            HashPartitioningBackend,
+            Storage,
+        };
+        use crate::default_debug_filesystem_store_backend;
+        default_debug_filesystem_store_backend!(HashPartitioningBackend);
+        impl Storage for HashPartitioningBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+    }
 }
 // pub mod producer;
 pub mod producer {
@@ -47,6 +109,28 @@ pub mod producer {
     }
 }
 pub mod reference;
+// pub mod table;
+pub mod table {
+    use crate::datatype::artifact_graph::ArtifactMeta;
+    use crate::datatype::table::{
+        TableDatatypeBackend,
+        Storage,
+    };
+    use crate::default_debug_filesystem_store_backend;
+    default_debug_filesystem_store_backend!(TableDatatypeBackend);
+    impl ArtifactMeta for TableDatatypeBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+    impl Storage for TableDatatypeBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+}
+// pub mod text;
+pub mod text {
+    use crate::datatype::text::{
+        TextDatatypeBackend,
+        Storage,
+    };
+    use crate::default_debug_filesystem_store_backend;
+    default_debug_filesystem_store_backend!(TextDatatypeBackend);
+    impl Storage for TextDatatypeBackend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
+}
 // pub mod tracking_branch_producer;
 pub mod tracking_branch_producer {
     use crate::datatype::tracking_branch_producer::TrackingBranchProducerBackend;
@@ -77,34 +161,33 @@ pub mod tracking_branch_producer {
 #[macro_export]
 macro_rules! default_debug_filesystem_store_backend {
     ( $store_backend:ident ) => {
-        use std::borrow::Borrow;
-
         impl heraclitus::store::debug_filesystem::datatype::DebugFilesystemMetaController for
             $store_backend<heraclitus::store::debug_filesystem::DebugFilesystemRepository> {}
 
         impl heraclitus::datatype::Storage for
             $store_backend<heraclitus::store::debug_filesystem::DebugFilesystemRepository>
         {
-
-            default fn write_hunk(
+            fn write_hunk(
                 &mut self,
                 repo: &heraclitus::repo::Repository,
                 hunk: &heraclitus::Hunk,
                 payload: &heraclitus::datatype::Payload<Self::StateType, Self::DeltaType>,
             ) -> Result<(), heraclitus::Error> {
-                let rc: &heraclitus::store::debug_filesystem::DebugFilesystemRepository = repo.borrow();
+                let rc: &heraclitus::store::debug_filesystem::DebugFilesystemRepository =
+                    std::borrow::Borrow::borrow(repo);
 
                 let mut path = heraclitus::store::debug_filesystem::hunk_path(rc, hunk);
                 path.push("payload.json");
                 heraclitus::store::debug_filesystem::write_json(path, payload)
             }
 
-            default fn read_hunk(
+            fn read_hunk(
                 &self,
                 repo: &heraclitus::repo::Repository,
                 hunk: &heraclitus::Hunk,
             ) -> Result<heraclitus::datatype::Payload<Self::StateType, Self::DeltaType>, heraclitus::Error> {
-                let rc: &heraclitus::store::debug_filesystem::DebugFilesystemRepository = repo.borrow();
+                let rc: &heraclitus::store::debug_filesystem::DebugFilesystemRepository =
+                    std::borrow::Borrow::borrow(repo);
 
                 let mut path = heraclitus::store::debug_filesystem::hunk_path(rc, hunk);
                 path.push("payload.json");
@@ -0,0 +1,154 @@
+use std::borrow::Borrow;
+
+use heraclitus_core::{
+    postgres,
+    schemer,
+    schemer_postgres,
+};
+use postgres::error::Error as PostgresError;
+use postgres::transaction::Transaction;
+use schemer::migration;
+use schemer_postgres::{PostgresAdapter, PostgresMigration};
+
+use crate::{
+    RepresentationKind,
+    Error,
+    Hunk,
+};
+use crate::datatype::{
+    Payload,
+};
+use crate::datatype::arrow_table::{
+    ArrowDatatypeBackend,
+    ArrowDelta,
+    RecordBatches,
+    Storage,
+};
+use crate::repo::Repository;
+use crate::store::postgres::{PostgresMigratable, PostgresRepository};
+
+
+struct PGMigrationArrow;
+migration!(
+    PGMigrationArrow,
+    "b1c4bf3b-533a-4b99-a7bd-4b0c6581c4ac",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "create arrow tables");
+
+impl PostgresMigration for PGMigrationArrow {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/arrow_0001.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/arrow_0001.down.sql"))
+    }
+}
+
+
+impl PostgresMigratable for ArrowDatatypeBackend<PostgresRepository> {
+    fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+        vec![
+            Box::new(PGMigrationArrow),
+        ]
+    }
+}
+
+impl super::PostgresMetaController for ArrowDatatypeBackend<PostgresRepository> {}
+
+impl crate::datatype::Storage for ArrowDatatypeBackend<PostgresRepository> {
+    fn write_hunk(
+        &mut self,
+        repo: &Repository,
+        hunk: &Hunk,
+        payload: &Payload<Self::StateType, Self::DeltaType>,
+    ) -> Result<(), Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        match hunk.representation {
+            RepresentationKind::State =>
+                match *payload {
+                    Payload::State(ref batches) => {
+                        trans.execute(r#"
+                                INSERT INTO arrow_dtype_state (hunk_id, batches)
+                                SELECT h.id, r.batches
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::bytea))
+                                  AS r (uuid_, hash, batches)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &batches.to_ipc()?])?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+                },
+            RepresentationKind::Delta =>
+                match *payload {
+                    Payload::Delta(ArrowDelta {ref deletions, ref appended}) => {
+                        let deletions = serde_json::to_value(deletions)
+                            .map_err(|e| Error::Store(e.to_string()))?;
+                        trans.execute(r#"
+                                INSERT INTO arrow_dtype_delta (hunk_id, deletions, appended)
+                                SELECT h.id, r.deletions, r.appended
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::jsonb, $4::bytea))
+                                  AS r (uuid_, hash, deletions, appended)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &deletions, &appended.to_ipc()?])?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+                },
+            _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+        }
+
+        trans.set_commit();
+        Ok(())
+    }
+
+    fn read_hunk(
+        &self,
+        repo: &Repository,
+        hunk: &Hunk,
+    ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let payload = match hunk.representation {
+            RepresentationKind::State => {
+                let state_rows = trans.query(r#"
+                        SELECT a.batches
+                        FROM arrow_dtype_state a
+                        JOIN hunk h
+                          ON (h.id = a.hunk_id)
+                        WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                let ipc: Vec<u8> = state_rows.get(0).get(0);
+                Payload::State(RecordBatches::from_ipc(&ipc[..])?)
+            },
+            RepresentationKind::Delta => {
+                let delta_rows = trans.query(r#"
+                        SELECT a.deletions, a.appended
+                        FROM arrow_dtype_delta a
+                        JOIN hunk h
+                          ON (h.id = a.hunk_id)
+                        WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                let delta_row = delta_rows.get(0);
+                let ipc: Vec<u8> = delta_row.get(1);
+                Payload::Delta(ArrowDelta {
+                    deletions: serde_json::from_value(delta_row.get(0))
+                        .map_err(|e| Error::Store(e.to_string()))?,
+                    appended: RecordBatches::from_ipc(&ipc[..])?,
+                })
+            },
+            _ => return Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+        };
+
+        Ok(payload)
+    }
+}
+
+impl Storage for ArrowDatatypeBackend<PostgresRepository> {}
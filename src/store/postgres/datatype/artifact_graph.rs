@@ -1,5 +1,6 @@
 use std::borrow::Borrow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryFrom;
 
 use heraclitus_core::{
     daggy,
@@ -66,6 +67,7 @@ use crate::datatype::interface::{
     CustomProductionPolicyController,
     ProducerController,
 };
+use crate::datatype::snapshot::SnapshotPolicy;
 use crate::repo::Repository;
 use crate::store::postgres::{
     PostgresMigratable,
@@ -162,7 +164,8 @@ impl ArtifactGraphDtypeBackend<PostgresRepository> {
                 FROM version_parent vp
                 JOIN version v
                   ON ({})
-                JOIN artifact a ON a.id = v.artifact_id;
+                JOIN artifact a ON a.id = v.artifact_id
+                ORDER BY vp.child_id, vp.ordinal;
             "#, ancestry_join),
             &[&v_db_ids])?;
         for row in &ancestry_node_rows {
@@ -297,11 +300,84 @@ impl PostgresMigration for PGMigrationArtifactGraphs {
     }
 }
 
+struct PGMigrationPartitionKinds;
+migration!(
+    PGMigrationPartitionKinds,
+    "ef5a493d-5241-4a5e-907c-c651176a8467",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "add partition kinds to hunks");
+
+impl PostgresMigration for PGMigrationPartitionKinds {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/artifact_graph_0002.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/artifact_graph_0002.down.sql"))
+    }
+}
+
+struct PGMigrationHunkPayloads;
+migration!(
+    PGMigrationHunkPayloads,
+    "7c0067fe-9c48-4cb2-891b-df1e3a43aa92",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "create shared hunk payload table");
+
+impl PostgresMigration for PGMigrationHunkPayloads {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/artifact_graph_0003.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/artifact_graph_0003.down.sql"))
+    }
+}
+
+struct PGMigrationVersionParentOrdinals;
+migration!(
+    PGMigrationVersionParentOrdinals,
+    "d8546b23-2827-4b8e-88aa-19f4ea179be5",
+    ["7c0067fe-9c48-4cb2-891b-df1e3a43aa92",],
+    "add ordinals to version parents");
+
+impl PostgresMigration for PGMigrationVersionParentOrdinals {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/artifact_graph_0004.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/artifact_graph_0004.down.sql"))
+    }
+}
+
+struct PGMigrationSnapshotPolicies;
+migration!(
+    PGMigrationSnapshotPolicies,
+    "80bf7500-9a8e-4c07-a034-471f09fcedd5",
+    ["d8546b23-2827-4b8e-88aa-19f4ea179be5",],
+    "create artifact snapshot policy table");
+
+impl PostgresMigration for PGMigrationSnapshotPolicies {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/artifact_graph_0005.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/artifact_graph_0005.down.sql"))
+    }
+}
+
+
 
 impl PostgresMigratable for ArtifactGraphDtypeBackend<PostgresRepository> {
     fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
         vec![
             Box::new(PGMigrationArtifactGraphs),
+            Box::new(PGMigrationPartitionKinds),
+            Box::new(PGMigrationHunkPayloads),
+            Box::new(PGMigrationVersionParentOrdinals),
+            Box::new(PGMigrationSnapshotPolicies),
         ]
     }
 }
@@ -762,10 +838,10 @@ impl Storage for ArtifactGraphDtypeBackend<PostgresRepository> {
         let ver_id: i64 = ver_id_row.get(0).get(0);
 
         let insert_parent = trans.prepare(r#"
-                INSERT INTO version_parent (parent_id, child_id)
-                SELECT v.id, r.child_id
-                FROM (VALUES ($1::uuid, $2::bigint))
-                AS r (parent_uuid, child_id)
+                INSERT INTO version_parent (parent_id, child_id, ordinal)
+                SELECT v.id, r.child_id, r.ordinal
+                FROM (VALUES ($1::uuid, $2::bigint, $3::smallint))
+                AS r (parent_uuid, child_id, ordinal)
                 JOIN version v ON v.uuid_ = r.parent_uuid;
             "#)?;
         let insert_relation = trans.prepare(r#"
@@ -778,13 +854,22 @@ impl Storage for ArtifactGraphDtypeBackend<PostgresRepository> {
                 JOIN version vc ON vc.id = r.child_id;
             "#)?;
 
-        for (e_idx, p_idx) in ver_graph.versions.parents(v_idx).iter(&ver_graph.versions) {
-            let edge = &ver_graph[e_idx];
+        for (ordinal, p_idx) in ver_graph.get_parents(v_idx).into_iter().enumerate() {
             let parent = &ver_graph[p_idx];
-            assert_eq!(match *edge {
-                VersionRelation::Dependence(_) => &insert_relation,
-                VersionRelation::Parent => &insert_parent,
-            }.execute(&[&parent.id.uuid, &ver_id])?, 1, "TODO: relation not inserted!");
+            if insert_parent.execute(&[&parent.id.uuid, &ver_id, &(ordinal as i16)])? != 1 {
+                return Err(ModelError::MalformedGraph(format!(
+                    "Parent version {} of staging version is not stored", parent.id.uuid)).into());
+            }
+        }
+
+        for (e_idx, p_idx) in ver_graph.versions.parents(v_idx).iter(&ver_graph.versions) {
+            if let VersionRelation::Dependence(_) = ver_graph[e_idx] {
+                let parent = &ver_graph[p_idx];
+                if insert_relation.execute(&[&parent.id.uuid, &ver_id])? != 1 {
+                    return Err(ModelError::MalformedGraph(format!(
+                        "Dependency version {} of staging version is not stored", parent.id.uuid)).into());
+                }
+            }
         }
 
         trans.set_commit();
@@ -1058,6 +1143,149 @@ impl Storage for ArtifactGraphDtypeBackend<PostgresRepository> {
         Ok(ver_graph)
     }
 
+    fn get_ancestor_uuids(
+        &self,
+        repo: &Repository,
+        ver_graph: &VersionGraph,
+        v_idx: VersionGraphIndex,
+    ) -> Result<BTreeSet<Uuid>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let rows = trans.query(r#"
+                WITH RECURSIVE ancestor (id) AS (
+                    SELECT vp.parent_id
+                    FROM version_parent vp
+                    JOIN version v ON v.id = vp.child_id
+                    WHERE v.uuid_ = $1::uuid
+                  UNION
+                    SELECT vp.parent_id
+                    FROM version_parent vp
+                    JOIN ancestor a ON a.id = vp.child_id
+                )
+                SELECT v.uuid_
+                FROM ancestor a
+                JOIN version v ON v.id = a.id;
+            "#, &[&ver_graph[v_idx].id.uuid])?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    fn get_descendant_uuids(
+        &self,
+        repo: &Repository,
+        ver_graph: &VersionGraph,
+        v_idx: VersionGraphIndex,
+    ) -> Result<BTreeSet<Uuid>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let rows = trans.query(r#"
+                WITH RECURSIVE descendant (id) AS (
+                    SELECT vp.child_id
+                    FROM version_parent vp
+                    JOIN version v ON v.id = vp.parent_id
+                    WHERE v.uuid_ = $1::uuid
+                  UNION
+                    SELECT vp.child_id
+                    FROM version_parent vp
+                    JOIN descendant d ON d.id = vp.parent_id
+                )
+                SELECT v.uuid_
+                FROM descendant d
+                JOIN version v ON v.id = d.id;
+            "#, &[&ver_graph[v_idx].id.uuid])?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    fn get_merge_base_uuids(
+        &self,
+        repo: &Repository,
+        ver_graph: &VersionGraph,
+        v_idxs: &[VersionGraphIndex],
+    ) -> Result<BTreeSet<Uuid>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let v_uuids = v_idxs.iter()
+            .map(|&v_idx| ver_graph[v_idx].id.uuid)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if v_uuids.is_empty() {
+            return Ok(BTreeSet::new());
+        }
+
+        // Common ancestors are closed under ancestry, so those without a
+        // child among them are the lowest.
+        let rows = trans.query(r#"
+                WITH RECURSIVE ancestor (source_id, id) AS (
+                    SELECT v.id, v.id
+                    FROM version v
+                    WHERE v.uuid_ = ANY($1::uuid[])
+                  UNION
+                    SELECT a.source_id, vp.parent_id
+                    FROM version_parent vp
+                    JOIN ancestor a ON a.id = vp.child_id
+                ),
+                common (id) AS (
+                    SELECT a.id
+                    FROM ancestor a
+                    GROUP BY a.id
+                    HAVING count(DISTINCT a.source_id) = $2::bigint
+                )
+                SELECT v.uuid_
+                FROM common c
+                JOIN version v ON v.id = c.id
+                WHERE NOT EXISTS (
+                    SELECT 1
+                    FROM version_parent vp
+                    JOIN common cc ON cc.id = vp.child_id
+                    WHERE vp.parent_id = c.id
+                );
+            "#, &[&v_uuids, &(v_uuids.len() as i64)])?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    fn get_first_parent_history_uuids(
+        &self,
+        repo: &Repository,
+        ver_graph: &VersionGraph,
+        v_idx: VersionGraphIndex,
+    ) -> Result<Vec<Uuid>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let rows = trans.query(r#"
+                WITH RECURSIVE history (id, depth) AS (
+                    SELECT v.id, 0
+                    FROM version v
+                    WHERE v.uuid_ = $1::uuid
+                  UNION ALL
+                    SELECT vp.parent_id, h.depth + 1
+                    FROM version_parent vp
+                    JOIN history h ON h.id = vp.child_id
+                    WHERE vp.ordinal = 0
+                )
+                SELECT v.uuid_
+                FROM history h
+                JOIN version v ON v.id = h.id
+                ORDER BY h.depth;
+            "#, &[&ver_graph[v_idx].id.uuid])?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
     fn create_hunks<'ag: 'vg1 + 'vg2, 'vg1, 'vg2, H>(
         &mut self,
         repo: &Repository,
@@ -1073,14 +1301,16 @@ impl Storage for ArtifactGraphDtypeBackend<PostgresRepository> {
         let insert_hunk = trans.prepare(r#"
                 INSERT INTO hunk (
                     uuid_, hash,
-                    version_id, partition_id,
+                    version_id, partition_id, partition_kind,
                     representation, completion)
-                SELECT r.uuid_, r.hash, v.id, r.partition_id, r.representation, r.completion
+                SELECT r.uuid_, r.hash, v.id, r.partition_id, r.partition_kind,
+                    r.representation, r.completion
                 FROM (VALUES (
                         $1::uuid, $2::bigint,
-                        $3::uuid, $4::bigint, $5::bigint,
-                        $6::representation_kind, $7::part_completion))
-                  AS r (uuid_, hash, v_uuid, v_hash, partition_id, representation, completion)
+                        $3::uuid, $4::bigint, $5::bigint, $6::partition_kind,
+                        $7::representation_kind, $8::part_completion))
+                  AS r (uuid_, hash, v_uuid, v_hash, partition_id, partition_kind,
+                    representation, completion)
                 JOIN version v
                   ON (v.uuid_ = r.v_uuid AND v.hash = r.v_hash)
                 RETURNING version_id;
@@ -1106,7 +1336,7 @@ impl Storage for ArtifactGraphDtypeBackend<PostgresRepository> {
             let version_id_row = insert_hunk.query(
                     &[&hunk.id.uuid, &(hunk.id.hash as i64),
                       &hunk.version.id.uuid, &(hunk.version.id.hash as i64),
-                      &(hunk.partition.index as i64),
+                      &(hunk.partition.index as i64), &hunk.partition.kind,
                       &hunk.representation, &hunk.completion])?;
 
             if let Some(ref ver_uuid) = hunk.precedence {
@@ -1136,19 +1366,21 @@ impl Storage for ArtifactGraphDtypeBackend<PostgresRepository> {
             UUID = 0,
             Hash,
             PartitionID,
+            PartitionKind,
             Representation,
             Completion,
             PrecedingVersionUuid,
         };
         let hunk_query = r#"
                 SELECT
-                    h.uuid_, h.hash, h.partition_id, h.representation, h.completion,
+                    h.uuid_, h.hash, h.partition_id, h.partition_kind,
+                    h.representation, h.completion,
                     hpv.uuid_
                 FROM version v
                 JOIN hunk h ON (h.version_id = v.id)
                 LEFT JOIN hunk_precedence hp
                   ON (hp.merge_version_id = v.id AND h.partition_id = hp.partition_id)
-                LEFT JOIN version hpv ON (hp.precedent_version_id = v.id)
+                LEFT JOIN version hpv ON (hp.precedent_version_id = hpv.id)
                 WHERE v.uuid_ = $1::uuid AND v.hash = $2::bigint"#;
         let hunk_rows = match partitions {
             Some(part_idxs) => {
@@ -1176,6 +1408,7 @@ impl Storage for ArtifactGraphDtypeBackend<PostgresRepository> {
                 partition: Partition {
                     partitioning,
                     index: row.get::<_, i64>(HunkRow::PartitionID as usize) as PartitionIndex,
+                    kind: row.get(HunkRow::PartitionKind as usize),
                 },
                 representation: row.get(HunkRow::Representation as usize),
                 completion: row.get(HunkRow::Completion as usize),
@@ -1288,4 +1521,68 @@ impl Storage for ArtifactGraphDtypeBackend<PostgresRepository> {
             representation: spec_row.get(0).get(0),
         })
     }
+
+    fn write_snapshot_policy(
+        &mut self,
+        repo: &Repository,
+        artifact: &Artifact,
+        policy: &SnapshotPolicy,
+    ) -> Result<(), Error> {
+        let out_of_range = |bound| ModelError::Other(format!("Snapshot policy {} is too large", bound));
+        let max_deltas = policy.max_deltas.map(i32::try_from).transpose()
+            .map_err(|_| out_of_range("max_deltas"))?;
+        let max_bytes = policy.max_bytes.map(i64::try_from).transpose()
+            .map_err(|_| out_of_range("max_bytes"))?;
+
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        trans.execute(r#"
+                INSERT INTO snapshot_artifact (artifact_id, max_deltas, max_bytes)
+                SELECT a.id, r.max_deltas, r.max_bytes
+                FROM (VALUES ($1::uuid, $2::integer, $3::bigint))
+                  AS r (a_uuid, max_deltas, max_bytes)
+                JOIN artifact a
+                  ON (a.uuid_ = r.a_uuid)
+                ON CONFLICT (artifact_id) DO UPDATE
+                  SET max_deltas = EXCLUDED.max_deltas, max_bytes = EXCLUDED.max_bytes;
+            "#, &[
+                &artifact.id.uuid,
+                &max_deltas,
+                &max_bytes,
+            ])?;
+
+        trans.set_commit();
+        Ok(())
+    }
+
+    fn get_snapshot_policy(
+        &self,
+        repo: &Repository,
+        artifact: &Artifact,
+    ) -> Result<Option<SnapshotPolicy>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let policy_rows = trans.query(r#"
+                SELECT sa.max_deltas, sa.max_bytes
+                FROM artifact a
+                JOIN snapshot_artifact sa ON (sa.artifact_id = a.id)
+                WHERE a.uuid_ = $1::uuid;"#,
+            &[&artifact.id.uuid])?;
+        Ok(match policy_rows.len() {
+            0 => None,
+            _ => {
+                let row = policy_rows.get(0);
+                Some(SnapshotPolicy {
+                    max_deltas: row.get::<_, Option<i32>>(0).map(|n| n as usize),
+                    max_bytes: row.get::<_, Option<i64>>(1).map(|n| n as u64),
+                })
+            },
+        })
+    }
 }
@@ -0,0 +1,148 @@
+use std::borrow::Borrow;
+
+use heraclitus_core::{
+    postgres,
+    schemer,
+    schemer_postgres,
+};
+use postgres::error::Error as PostgresError;
+use postgres::transaction::Transaction;
+use schemer::migration;
+use schemer_postgres::{PostgresAdapter, PostgresMigration};
+
+use crate::{
+    RepresentationKind,
+    Error,
+    Hunk,
+};
+use crate::datatype::{
+    Payload,
+};
+use crate::datatype::json::{
+    JsonDatatypeBackend,
+    JsonValue,
+    Storage,
+};
+use crate::repo::Repository;
+use crate::store::postgres::{PostgresMigratable, PostgresRepository};
+
+
+struct PGMigrationJson;
+migration!(
+    PGMigrationJson,
+    "1d3fee02-dd7e-47d7-be92-03c55e4cc4f1",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "create json tables");
+
+impl PostgresMigration for PGMigrationJson {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/json_0001.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/json_0001.down.sql"))
+    }
+}
+
+
+impl PostgresMigratable for JsonDatatypeBackend<PostgresRepository> {
+    fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+        vec![
+            Box::new(PGMigrationJson),
+        ]
+    }
+}
+
+impl super::PostgresMetaController for JsonDatatypeBackend<PostgresRepository> {}
+
+impl crate::datatype::Storage for JsonDatatypeBackend<PostgresRepository> {
+    fn write_hunk(
+        &mut self,
+        repo: &Repository,
+        hunk: &Hunk,
+        payload: &Payload<Self::StateType, Self::DeltaType>,
+    ) -> Result<(), Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        match hunk.representation {
+            RepresentationKind::State =>
+                match *payload {
+                    Payload::State(JsonValue(ref document)) => {
+                        trans.execute(r#"
+                                INSERT INTO json_dtype_state (hunk_id, document)
+                                SELECT h.id, r.document
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::jsonb))
+                                  AS r (uuid_, hash, document)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), document])?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+                },
+            RepresentationKind::Delta =>
+                match *payload {
+                    Payload::Delta(ref patch) => {
+                        let patch = serde_json::to_value(patch)
+                            .map_err(|e| Error::Store(e.to_string()))?;
+                        trans.execute(r#"
+                                INSERT INTO json_dtype_delta (hunk_id, patch)
+                                SELECT h.id, r.patch
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::jsonb))
+                                  AS r (uuid_, hash, patch)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &patch])?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+                },
+            _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+        }
+
+        trans.set_commit();
+        Ok(())
+    }
+
+    fn read_hunk(
+        &self,
+        repo: &Repository,
+        hunk: &Hunk,
+    ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let payload = match hunk.representation {
+            RepresentationKind::State => {
+                let json_rows = trans.query(r#"
+                        SELECT j.document
+                        FROM json_dtype_state j
+                        JOIN hunk h
+                          ON (h.id = j.hunk_id)
+                        WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                Payload::State(JsonValue(json_rows.get(0).get(0)))
+            },
+            RepresentationKind::Delta => {
+                let json_rows = trans.query(r#"
+                        SELECT j.patch
+                        FROM json_dtype_delta j
+                        JOIN hunk h
+                          ON (h.id = j.hunk_id)
+                        WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                let patch: serde_json::Value = json_rows.get(0).get(0);
+                Payload::Delta(serde_json::from_value(patch)
+                    .map_err(|e| Error::Store(e.to_string()))?)
+            },
+            _ => return Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+        };
+
+        Ok(payload)
+    }
+}
+
+impl Storage for JsonDatatypeBackend<PostgresRepository> {}
@@ -0,0 +1,152 @@
+use std::borrow::Borrow;
+
+use heraclitus_core::{
+    postgres,
+    schemer,
+    schemer_postgres,
+};
+use postgres::error::Error as PostgresError;
+use postgres::transaction::Transaction;
+use schemer::migration;
+use schemer_postgres::{PostgresAdapter, PostgresMigration};
+
+use crate::{
+    RepresentationKind,
+    Error,
+    Hunk,
+};
+use crate::datatype::{
+    Payload,
+};
+use crate::datatype::json::JsonValue;
+use crate::datatype::key_value::{
+    EntryValue,
+    KeyValueDatatypeBackend,
+    Storage,
+};
+use crate::repo::Repository;
+use crate::store::postgres::{PostgresMigratable, PostgresRepository};
+
+
+struct PGMigrationKeyValue;
+migration!(
+    PGMigrationKeyValue,
+    "842ff3c7-9a32-44b9-8186-a36f895434cb",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "create key value table");
+
+impl PostgresMigration for PGMigrationKeyValue {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/key_value_0001.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/key_value_0001.down.sql"))
+    }
+}
+
+
+impl PostgresMigratable for KeyValueDatatypeBackend<PostgresRepository> {
+    fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+        vec![
+            Box::new(PGMigrationKeyValue),
+        ]
+    }
+}
+
+impl super::PostgresMetaController for KeyValueDatatypeBackend<PostgresRepository> {}
+
+impl crate::datatype::Storage for KeyValueDatatypeBackend<PostgresRepository> {
+    fn write_hunk(
+        &mut self,
+        repo: &Repository,
+        hunk: &Hunk,
+        payload: &Payload<Self::StateType, Self::DeltaType>,
+    ) -> Result<(), Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let entries: Box<dyn Iterator<Item = (&String, Option<&EntryValue>)>> = match hunk.representation {
+            RepresentationKind::State =>
+                match *payload {
+                    Payload::State(ref state) => Box::new(state.iter().map(|(k, v)| (k, Some(v)))),
+                    _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+                },
+            RepresentationKind::Delta =>
+                match *payload {
+                    Payload::Delta(ref delta) => Box::new(delta.iter().map(|(k, v)| (k, v.as_ref()))),
+                    _ => return Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+                },
+            _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+        };
+
+        let insert_entry = trans.prepare(r#"
+                INSERT INTO key_value_dtype_entry (hunk_id, key, json_value, bytes_value)
+                SELECT h.id, r.key, r.json_value, r.bytes_value
+                FROM (VALUES ($1::uuid, $2::bigint, $3::text, $4::jsonb, $5::bytea))
+                  AS r (uuid_, hash, key, json_value, bytes_value)
+                JOIN hunk h
+                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+            "#)?;
+        for (key, value) in entries {
+            let (json_value, bytes_value) = match value {
+                Some(EntryValue::Json(JsonValue(json))) => (Some(json), None),
+                Some(EntryValue::Bytes(bytes)) => (None, Some(bytes)),
+                None => (None, None),
+            };
+            insert_entry.execute(&[
+                &hunk.id.uuid,
+                &(hunk.id.hash as i64),
+                key,
+                &json_value,
+                &bytes_value,
+            ])?;
+        }
+
+        trans.set_commit();
+        Ok(())
+    }
+
+    fn read_hunk(
+        &self,
+        repo: &Repository,
+        hunk: &Hunk,
+    ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let entry_rows = trans.query(r#"
+                SELECT kv.key, kv.json_value, kv.bytes_value
+                FROM key_value_dtype_entry kv
+                JOIN hunk h
+                  ON (h.id = kv.hunk_id)
+                WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+        let entries = entry_rows.iter().map(|row| {
+            let key: String = row.get(0);
+            let value = match (row.get::<_, Option<serde_json::Value>>(1), row.get::<_, Option<Vec<u8>>>(2)) {
+                (Some(json), _) => Some(EntryValue::Json(JsonValue(json))),
+                (None, Some(bytes)) => Some(EntryValue::Bytes(bytes)),
+                (None, None) => None,
+            };
+            (key, value)
+        });
+
+        let payload = match hunk.representation {
+            RepresentationKind::State => Payload::State(entries
+                .map(|(key, value)| value.map(|value| (key, value))
+                    .ok_or_else(|| Error::Store("State hunk contains a tombstone".into())))
+                .collect::<Result<_, _>>()?),
+            RepresentationKind::Delta => Payload::Delta(entries.collect()),
+            _ => return Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+        };
+
+        Ok(payload)
+    }
+}
+
+impl Storage for KeyValueDatatypeBackend<PostgresRepository> {}
@@ -1,9 +1,70 @@
 pub use heraclitus_core::store::postgres::datatype::*;
 
 
+#[cfg(feature = "datatype-arrow")]
+pub mod arrow_table;
 pub mod artifact_graph;
 pub mod blob;
+pub mod json;
+pub mod key_value;
+pub mod ndarray;
+pub mod octree;
+pub mod payload;
 pub mod partitioning;
 pub mod producer;
 pub mod reference;
+pub mod table;
+pub mod text;
 pub mod tracking_branch_producer;
+
+
+/// Implement Postgres storage for a datatype backend by storing serialized
+/// payloads in the shared `hunk_payload` table, so that the datatype needs no
+/// tables or migrations of its own.
+///
+/// Payloads are stored as `jsonb` by default, or as bincode `bytea` with
+/// `default_postgres_store_backend!(Backend, bytea)`.
+#[macro_export]
+macro_rules! default_postgres_store_backend {
+    ( $store_backend:ident ) => {
+        $crate::default_postgres_store_backend!($store_backend, jsonb);
+    };
+    ( $store_backend:ident, jsonb ) => {
+        $crate::default_postgres_store_backend!(@impl $store_backend, Jsonb);
+    };
+    ( $store_backend:ident, bytea ) => {
+        $crate::default_postgres_store_backend!(@impl $store_backend, Bytea);
+    };
+    ( @impl $store_backend:ident, $encoding:ident ) => {
+        impl heraclitus::store::postgres::PostgresMigratable for
+            $store_backend<heraclitus::store::postgres::PostgresRepository> {}
+
+        impl heraclitus::store::postgres::datatype::PostgresMetaController for
+            $store_backend<heraclitus::store::postgres::PostgresRepository> {}
+
+        impl heraclitus::datatype::Storage for
+            $store_backend<heraclitus::store::postgres::PostgresRepository>
+        {
+            fn write_hunk(
+                &mut self,
+                repo: &heraclitus::repo::Repository,
+                hunk: &heraclitus::Hunk,
+                payload: &heraclitus::datatype::Payload<Self::StateType, Self::DeltaType>,
+            ) -> Result<(), heraclitus::Error> {
+                heraclitus::store::postgres::datatype::payload::write_payload(
+                    repo,
+                    hunk,
+                    payload,
+                    heraclitus::store::postgres::datatype::payload::PayloadEncoding::$encoding)
+            }
+
+            fn read_hunk(
+                &self,
+                repo: &heraclitus::repo::Repository,
+                hunk: &heraclitus::Hunk,
+            ) -> Result<heraclitus::datatype::Payload<Self::StateType, Self::DeltaType>, heraclitus::Error> {
+                heraclitus::store::postgres::datatype::payload::read_payload(repo, hunk)
+            }
+        }
+    };
+}
@@ -0,0 +1,159 @@
+use std::borrow::Borrow;
+
+use heraclitus_core::{
+    postgres,
+    schemer,
+    schemer_postgres,
+};
+use postgres::error::Error as PostgresError;
+use postgres::transaction::Transaction;
+use schemer::migration;
+use schemer_postgres::{PostgresAdapter, PostgresMigration};
+
+use crate::{
+    RepresentationKind,
+    Error,
+    Hunk,
+};
+use crate::datatype::{
+    Payload,
+};
+use crate::datatype::ndarray::{
+    Block,
+    BlockDelta,
+    NdArrayDatatypeBackend,
+    Storage,
+};
+use crate::repo::Repository;
+use crate::store::postgres::{PostgresMigratable, PostgresRepository};
+
+
+struct PGMigrationNdArray;
+migration!(
+    PGMigrationNdArray,
+    "b4e197c8-f1c7-48dc-a9a0-7120fee9c127",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "create ndarray tables");
+
+impl PostgresMigration for PGMigrationNdArray {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/ndarray_0001.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/ndarray_0001.down.sql"))
+    }
+}
+
+
+impl PostgresMigratable for NdArrayDatatypeBackend<PostgresRepository> {
+    fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+        vec![
+            Box::new(PGMigrationNdArray),
+        ]
+    }
+}
+
+impl super::PostgresMetaController for NdArrayDatatypeBackend<PostgresRepository> {}
+
+impl crate::datatype::Storage for NdArrayDatatypeBackend<PostgresRepository> {
+    fn write_hunk(
+        &mut self,
+        repo: &Repository,
+        hunk: &Hunk,
+        payload: &Payload<Self::StateType, Self::DeltaType>,
+    ) -> Result<(), Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        match hunk.representation {
+            RepresentationKind::State =>
+                match *payload {
+                    Payload::State(Block {ref metadata, ref block_coords, ref data}) => {
+                        let metadata = serde_json::to_value(metadata)
+                            .map_err(|e| Error::Store(e.to_string()))?;
+                        let block_coords = block_coords.iter().map(|c| *c as i64).collect::<Vec<i64>>();
+                        trans.execute(r#"
+                                INSERT INTO ndarray_dtype_block (hunk_id, metadata, block_coords, data)
+                                SELECT h.id, r.metadata, r.block_coords, r.data
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::jsonb, $4::bigint[], $5::bytea))
+                                  AS r (uuid_, hash, metadata, block_coords, data)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &metadata, &block_coords, data])?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+                },
+            RepresentationKind::Delta =>
+                match *payload {
+                    Payload::Delta(BlockDelta {ref offsets, ref data}) => {
+                        let offsets = offsets.iter().map(|o| *o as i64).collect::<Vec<i64>>();
+                        trans.execute(r#"
+                                INSERT INTO ndarray_dtype_delta (hunk_id, offsets, data)
+                                SELECT h.id, r.offsets, r.data
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::bigint[], $4::bytea))
+                                  AS r (uuid_, hash, offsets, data)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &offsets, data])?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+                },
+            _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+        }
+
+        trans.set_commit();
+        Ok(())
+    }
+
+    fn read_hunk(
+        &self,
+        repo: &Repository,
+        hunk: &Hunk,
+    ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let payload = match hunk.representation {
+            RepresentationKind::State => {
+                let block_rows = trans.query(r#"
+                        SELECT b.metadata, b.block_coords, b.data
+                        FROM ndarray_dtype_block b
+                        JOIN hunk h
+                          ON (h.id = b.hunk_id)
+                        WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                let block_row = block_rows.get(0);
+                Payload::State(Block {
+                    metadata: serde_json::from_value(block_row.get(0))
+                        .map_err(|e| Error::Store(e.to_string()))?,
+                    block_coords: block_row.get::<_, Vec<i64>>(1).into_iter().map(|c| c as u64).collect(),
+                    data: block_row.get(2),
+                })
+            },
+            RepresentationKind::Delta => {
+                let delta_rows = trans.query(r#"
+                        SELECT d.offsets, d.data
+                        FROM ndarray_dtype_delta d
+                        JOIN hunk h
+                          ON (h.id = d.hunk_id)
+                        WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                let delta_row = delta_rows.get(0);
+                Payload::Delta(BlockDelta {
+                    offsets: delta_row.get::<_, Vec<i64>>(0).into_iter().map(|o| o as u64).collect(),
+                    data: delta_row.get(1),
+                })
+            },
+            _ => return Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+        };
+
+        Ok(payload)
+    }
+}
+
+impl Storage for NdArrayDatatypeBackend<PostgresRepository> {}
@@ -0,0 +1,221 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use heraclitus_core::{
+    postgres,
+    schemer,
+    schemer_postgres,
+};
+use postgres::error::Error as PostgresError;
+use postgres::transaction::Transaction;
+use schemer::migration;
+use schemer_postgres::{PostgresAdapter, PostgresMigration};
+
+use crate::{
+    RepresentationKind,
+    Error,
+    Hunk,
+};
+use crate::datatype::{
+    Payload,
+};
+use crate::datatype::octree::{
+    OctreeDelta,
+    OctreeLeaf,
+    OctreeState,
+    OctreeStructure,
+    Point,
+    PointId,
+    PointOctreeBackend,
+    Storage,
+};
+use crate::datatype::partitioning::UNARY_PARTITION_INDEX;
+use crate::repo::Repository;
+use crate::store::postgres::{PostgresMigratable, PostgresRepository};
+
+
+struct PGMigrationOctree;
+migration!(
+    PGMigrationOctree,
+    "2d3ab9ff-8687-411b-95ac-1bc4b74dcffe",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "create octree tables");
+
+impl PostgresMigration for PGMigrationOctree {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/octree_0001.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/octree_0001.down.sql"))
+    }
+}
+
+
+impl PostgresMigratable for PointOctreeBackend<PostgresRepository> {
+    fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+        vec![
+            Box::new(PGMigrationOctree),
+        ]
+    }
+}
+
+impl super::PostgresMetaController for PointOctreeBackend<PostgresRepository> {}
+
+fn to_i64(ids: &BTreeSet<u64>) -> Vec<i64> {
+    ids.iter().map(|&id| id as i64).collect()
+}
+
+fn to_u64(ids: Vec<i64>) -> BTreeSet<u64> {
+    ids.into_iter().map(|id| id as u64).collect()
+}
+
+fn write_points<'a>(
+    trans: &Transaction,
+    hunk: &Hunk,
+    points: impl Iterator<Item = (&'a PointId, Option<&'a Point>)>,
+) -> Result<(), Error> {
+    let insert_point = trans.prepare(r#"
+            INSERT INTO octree_dtype_point (hunk_id, point_id, position)
+            SELECT h.id, r.point_id, r.position
+            FROM (VALUES ($1::uuid, $2::bigint, $3::bigint, $4::bigint[]))
+              AS r (uuid_, hash, point_id, position)
+            JOIN hunk h
+              ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+        "#)?;
+    for (&id, point) in points {
+        let position = point.map(|p| p.iter().map(|&c| i64::from(c)).collect::<Vec<i64>>());
+        insert_point.execute(&[&hunk.id.uuid, &(hunk.id.hash as i64), &(id as i64), &position])?;
+    }
+
+    Ok(())
+}
+
+impl crate::datatype::Storage for PointOctreeBackend<PostgresRepository> {
+    fn write_hunk(
+        &mut self,
+        repo: &Repository,
+        hunk: &Hunk,
+        payload: &Payload<Self::StateType, Self::DeltaType>,
+    ) -> Result<(), Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        match *payload {
+            Payload::State(OctreeState::Structure(OctreeStructure {depth, ref leaves})) => {
+                trans.execute(r#"
+                        INSERT INTO octree_dtype_structure (hunk_id, depth, leaves)
+                        SELECT h.id, r.depth, r.leaves
+                        FROM (VALUES ($1::uuid, $2::bigint, $3::integer, $4::bigint[]))
+                          AS r (uuid_, hash, depth, leaves)
+                        JOIN hunk h
+                          ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &(depth as i32), &to_i64(leaves)])?;
+            },
+            Payload::Delta(OctreeDelta::Structure {ref splits, ref merges}) => {
+                trans.execute(r#"
+                        INSERT INTO octree_dtype_structure_delta (hunk_id, splits, merges)
+                        SELECT h.id, r.splits, r.merges
+                        FROM (VALUES ($1::uuid, $2::bigint, $3::bigint[], $4::bigint[]))
+                          AS r (uuid_, hash, splits, merges)
+                        JOIN hunk h
+                          ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &to_i64(splits), &to_i64(merges)])?;
+            },
+            Payload::State(OctreeState::Leaf(OctreeLeaf {ref points})) =>
+                write_points(&trans, hunk, points.iter().map(|(id, p)| (id, Some(p))))?,
+            Payload::Delta(OctreeDelta::Leaf {ref inserted, ref removed}) =>
+                write_points(&trans, hunk, removed.iter().map(|id| (id, None))
+                    .chain(inserted.iter().map(|(id, p)| (id, Some(p)))))?,
+        }
+
+        trans.set_commit();
+        Ok(())
+    }
+
+    fn read_hunk(
+        &self,
+        repo: &Repository,
+        hunk: &Hunk,
+    ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        if hunk.partition.index == UNARY_PARTITION_INDEX {
+            return match hunk.representation {
+                RepresentationKind::State => {
+                    let structure_rows = trans.query(r#"
+                            SELECT o.depth, o.leaves
+                            FROM octree_dtype_structure o
+                            JOIN hunk h
+                              ON (h.id = o.hunk_id)
+                            WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                        "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                    let structure_row = structure_rows.get(0);
+                    Ok(Payload::State(OctreeState::Structure(OctreeStructure {
+                        depth: structure_row.get::<_, i32>(0) as u32,
+                        leaves: to_u64(structure_row.get(1)),
+                    })))
+                },
+                RepresentationKind::Delta => {
+                    let delta_rows = trans.query(r#"
+                            SELECT o.splits, o.merges
+                            FROM octree_dtype_structure_delta o
+                            JOIN hunk h
+                              ON (h.id = o.hunk_id)
+                            WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                        "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                    let delta_row = delta_rows.get(0);
+                    Ok(Payload::Delta(OctreeDelta::Structure {
+                        splits: to_u64(delta_row.get(0)),
+                        merges: to_u64(delta_row.get(1)),
+                    }))
+                },
+                _ => Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+            };
+        }
+
+        let point_rows = trans.query(r#"
+                SELECT o.point_id, o.position
+                FROM octree_dtype_point o
+                JOIN hunk h
+                  ON (h.id = o.hunk_id)
+                WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+        let points = point_rows.iter().map(|row| {
+            let id = row.get::<_, i64>(0) as PointId;
+            let position = row.get::<_, Option<Vec<i64>>>(1)
+                .map(|p| [p[0] as u32, p[1] as u32, p[2] as u32]);
+            (id, position)
+        });
+
+        let payload = match hunk.representation {
+            RepresentationKind::State => Payload::State(OctreeState::Leaf(OctreeLeaf {
+                points: points
+                    .map(|(id, position)| position.map(|p| (id, p))
+                        .ok_or_else(|| Error::Store("State hunk contains a point removal".into())))
+                    .collect::<Result<_, _>>()?,
+            })),
+            RepresentationKind::Delta => {
+                let mut inserted = BTreeMap::new();
+                let mut removed = BTreeSet::new();
+                for (id, position) in points {
+                    match position {
+                        Some(p) => { inserted.insert(id, p); },
+                        None => { removed.insert(id); },
+                    }
+                }
+                Payload::Delta(OctreeDelta::Leaf {inserted, removed})
+            },
+            _ => return Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+        };
+
+        Ok(payload)
+    }
+}
+
+impl Storage for PointOctreeBackend<PostgresRepository> {}
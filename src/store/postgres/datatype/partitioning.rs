@@ -40,6 +40,7 @@ pub mod arbitrary {
     };
     use crate::datatype::partitioning::arbitrary::{
         ArbitraryPartitioningBackend,
+        ArbitraryPartitioningDelta,
         ArbitraryPartitioningState,
         Storage,
     };
@@ -63,10 +64,29 @@ pub mod arbitrary {
     }
 
 
+    struct PGMigrationArbitraryPartitioningDelta;
+    migration!(
+        PGMigrationArbitraryPartitioningDelta,
+        "8c1f6d81-3c50-43fa-a62e-55b97e0024e4",
+        ["bfef8343-453c-463f-a3c6-f3b957e28292",],
+        "key arbitrary_partitioning by hunk and create arbitrary_partitioning_delta table");
+
+    impl PostgresMigration for PGMigrationArbitraryPartitioningDelta {
+        fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+            transaction.batch_execute(include_str!("sql/arbitrary_partitioning_0002.up.sql"))
+        }
+
+        fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+            transaction.batch_execute(include_str!("sql/arbitrary_partitioning_0002.down.sql"))
+        }
+    }
+
+
     impl PostgresMigratable for ArbitraryPartitioningBackend<PostgresRepository> {
         fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
             vec![
                 Box::new(PGMigrationArbitraryPartitioning),
+                Box::new(PGMigrationArbitraryPartitioningDelta),
             ]
         }
     }
@@ -94,16 +114,33 @@ pub mod arbitrary {
                         let db_partition_ids = partition_ids.iter().map(|p| *p as i64).collect::<Vec<i64>>();
 
                         trans.execute(r#"
-                                INSERT INTO arbitrary_partitioning (version_id, partition_ids)
-                                SELECT v.id, r.partitioning_ids
-                                FROM (VALUES ($2::bigint[]))
-                                  AS r (partitioning_ids)
-                                JOIN version v
-                                  ON (v.uuid_ = $1::uuid);
-                            "#, &[&hunk.version.id.uuid, &db_partition_ids])?;
+                                INSERT INTO arbitrary_partitioning (hunk_id, partition_ids)
+                                SELECT h.id, r.partition_ids
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::bigint[]))
+                                  AS r (uuid_, hash, partition_ids)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &db_partition_ids])?;
                     }
                     _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
                 },
+            RepresentationKind::Delta =>
+                match *payload {
+                    Payload::Delta(ArbitraryPartitioningDelta {ref additions, ref removals}) => {
+                        let db_additions = additions.iter().map(|p| *p as i64).collect::<Vec<i64>>();
+                        let db_removals = removals.iter().map(|p| *p as i64).collect::<Vec<i64>>();
+
+                        trans.execute(r#"
+                                INSERT INTO arbitrary_partitioning_delta (hunk_id, additions, removals)
+                                SELECT h.id, r.additions, r.removals
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::bigint[], $4::bigint[]))
+                                  AS r (uuid_, hash, additions, removals)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &db_additions, &db_removals])?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+                },
             _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
         }
 
@@ -121,20 +158,281 @@ pub mod arbitrary {
             let conn = rc.conn()?;
             let trans = conn.transaction()?;
 
-            let partition_ids_row = trans.query(r#"
-                    SELECT partition_ids
-                    FROM arbitrary_partitioning
-                    JOIN version ON id = version_id
-                    WHERE uuid_ = $1::uuid;
-                "#, &[&hunk.version.id.uuid])?;
-            let partition_ids = partition_ids_row.get(0).get::<_, Vec<i64>>(0)
-                .into_iter()
+            let to_partition_ids = |ids: Vec<i64>| ids.into_iter()
                 .map(|p| p as PartitionIndex)
                 .collect();
 
-            Ok(Payload::State(ArbitraryPartitioningState {partition_ids}))
+            match hunk.representation {
+                RepresentationKind::State => {
+                    let partition_ids_row = trans.query(r#"
+                            SELECT a.partition_ids
+                            FROM arbitrary_partitioning a
+                            JOIN hunk h
+                              ON (h.id = a.hunk_id)
+                            WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                        "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                    let partition_ids = to_partition_ids(partition_ids_row.get(0).get(0));
+
+                    Ok(Payload::State(ArbitraryPartitioningState {partition_ids}))
+                },
+                RepresentationKind::Delta => {
+                    let delta_rows = trans.query(r#"
+                            SELECT a.additions, a.removals
+                            FROM arbitrary_partitioning_delta a
+                            JOIN hunk h
+                              ON (h.id = a.hunk_id)
+                            WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                        "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                    let delta_row = delta_rows.get(0);
+
+                    Ok(Payload::Delta(ArbitraryPartitioningDelta {
+                        additions: to_partition_ids(delta_row.get(0)),
+                        removals: to_partition_ids(delta_row.get(1)),
+                    }))
+                },
+                _ => Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+            }
         }
     }
 
     impl Storage for ArbitraryPartitioningBackend<PostgresRepository> {}
 }
+
+
+pub mod grid {
+    use super::*;
+
+    use std::borrow::Borrow;
+
+    use crate::{
+        Hunk,
+        RepresentationKind,
+    };
+    use crate::datatype::{
+        Payload,
+    };
+    use crate::datatype::partitioning::grid::{
+        GridPartitioningBackend,
+        GridPartitioningState,
+        Storage,
+    };
+
+
+    struct PGMigrationGridPartitioning;
+    migration!(
+        PGMigrationGridPartitioning,
+        "b1d5a32f-70ec-415e-8a4d-f6854c4d0fe6",
+        ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+        "create grid_partitioning table");
+
+    impl PostgresMigration for PGMigrationGridPartitioning {
+        fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+            transaction.batch_execute(include_str!("sql/grid_partitioning_0001.up.sql"))
+        }
+
+        fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+            transaction.batch_execute(include_str!("sql/grid_partitioning_0001.down.sql"))
+        }
+    }
+
+
+    impl PostgresMigratable for GridPartitioningBackend<PostgresRepository> {
+        fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+            vec![
+                Box::new(PGMigrationGridPartitioning),
+            ]
+        }
+    }
+
+    impl PostgresMetaController for GridPartitioningBackend<PostgresRepository> {}
+
+    impl crate::datatype::Storage for GridPartitioningBackend<PostgresRepository> {
+        fn write_hunk(
+            &mut self,
+            repo: &Repository,
+            hunk: &Hunk,
+            payload: &Payload<Self::StateType, Self::DeltaType>,
+        ) -> Result<(), Error> {
+            let rc: &PostgresRepository = repo.borrow();
+
+            let conn = rc.conn()?;
+            let trans = conn.transaction()?;
+
+            match hunk.representation {
+                RepresentationKind::State =>
+                    match *payload {
+                        Payload::State(ref state) => {
+                            state.validate()?;
+                            let extent = state.extent.iter().map(|e| *e as i64).collect::<Vec<i64>>();
+                            let block_size = state.block_size.iter().map(|b| *b as i64).collect::<Vec<i64>>();
+
+                            trans.execute(r#"
+                                    INSERT INTO grid_partitioning (hunk_id, extent, block_size)
+                                    SELECT h.id, r.extent, r.block_size
+                                    FROM (VALUES ($1::uuid, $2::bigint, $3::bigint[], $4::bigint[]))
+                                      AS r (uuid_, hash, extent, block_size)
+                                    JOIN hunk h
+                                      ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                                "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &extent, &block_size])?;
+                        }
+                        _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+                    },
+                _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+            }
+
+            trans.set_commit();
+            Ok(())
+        }
+
+        fn read_hunk(
+            &self,
+            repo: &Repository,
+            hunk: &Hunk,
+        ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+            let rc: &PostgresRepository = repo.borrow();
+
+            let conn = rc.conn()?;
+            let trans = conn.transaction()?;
+
+            let grid_rows = trans.query(r#"
+                    SELECT g.extent, g.block_size
+                    FROM grid_partitioning g
+                    JOIN hunk h
+                      ON (h.id = g.hunk_id)
+                    WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+            let grid_row = grid_rows.get(0);
+            let to_u64 = |v: Vec<i64>| v.into_iter().map(|x| x as u64).collect();
+
+            Ok(Payload::State(GridPartitioningState {
+                extent: to_u64(grid_row.get(0)),
+                block_size: to_u64(grid_row.get(1)),
+            }))
+        }
+    }
+
+    impl Storage for GridPartitioningBackend<PostgresRepository> {}
+}
+
+
+pub mod hash {
+    use super::*;
+
+    use std::borrow::Borrow;
+    use std::collections::BTreeMap;
+
+    use crate::{
+        Hunk,
+        RepresentationKind,
+    };
+    use crate::datatype::{
+        Payload,
+    };
+    use crate::datatype::partitioning::hash::{
+        HashPartitioningBackend,
+        HashPartitioningState,
+        Storage,
+    };
+
+
+    struct PGMigrationHashPartitioning;
+    migration!(
+        PGMigrationHashPartitioning,
+        "ce55b79f-2897-45d8-b9fd-7d8152c1e22b",
+        ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+        "create hash_partitioning_range table");
+
+    impl PostgresMigration for PGMigrationHashPartitioning {
+        fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+            transaction.batch_execute(include_str!("sql/hash_partitioning_0001.up.sql"))
+        }
+
+        fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+            transaction.batch_execute(include_str!("sql/hash_partitioning_0001.down.sql"))
+        }
+    }
+
+
+    impl PostgresMigratable for HashPartitioningBackend<PostgresRepository> {
+        fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+            vec![
+                Box::new(PGMigrationHashPartitioning),
+            ]
+        }
+    }
+
+    impl PostgresMetaController for HashPartitioningBackend<PostgresRepository> {}
+
+    impl crate::datatype::Storage for HashPartitioningBackend<PostgresRepository> {
+        fn write_hunk(
+            &mut self,
+            repo: &Repository,
+            hunk: &Hunk,
+            payload: &Payload<Self::StateType, Self::DeltaType>,
+        ) -> Result<(), Error> {
+            let rc: &PostgresRepository = repo.borrow();
+
+            let conn = rc.conn()?;
+            let trans = conn.transaction()?;
+
+            let ranges = match hunk.representation {
+                RepresentationKind::State =>
+                    match *payload {
+                        Payload::State(HashPartitioningState {ref ranges}) => ranges,
+                        _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+                    },
+                RepresentationKind::Delta =>
+                    match *payload {
+                        Payload::Delta(ref ranges) => ranges,
+                        _ => return Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+                    },
+                _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+            };
+
+            let insert_range = trans.prepare(r#"
+                    INSERT INTO hash_partitioning_range (hunk_id, range_start, partition_id)
+                    SELECT h.id, r.range_start, r.partition_id
+                    FROM (VALUES ($1::uuid, $2::bigint, $3::bigint, $4::bigint))
+                      AS r (uuid_, hash, range_start, partition_id)
+                    JOIN hunk h
+                      ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                "#)?;
+            for (&start, &p_idx) in ranges {
+                insert_range.execute(&[&hunk.id.uuid, &(hunk.id.hash as i64), &(start as i64), &(p_idx as i64)])?;
+            }
+
+            trans.set_commit();
+            Ok(())
+        }
+
+        fn read_hunk(
+            &self,
+            repo: &Repository,
+            hunk: &Hunk,
+        ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+            let rc: &PostgresRepository = repo.borrow();
+
+            let conn = rc.conn()?;
+            let trans = conn.transaction()?;
+
+            let range_rows = trans.query(r#"
+                    SELECT r.range_start, r.partition_id
+                    FROM hash_partitioning_range r
+                    JOIN hunk h
+                      ON (h.id = r.hunk_id)
+                    WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+            let ranges: BTreeMap<u64, u64> = range_rows.iter()
+                .map(|row| (row.get::<_, i64>(0) as u64, row.get::<_, i64>(1) as u64))
+                .collect();
+
+            match hunk.representation {
+                RepresentationKind::State => Ok(Payload::State(HashPartitioningState {ranges})),
+                RepresentationKind::Delta => Ok(Payload::Delta(ranges)),
+                _ => Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+            }
+        }
+    }
+
+    impl Storage for HashPartitioningBackend<PostgresRepository> {}
+}
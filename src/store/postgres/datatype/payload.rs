@@ -0,0 +1,130 @@
+//! Storage of serializable payloads in the `hunk_payload` table shared by all
+//! datatypes, for datatypes without their own tables.
+//!
+//! Datatypes usually do not use this module directly, but through
+//! `default_postgres_store_backend!` or `#[derive(Datatype)]`.
+
+use std::borrow::Borrow;
+
+use serde::{
+    de::DeserializeOwned,
+    Serialize,
+};
+
+use crate::{
+    Error,
+    Hunk,
+    RepresentationKind,
+};
+use crate::datatype::Payload;
+use crate::repo::Repository;
+use crate::store::postgres::PostgresRepository;
+
+
+/// How payloads are serialized in the `hunk_payload` table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    /// JSON documents in the `document` column, which can be queried.
+    Jsonb,
+    /// Bincode in the `data` column, which is more compact for binary data.
+    Bytea,
+}
+
+fn payload_value<'a, S, D>(
+    hunk: &Hunk,
+    payload: &'a Payload<S, D>,
+) -> Result<Payload<&'a S, &'a D>, Error> {
+    match (hunk.representation, payload) {
+        (RepresentationKind::State, Payload::State(state)) => Ok(Payload::State(state)),
+        (RepresentationKind::State, _) =>
+            Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+        (RepresentationKind::Delta, Payload::Delta(delta)) => Ok(Payload::Delta(delta)),
+        (RepresentationKind::Delta, _) =>
+            Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+        _ => Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+    }
+}
+
+/// Write a hunk payload with the given encoding.
+pub fn write_payload<S: Serialize, D: Serialize>(
+    repo: &Repository,
+    hunk: &Hunk,
+    payload: &Payload<S, D>,
+    encoding: PayloadEncoding,
+) -> Result<(), Error> {
+    let value = payload_value(hunk, payload)?;
+    let (document, data) = match encoding {
+        PayloadEncoding::Jsonb => {
+            let document = match value {
+                Payload::State(state) => serde_json::to_value(state),
+                Payload::Delta(delta) => serde_json::to_value(delta),
+            }.map_err(|e| Error::Store(e.to_string()))?;
+            (Some(document), None)
+        },
+        PayloadEncoding::Bytea => {
+            let data = match value {
+                Payload::State(state) => bincode::serialize(state),
+                Payload::Delta(delta) => bincode::serialize(delta),
+            }.map_err(|e| Error::Store(e.to_string()))?;
+            (None, Some(data))
+        },
+    };
+
+    let rc: &PostgresRepository = repo.borrow();
+
+    let conn = rc.conn()?;
+    let trans = conn.transaction()?;
+
+    trans.execute(r#"
+            INSERT INTO hunk_payload (hunk_id, document, data)
+            SELECT h.id, r.document, r.data
+            FROM (VALUES ($1::uuid, $2::bigint, $3::jsonb, $4::bytea))
+              AS r (uuid_, hash, document, data)
+            JOIN hunk h
+              ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+        "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &document, &data])?;
+
+    trans.set_commit();
+    Ok(())
+}
+
+/// Read a hunk payload written by `write_payload` with either encoding.
+pub fn read_payload<S: DeserializeOwned, D: DeserializeOwned>(
+    repo: &Repository,
+    hunk: &Hunk,
+) -> Result<Payload<S, D>, Error> {
+    let rc: &PostgresRepository = repo.borrow();
+
+    let conn = rc.conn()?;
+    let trans = conn.transaction()?;
+
+    let payload_rows = trans.query(r#"
+            SELECT p.document, p.data
+            FROM hunk_payload p
+            JOIN hunk h
+              ON (h.id = p.hunk_id)
+            WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+        "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+    if payload_rows.is_empty() {
+        return Err(Error::Store("Hunk has no stored payload".into()));
+    }
+    let payload_row = payload_rows.get(0);
+    let document: Option<serde_json::Value> = payload_row.get(0);
+    let data: Option<Vec<u8>> = payload_row.get(1);
+
+    let payload = match (hunk.representation, document, data) {
+        (RepresentationKind::State, Some(document), _) =>
+            serde_json::from_value(document).map(Payload::State).map_err(|e| e.to_string()),
+        (RepresentationKind::Delta, Some(document), _) =>
+            serde_json::from_value(document).map(Payload::Delta).map_err(|e| e.to_string()),
+        (RepresentationKind::State, None, Some(data)) =>
+            bincode::deserialize(&data).map(Payload::State).map_err(|e| e.to_string()),
+        (RepresentationKind::Delta, None, Some(data)) =>
+            bincode::deserialize(&data).map(Payload::Delta).map_err(|e| e.to_string()),
+        (RepresentationKind::State, None, None) | (RepresentationKind::Delta, None, None) =>
+            return Err(Error::Store("Hunk has no stored payload".into())),
+        _ => return Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+    }.map_err(Error::Store)?;
+
+    Ok(payload)
+}
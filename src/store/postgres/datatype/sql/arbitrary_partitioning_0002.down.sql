@@ -0,0 +1,22 @@
+DROP TABLE arbitrary_partitioning_delta;
+
+-- Snapshot states can not be keyed by version, so are dropped.
+DELETE FROM arbitrary_partitioning a
+USING hunk h
+WHERE h.id = a.hunk_id
+  AND EXISTS (
+    SELECT 1 FROM hunk d
+    WHERE d.version_id = h.version_id AND d.representation <> 'state');
+
+ALTER TABLE arbitrary_partitioning
+ADD COLUMN version_id bigint REFERENCES version (id) DEFERRABLE INITIALLY IMMEDIATE;
+
+UPDATE arbitrary_partitioning a
+SET version_id = h.version_id
+FROM hunk h
+WHERE h.id = a.hunk_id;
+
+ALTER TABLE arbitrary_partitioning
+DROP COLUMN hunk_id,
+ALTER COLUMN version_id SET NOT NULL,
+ADD PRIMARY KEY (version_id);
@@ -0,0 +1,22 @@
+-- States are keyed by hunk, so that a version may have a snapshot state hunk
+-- alongside its delta hunk.
+ALTER TABLE arbitrary_partitioning
+ADD COLUMN hunk_id bigint REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE;
+
+UPDATE arbitrary_partitioning a
+SET hunk_id = h.id
+FROM hunk h
+WHERE h.version_id = a.version_id AND h.representation = 'state';
+
+ALTER TABLE arbitrary_partitioning
+DROP COLUMN version_id,
+ALTER COLUMN hunk_id SET NOT NULL,
+ADD PRIMARY KEY (hunk_id);
+
+CREATE TABLE arbitrary_partitioning_delta (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  additions bigint[] NOT NULL,
+  removals bigint[] NOT NULL
+) WITH (
+  OIDS=FALSE
+);
@@ -0,0 +1,2 @@
+DROP TABLE arrow_dtype_state;
+DROP TABLE arrow_dtype_delta;
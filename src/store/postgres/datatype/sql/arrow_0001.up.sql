@@ -0,0 +1,16 @@
+CREATE TABLE arrow_dtype_state (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  -- Arrow IPC stream.
+  batches bytea NOT NULL
+) WITH (
+  OIDS=FALSE
+);
+
+CREATE TABLE arrow_dtype_delta (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  deletions jsonb NOT NULL,
+  -- Arrow IPC stream.
+  appended bytea NOT NULL
+) WITH (
+  OIDS=FALSE
+);
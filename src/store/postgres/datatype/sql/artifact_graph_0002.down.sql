@@ -0,0 +1,4 @@
+ALTER TABLE hunk
+DROP COLUMN partition_kind;
+
+DROP TYPE partition_kind;
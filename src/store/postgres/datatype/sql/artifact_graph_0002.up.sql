@@ -0,0 +1,9 @@
+CREATE TYPE partition_kind AS ENUM (
+  'leaf',
+  'neighborhood',
+  'level',
+  'arbitrary'
+);
+
+ALTER TABLE hunk
+ADD COLUMN partition_kind partition_kind NOT NULL DEFAULT 'leaf';
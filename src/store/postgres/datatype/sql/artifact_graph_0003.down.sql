@@ -0,0 +1 @@
+DROP TABLE hunk_payload;
@@ -0,0 +1,9 @@
+-- Payloads are stored either as a JSON document or as binary data.
+CREATE TABLE hunk_payload (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  document jsonb,
+  data bytea,
+  CONSTRAINT hunk_payload_encoding_check CHECK ((document IS NULL) <> (data IS NULL))
+) WITH (
+  OIDS=FALSE
+);
@@ -0,0 +1,3 @@
+ALTER TABLE version_parent
+DROP CONSTRAINT version_parent_ordinal_unique,
+DROP COLUMN ordinal;
@@ -0,0 +1,17 @@
+ALTER TABLE version_parent
+ADD COLUMN ordinal smallint;
+
+-- The original order of existing parents is unknown, so parents are ordered
+-- by ID to give each child exactly one first parent.
+UPDATE version_parent vp
+SET ordinal = o.ordinal
+FROM (
+    SELECT parent_id, child_id,
+      row_number() OVER (PARTITION BY child_id ORDER BY parent_id) - 1 AS ordinal
+    FROM version_parent
+  ) o
+WHERE vp.parent_id = o.parent_id AND vp.child_id = o.child_id;
+
+ALTER TABLE version_parent
+ALTER COLUMN ordinal SET NOT NULL,
+ADD CONSTRAINT version_parent_ordinal_unique UNIQUE (child_id, ordinal);
@@ -0,0 +1,7 @@
+CREATE TABLE snapshot_artifact (
+  artifact_id bigint PRIMARY KEY REFERENCES artifact (id) DEFERRABLE INITIALLY IMMEDIATE,
+  max_deltas integer,
+  max_bytes bigint
+) WITH (
+  OIDS=FALSE
+);
@@ -0,0 +1 @@
+DROP TABLE grid_partitioning;
@@ -0,0 +1,7 @@
+CREATE TABLE grid_partitioning (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  extent bigint[] NOT NULL,
+  block_size bigint[] NOT NULL
+) WITH (
+  OIDS=FALSE
+);
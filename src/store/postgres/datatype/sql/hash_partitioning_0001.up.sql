@@ -0,0 +1,9 @@
+-- Hash ranges of state hunks, or new ranges of delta hunks.
+CREATE TABLE hash_partitioning_range (
+  hunk_id bigint NOT NULL REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  range_start bigint NOT NULL,
+  partition_id bigint NOT NULL,
+  PRIMARY KEY (hunk_id, range_start)
+) WITH (
+  OIDS=FALSE
+);
@@ -0,0 +1,2 @@
+DROP TABLE json_dtype_state;
+DROP TABLE json_dtype_delta;
@@ -0,0 +1,13 @@
+CREATE TABLE json_dtype_state (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  document jsonb NOT NULL
+) WITH (
+  OIDS=FALSE
+);
+
+CREATE TABLE json_dtype_delta (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  patch jsonb NOT NULL
+) WITH (
+  OIDS=FALSE
+);
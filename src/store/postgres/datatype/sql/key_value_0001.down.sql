@@ -0,0 +1 @@
+DROP TABLE key_value_dtype_entry;
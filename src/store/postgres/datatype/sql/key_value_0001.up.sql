@@ -0,0 +1,11 @@
+CREATE TABLE key_value_dtype_entry (
+  hunk_id bigint NOT NULL REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  key text NOT NULL,
+  -- At most one of these is non-null. Both are null for delta tombstones.
+  json_value jsonb,
+  bytes_value bytea,
+  PRIMARY KEY (hunk_id, key),
+  CHECK (json_value IS NULL OR bytes_value IS NULL)
+) WITH (
+  OIDS=FALSE
+);
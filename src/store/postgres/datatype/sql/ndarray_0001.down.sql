@@ -0,0 +1,2 @@
+DROP TABLE ndarray_dtype_block;
+DROP TABLE ndarray_dtype_delta;
@@ -0,0 +1,16 @@
+CREATE TABLE ndarray_dtype_block (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  metadata jsonb NOT NULL,
+  block_coords bigint[] NOT NULL,
+  data bytea NOT NULL
+) WITH (
+  OIDS=FALSE
+);
+
+CREATE TABLE ndarray_dtype_delta (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  offsets bigint[] NOT NULL,
+  data bytea NOT NULL
+) WITH (
+  OIDS=FALSE
+);
@@ -0,0 +1,3 @@
+DROP TABLE octree_dtype_structure;
+DROP TABLE octree_dtype_structure_delta;
+DROP TABLE octree_dtype_point;
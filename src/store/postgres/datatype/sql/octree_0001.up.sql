@@ -0,0 +1,25 @@
+CREATE TABLE octree_dtype_structure (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  depth integer NOT NULL,
+  leaves bigint[] NOT NULL
+) WITH (
+  OIDS=FALSE
+);
+
+CREATE TABLE octree_dtype_structure_delta (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  splits bigint[] NOT NULL,
+  merges bigint[] NOT NULL
+) WITH (
+  OIDS=FALSE
+);
+
+-- Points of leaf hunks. A NULL position is a removal in a delta hunk.
+CREATE TABLE octree_dtype_point (
+  hunk_id bigint NOT NULL REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  point_id bigint NOT NULL,
+  position bigint[],
+  PRIMARY KEY (hunk_id, point_id)
+) WITH (
+  OIDS=FALSE
+);
@@ -0,0 +1,17 @@
+-- Drop the per-artifact row tables created by `init_artifact`.
+DO $$
+DECLARE
+  rows_table text;
+BEGIN
+  FOR rows_table IN
+    SELECT tablename
+    FROM pg_tables
+    WHERE schemaname = current_schema()
+      AND tablename LIKE 'table\_dtype\_rows\_%'
+  LOOP
+    EXECUTE format('DROP TABLE %I;', rows_table);
+  END LOOP;
+END
+$$;
+
+DROP TABLE table_dtype_schema;
@@ -0,0 +1,9 @@
+-- Rows of each table artifact are stored in a typed table per artifact,
+-- created when the artifact is initialized. This table records the schema
+-- of each hunk's rows.
+CREATE TABLE table_dtype_schema (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  schema jsonb NOT NULL
+) WITH (
+  OIDS=FALSE
+);
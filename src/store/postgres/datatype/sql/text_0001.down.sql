@@ -0,0 +1,2 @@
+DROP TABLE text_dtype_state;
+DROP TABLE text_dtype_delta;
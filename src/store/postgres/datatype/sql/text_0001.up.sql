@@ -0,0 +1,17 @@
+CREATE TABLE text_dtype_state (
+  hunk_id bigint PRIMARY KEY REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  text text NOT NULL
+) WITH (
+  OIDS=FALSE
+);
+
+CREATE TABLE text_dtype_delta (
+  hunk_id bigint NOT NULL REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+  ordinal integer NOT NULL,
+  start_line bigint NOT NULL,
+  removed bigint NOT NULL,
+  inserted text[] NOT NULL,
+  PRIMARY KEY (hunk_id, ordinal)
+) WITH (
+  OIDS=FALSE
+);
@@ -0,0 +1,395 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use heraclitus_core::{
+    postgres,
+    schemer,
+    schemer_postgres,
+};
+use postgres::error::Error as PostgresError;
+use postgres::rows::Row as PostgresRow;
+use postgres::transaction::Transaction;
+use postgres::types::ToSql;
+use schemer::migration;
+use schemer_postgres::{PostgresAdapter, PostgresMigration};
+
+use crate::{
+    Artifact,
+    RepresentationKind,
+    Error,
+    Hunk,
+};
+use crate::datatype::{
+    Payload,
+};
+use crate::datatype::artifact_graph::ArtifactMeta;
+use crate::datatype::table::{
+    CellValue,
+    Column,
+    ColumnType,
+    Row,
+    RowOperation,
+    TableDatatypeBackend,
+    TableDelta,
+    TableSchema,
+    TableState,
+    Storage,
+};
+use crate::repo::Repository;
+use crate::store::postgres::{PostgresMigratable, PostgresRepository};
+
+
+struct PGMigrationTable;
+migration!(
+    PGMigrationTable,
+    "e97fab49-6db7-4c9c-845c-8b506047ca05",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "create table schema table");
+
+impl PostgresMigration for PGMigrationTable {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/table_0001.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/table_0001.down.sql"))
+    }
+}
+
+
+impl PostgresMigratable for TableDatatypeBackend<PostgresRepository> {
+    fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+        vec![
+            Box::new(PGMigrationTable),
+        ]
+    }
+}
+
+impl super::PostgresMetaController for TableDatatypeBackend<PostgresRepository> {}
+
+/// Name of the typed table holding rows for a table artifact.
+fn rows_table(artifact: &Artifact) -> String {
+    format!("table_dtype_rows_{}", artifact.id.uuid.simple())
+}
+
+fn sql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Boolean => "boolean",
+        ColumnType::Integer => "bigint",
+        ColumnType::Float => "double precision",
+        ColumnType::Text => "text",
+        ColumnType::Bytes => "bytea",
+    }
+}
+
+/// Name of the database column for a schema column. The type is part of the
+/// name so that schemas of different versions may reuse column names with
+/// different types.
+fn column_name(column: &Column) -> String {
+    let tag = match column.column_type {
+        ColumnType::Boolean => "bool",
+        ColumnType::Integer => "int8",
+        ColumnType::Float => "float8",
+        ColumnType::Text => "text",
+        ColumnType::Bytes => "bytea",
+    };
+    format!("{}__{}", column.name, tag)
+}
+
+/// Quoted identifier of the database column for a schema column.
+fn column_ident(column: &Column) -> String {
+    format!("\"{}\"", column_name(column).replace('"', "\"\""))
+}
+
+/// Maximum length in bytes of a Postgres identifier. Longer identifiers are
+/// silently truncated.
+const MAX_IDENTIFIER_BYTES: usize = 63;
+
+/// Check that a schema's columns map to distinct database columns whose names
+/// Postgres stores without truncation.
+fn validate_column_names(schema: &TableSchema) -> Result<(), Error> {
+    schema.validate()?;
+
+    let mut names = BTreeSet::new();
+    for column in &schema.columns {
+        let name = column_name(column);
+        if name.len() > MAX_IDENTIFIER_BYTES {
+            return Err(Error::Store(format!(
+                "Table column name {} is longer than {} bytes with its type", column.name, MAX_IDENTIFIER_BYTES)));
+        }
+        if name.contains('\0') {
+            return Err(Error::Store(format!("Table column name {} contains a null byte", column.name)));
+        }
+        if !names.insert(name) {
+            return Err(Error::Store(format!("Table column {} collides with another column", column.name)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Add columns of `schema` missing from an artifact's rows table. Altering
+/// the table takes an exclusive lock, so is only done when a schema
+/// introduces new columns.
+fn add_schema_columns(
+    trans: &Transaction,
+    table: &str,
+    schema: &TableSchema,
+) -> Result<(), Error> {
+    let existing = trans.query(r#"
+            SELECT column_name::text
+            FROM information_schema.columns
+            WHERE table_schema = current_schema() AND table_name = $1::text;
+        "#, &[&table])?
+        .iter()
+        .map(|row| row.get::<_, String>(0))
+        .collect::<BTreeSet<_>>();
+
+    for column in &schema.columns {
+        if !existing.contains(&column_name(column)) {
+            trans.batch_execute(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {};",
+                table, column_ident(column), sql_type(column.column_type)))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn cell_to_sql(column: &Column, value: &CellValue) -> Result<Box<dyn ToSql>, Error> {
+    Ok(match (column.column_type, value) {
+        (ColumnType::Boolean, CellValue::Null) => Box::new(None::<bool>),
+        (ColumnType::Integer, CellValue::Null) => Box::new(None::<i64>),
+        (ColumnType::Float, CellValue::Null) => Box::new(None::<f64>),
+        (ColumnType::Text, CellValue::Null) => Box::new(None::<String>),
+        (ColumnType::Bytes, CellValue::Null) => Box::new(None::<Vec<u8>>),
+        (ColumnType::Boolean, CellValue::Boolean(v)) => Box::new(*v),
+        (ColumnType::Integer, CellValue::Integer(v)) => Box::new(*v),
+        (ColumnType::Float, CellValue::Float(v)) => Box::new(*v),
+        (ColumnType::Text, CellValue::Text(v)) => Box::new(v.clone()),
+        (ColumnType::Bytes, CellValue::Bytes(v)) => Box::new(v.clone()),
+        _ => return Err(Error::Store(format!("Value does not match type of column {}", column.name))),
+    })
+}
+
+fn cell_from_sql(row: &PostgresRow, idx: usize, column: &Column) -> CellValue {
+    match column.column_type {
+        ColumnType::Boolean => row.get::<_, Option<bool>>(idx).map(CellValue::Boolean),
+        ColumnType::Integer => row.get::<_, Option<i64>>(idx).map(CellValue::Integer),
+        ColumnType::Float => row.get::<_, Option<f64>>(idx).map(CellValue::Float),
+        ColumnType::Text => row.get::<_, Option<String>>(idx).map(CellValue::Text),
+        ColumnType::Bytes => row.get::<_, Option<Vec<u8>>>(idx).map(CellValue::Bytes),
+    }.unwrap_or(CellValue::Null)
+}
+
+impl ArtifactMeta for TableDatatypeBackend<PostgresRepository> {
+    fn init_artifact(
+        &mut self,
+        repo: &Repository,
+        artifact: &Artifact,
+    ) -> Result<(), Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        conn.batch_execute(&format!(r#"
+                CREATE TABLE {} (
+                  hunk_id bigint NOT NULL REFERENCES hunk (id) DEFERRABLE INITIALLY IMMEDIATE,
+                  ordinal integer NOT NULL,
+                  operation text NOT NULL CHECK (operation IN ('insert', 'update', 'delete')),
+                  row_key text NOT NULL,
+                  PRIMARY KEY (hunk_id, ordinal)
+                ) WITH (
+                  OIDS=FALSE
+                );
+            "#, rows_table(artifact)))?;
+
+        Ok(())
+    }
+}
+
+fn write_rows<'a, I>(
+    trans: &Transaction,
+    hunk: &Hunk,
+    schema: &TableSchema,
+    rows: I,
+) -> Result<(), Error>
+        where I: Iterator<Item = (&'static str, &'a String, Option<&'a Row>)> {
+
+    validate_column_names(schema)?;
+    let table = rows_table(hunk.version.artifact);
+
+    trans.execute(r#"
+            INSERT INTO table_dtype_schema (hunk_id, schema)
+            SELECT h.id, r.schema
+            FROM (VALUES ($1::uuid, $2::bigint, $3::jsonb))
+              AS r (uuid_, hash, schema)
+            JOIN hunk h
+              ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+        "#, &[&hunk.id.uuid, &(hunk.id.hash as i64),
+              &serde_json::to_value(schema).map_err(|e| Error::Store(e.to_string()))?])?;
+
+    add_schema_columns(trans, &table, schema)?;
+
+    let insert_row = trans.prepare(&format!(r#"
+            INSERT INTO {} (hunk_id, ordinal, operation, row_key{})
+            SELECT h.id, $3::integer, $4::text, $5::text{}
+            FROM hunk h
+            WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+        "#,
+        table,
+        schema.columns.iter().map(|c| format!(", {}", column_ident(c))).collect::<String>(),
+        schema.columns.iter().enumerate()
+            .map(|(i, c)| format!(", ${}::{}", i + 6, sql_type(c.column_type)))
+            .collect::<String>()))?;
+    let delete_row = trans.prepare(&format!(r#"
+            INSERT INTO {} (hunk_id, ordinal, operation, row_key)
+            SELECT h.id, $3::integer, $4::text, $5::text
+            FROM hunk h
+            WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+        "#, table))?;
+
+    let hash = hunk.id.hash as i64;
+    for (ordinal, (operation, key, row)) in rows.enumerate() {
+        let ordinal = ordinal as i32;
+        let operation = operation.to_owned();
+        match row {
+            Some(row) => {
+                if !schema.is_valid_row(row) {
+                    return Err(Error::Store(format!("Row {} does not conform to table schema", key)));
+                }
+                let values = schema.columns.iter().zip(row.iter())
+                    .map(|(column, value)| cell_to_sql(column, value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut params: Vec<&dyn ToSql> = vec![&hunk.id.uuid, &hash, &ordinal, &operation, key];
+                params.extend(values.iter().map(|v| &**v));
+                insert_row.execute(&params)?;
+            },
+            None => {
+                delete_row.execute(&[&hunk.id.uuid, &hash, &ordinal, &operation, key])?;
+            },
+        }
+    }
+
+    Ok(())
+}
+
+impl crate::datatype::Storage for TableDatatypeBackend<PostgresRepository> {
+    fn write_hunk(
+        &mut self,
+        repo: &Repository,
+        hunk: &Hunk,
+        payload: &Payload<Self::StateType, Self::DeltaType>,
+    ) -> Result<(), Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        match hunk.representation {
+            RepresentationKind::State =>
+                match *payload {
+                    Payload::State(TableState {ref schema, ref rows}) => {
+                        write_rows(&trans, hunk, schema, rows.iter()
+                            .map(|(key, row)| ("insert", key, Some(row))))?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+                },
+            RepresentationKind::Delta =>
+                match *payload {
+                    Payload::Delta(TableDelta {ref schema, ref operations}) => {
+                        write_rows(&trans, hunk, schema, operations.iter()
+                            .map(|operation| match operation {
+                                RowOperation::Insert(key, row) => ("insert", key, Some(row)),
+                                RowOperation::Update(key, row) => ("update", key, Some(row)),
+                                RowOperation::Delete(key) => ("delete", key, None),
+                            }))?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+                },
+            _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+        }
+
+        trans.set_commit();
+        Ok(())
+    }
+
+    fn read_hunk(
+        &self,
+        repo: &Repository,
+        hunk: &Hunk,
+    ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let schema_rows = trans.query(r#"
+                SELECT s.schema
+                FROM table_dtype_schema s
+                JOIN hunk h
+                  ON (h.id = s.hunk_id)
+                WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+        let schema: TableSchema = serde_json::from_value(schema_rows.get(0).get(0))
+            .map_err(|e| Error::Store(e.to_string()))?;
+
+        let rows = trans.query(&format!(r#"
+                SELECT r.operation, r.row_key{}
+                FROM {} r
+                JOIN hunk h
+                  ON (h.id = r.hunk_id)
+                WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint
+                ORDER BY r.ordinal ASC;
+            "#,
+            schema.columns.iter().map(|c| format!(", r.{}", column_ident(c))).collect::<String>(),
+            rows_table(hunk.version.artifact)),
+            &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+        let operations = rows.iter().map(|row| {
+            let operation: String = row.get(0);
+            let key: String = row.get(1);
+            let values = || schema.columns.iter().enumerate()
+                .map(|(i, column)| cell_from_sql(&row, i + 2, column))
+                .collect::<Row>();
+            match operation.as_str() {
+                "insert" => RowOperation::Insert(key, values()),
+                "update" => RowOperation::Update(key, values()),
+                _ => RowOperation::Delete(key),
+            }
+        }).collect::<Vec<_>>();
+
+        let payload = match hunk.representation {
+            RepresentationKind::State => {
+                let rows = operations.into_iter().filter_map(|operation| match operation {
+                        RowOperation::Insert(key, row) => Some((key, row)),
+                        _ => None,
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                Payload::State(TableState {schema, rows})
+            },
+            RepresentationKind::Delta => Payload::Delta(TableDelta {schema, operations}),
+            _ => return Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+        };
+
+        Ok(payload)
+    }
+}
+
+impl Storage for TableDatatypeBackend<PostgresRepository> {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_column_names() {
+        let column = |name: String| Column {name, column_type: ColumnType::Text, nullable: true};
+
+        let fits = "a".repeat(MAX_IDENTIFIER_BYTES - "__text".len());
+        assert!(validate_column_names(&TableSchema {columns: vec![column(fits.clone())]}).is_ok());
+
+        let too_long = format!("{}a", fits);
+        assert!(validate_column_names(&TableSchema {columns: vec![column(too_long)]}).is_err(),
+            "Column name which would be truncated was accepted");
+    }
+}
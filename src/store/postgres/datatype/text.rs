@@ -0,0 +1,159 @@
+use std::borrow::Borrow;
+
+use heraclitus_core::{
+    postgres,
+    schemer,
+    schemer_postgres,
+};
+use postgres::error::Error as PostgresError;
+use postgres::transaction::Transaction;
+use schemer::migration;
+use schemer_postgres::{PostgresAdapter, PostgresMigration};
+
+use crate::{
+    RepresentationKind,
+    Error,
+    Hunk,
+};
+use crate::datatype::{
+    Payload,
+};
+use crate::datatype::text::{
+    LineHunk,
+    TextDatatypeBackend,
+    Storage,
+};
+use crate::repo::Repository;
+use crate::store::postgres::{PostgresMigratable, PostgresRepository};
+
+
+struct PGMigrationText;
+migration!(
+    PGMigrationText,
+    "1b666e34-1d0d-4bf9-8b1d-fc51febba28b",
+    ["7d1fb6d1-a1b0-4bd4-aa6d-e3ee71c4353b",],
+    "create text tables");
+
+impl PostgresMigration for PGMigrationText {
+    fn up(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/text_0001.up.sql"))
+    }
+
+    fn down(&self, transaction: &Transaction) -> Result<(), PostgresError> {
+        transaction.batch_execute(include_str!("sql/text_0001.down.sql"))
+    }
+}
+
+
+impl PostgresMigratable for TextDatatypeBackend<PostgresRepository> {
+    fn migrations(&self) -> Vec<Box<<PostgresAdapter as schemer::Adapter>::MigrationType>> {
+        vec![
+            Box::new(PGMigrationText),
+        ]
+    }
+}
+
+impl super::PostgresMetaController for TextDatatypeBackend<PostgresRepository> {}
+
+impl crate::datatype::Storage for TextDatatypeBackend<PostgresRepository> {
+    fn write_hunk(
+        &mut self,
+        repo: &Repository,
+        hunk: &Hunk,
+        payload: &Payload<Self::StateType, Self::DeltaType>,
+    ) -> Result<(), Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        match hunk.representation {
+            RepresentationKind::State =>
+                match *payload {
+                    Payload::State(ref text) => {
+                        trans.execute(r#"
+                                INSERT INTO text_dtype_state (hunk_id, text)
+                                SELECT h.id, r.text
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::text))
+                                  AS r (uuid_, hash, text)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#, &[&hunk.id.uuid, &(hunk.id.hash as i64), &text])?;
+                    }
+                    _ => return Err(Error::Store("Attempt to write state hunk with non-state payload".into())),
+                },
+            RepresentationKind::Delta =>
+                match *payload {
+                    Payload::Delta(ref line_hunks) => {
+                        let insert_line_hunk = trans.prepare(r#"
+                                INSERT INTO text_dtype_delta (hunk_id, ordinal, start_line, removed, inserted)
+                                SELECT h.id, r.ordinal, r.start_line, r.removed, r.inserted
+                                FROM (VALUES ($1::uuid, $2::bigint, $3::integer, $4::bigint, $5::bigint, $6::text[]))
+                                  AS r (uuid_, hash, ordinal, start_line, removed, inserted)
+                                JOIN hunk h
+                                  ON (h.uuid_ = r.uuid_ AND h.hash = r.hash);
+                            "#)?;
+                        for (ordinal, line_hunk) in line_hunks.iter().enumerate() {
+                            insert_line_hunk.execute(&[
+                                &hunk.id.uuid,
+                                &(hunk.id.hash as i64),
+                                &(ordinal as i32),
+                                &(line_hunk.start as i64),
+                                &(line_hunk.removed as i64),
+                                &line_hunk.inserted,
+                            ])?;
+                        }
+                    }
+                    _ => return Err(Error::Store("Attempt to write delta hunk with non-delta payload".into())),
+                },
+            _ => return Err(Error::Store("Attempt to write a hunk with an unsupported representation".into())),
+        }
+
+        trans.set_commit();
+        Ok(())
+    }
+
+    fn read_hunk(
+        &self,
+        repo: &Repository,
+        hunk: &Hunk,
+    ) -> Result<Payload<Self::StateType, Self::DeltaType>, Error> {
+        let rc: &PostgresRepository = repo.borrow();
+
+        let conn = rc.conn()?;
+        let trans = conn.transaction()?;
+
+        let payload = match hunk.representation {
+            RepresentationKind::State => {
+                let text_rows = trans.query(r#"
+                        SELECT t.text
+                        FROM text_dtype_state t
+                        JOIN hunk h
+                          ON (h.id = t.hunk_id)
+                        WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint;
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                Payload::State(text_rows.get(0).get(0))
+            },
+            RepresentationKind::Delta => {
+                let line_hunk_rows = trans.query(r#"
+                        SELECT t.start_line, t.removed, t.inserted
+                        FROM text_dtype_delta t
+                        JOIN hunk h
+                          ON (h.id = t.hunk_id)
+                        WHERE h.uuid_ = $1::uuid AND h.hash = $2::bigint
+                        ORDER BY t.ordinal ASC;
+                    "#, &[&hunk.id.uuid, &(hunk.id.hash as i64)])?;
+                Payload::Delta(line_hunk_rows.iter().map(|row| LineHunk {
+                    start: row.get::<_, i64>(0) as usize,
+                    removed: row.get::<_, i64>(1) as usize,
+                    inserted: row.get(2),
+                }).collect())
+            },
+            _ => return Err(Error::Store("Attempt to read a hunk with an unsupported representation".into())),
+        };
+
+        Ok(payload)
+    }
+}
+
+impl Storage for TextDatatypeBackend<PostgresRepository> {}
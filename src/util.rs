@@ -12,7 +12,7 @@ pub mod petgraph {
         VisitMap,
     };
 
-    use crate::Error;
+    use crate::{Error, ModelError};
 
     /// Perform a topological sort of a node's induced stream in a directed graph.
     ///
@@ -54,7 +54,7 @@ pub mod petgraph {
                                 }) {
                             if succ == nx {
                                 // self cycle
-                                return Err(Error::TODO("cycle"));
+                                return Err(Error::Model(ModelError::Cycle));
                             }
                             if !dfs.discovered.is_visited(&succ) {
                                 dfs.stack.push(succ);